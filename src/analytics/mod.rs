@@ -4,19 +4,27 @@
 //!
 //! These functions are used by the GUI and `music-stats` CLI binary
 //! through the `Database` wrapper methods.
+//!
+//! Timestamps are stored in UTC. Day and hour buckets are in local time,
+//! either where each play happened or in the current zone, see [`LocalTime`].
+//! Date ranges are matched against the same local time, so a range holds the
+//! plays of the days it covers. Days start at a configurable time, so a late
+//! night counts as one day.
 
 // These types and functions are public API for binaries, not dead code
 #![allow(dead_code)]
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveTime};
 use duckdb::Connection;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::db::DateFilter;
+use crate::date_range;
+use crate::db::{ContentFilter, DateFilter, LocalTime};
 use crate::error::Result;
 
 /// Streak information
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StreakInfo {
     pub current_streak: i32,
     pub longest_streak: i32,
@@ -25,7 +33,7 @@ pub struct StreakInfo {
 }
 
 /// Session information
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SessionInfo {
     pub total_sessions: i32,
     pub avg_session_minutes: f64,
@@ -34,7 +42,7 @@ pub struct SessionInfo {
 }
 
 /// Night owl score
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NightOwlScore {
     pub percentage: f64,
     pub night_plays: i64,
@@ -42,25 +50,29 @@ pub struct NightOwlScore {
 }
 
 /// Hourly heatmap data
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HourlyHeatmap {
     pub hours: HashMap<i32, i64>,
     pub peak_hour: i32,
     pub peak_count: i64,
 }
 
-/// Get listening streaks
+/// Get listening streaks, in days starting at `day_start`
 pub fn get_listening_streaks(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
+    day_start: NaiveTime,
 ) -> Result<StreakInfo> {
-    // DuckDB uses CAST to DATE or strftime for date extraction
-    let mut query =
-        "SELECT DISTINCT CAST(timestamp AS DATE) as play_date FROM plays WHERE 1=1".to_string();
+    let day = local_time.day_sql(conn, day_start)?;
+    let mut query = format!("SELECT DISTINCT {day} as play_date FROM plays WHERE 1=1");
     let mut params = Vec::new();
 
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut params);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut params);
+    content.apply(&mut query);
 
     query.push_str(" ORDER BY play_date ASC");
 
@@ -112,7 +124,7 @@ pub fn get_listening_streaks(
         .unwrap_or((streak_start, streak_end, 1));
 
     // Current streak (if ends today or yesterday)
-    let today = chrono::Local::now().date_naive();
+    let today = date_range::current_day(day_start);
     let current_streak = if let Some((_, end, len)) = streaks.last() {
         let days_ago = (today - *end).num_days();
         if days_ago <= 1 {
@@ -137,11 +149,15 @@ pub fn get_night_owl_score(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
 ) -> Result<NightOwlScore> {
+    let local = local_time.timestamp_sql(conn)?;
     let mut base_query = "SELECT COUNT(*) FROM plays WHERE 1=1".to_string();
     let mut params = Vec::new();
 
-    DateFilter::new(start_date, end_date).apply(&mut base_query, &mut params);
+    DateFilter::new(start_date, end_date).apply(&local, &mut base_query, &mut params);
+    content.apply(&mut base_query);
 
     let param_refs = DateFilter::params_as_refs(&params);
 
@@ -154,9 +170,7 @@ pub fn get_night_owl_score(
     }
 
     // Night plays (10 PM to 4 AM = hours 22, 23, 0, 1, 2, 3)
-    let night_query = format!(
-        "{base_query} AND hour_of_day IS NOT NULL AND (hour_of_day >= 22 OR hour_of_day < 4)"
-    );
+    let night_query = format!("{base_query} AND (hour({local}) >= 22 OR hour({local}) < 4)");
     let mut stmt = conn.prepare(&night_query)?;
     let night_plays: i64 = stmt.query_row(param_refs.as_slice(), |row| row.get(0))?;
 
@@ -174,14 +188,18 @@ pub fn get_hourly_heatmap(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
 ) -> Result<HourlyHeatmap> {
+    let local = local_time.timestamp_sql(conn)?;
     let mut query =
-        "SELECT hour_of_day, COUNT(*) FROM plays WHERE hour_of_day IS NOT NULL".to_string();
+        format!("SELECT CAST(hour({local}) AS INTEGER) as hour, COUNT(*) FROM plays WHERE 1=1");
     let mut params = Vec::new();
 
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut params);
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut params);
+    content.apply(&mut query);
 
-    query.push_str(" GROUP BY hour_of_day ORDER BY hour_of_day");
+    query.push_str(" GROUP BY hour ORDER BY hour");
 
     let param_refs = DateFilter::params_as_refs(&params);
     let mut stmt = conn.prepare(&query)?;
@@ -217,6 +235,8 @@ pub fn get_genre_stats(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
     limit: u32,
 ) -> Result<Vec<(String, i64, i64)>> {
     let mut query = r"
@@ -227,7 +247,9 @@ pub fn get_genre_stats(
     .to_string();
 
     let mut params = Vec::new();
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut params);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut params);
+    content.apply(&mut query);
 
     query.push_str(&format!(
         " GROUP BY genre ORDER BY play_count DESC LIMIT {}",
@@ -251,41 +273,87 @@ pub fn get_genre_stats(
     Ok(stats)
 }
 
-/// Get skip rate (plays < 50% completion)
+/// Get skip rate (started tracks the user skipped before they counted as a play).
+///
+/// Every started track ends up either in `plays` or in `skips`, so the total is
+/// the sum of both. Auto-advances, closed players and interrupted listens are
+/// not counted as skips.
 pub fn get_skip_rate(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
 ) -> Result<(i64, i64, f64)> {
-    let mut base_where = "WHERE duration_ms > 0 AND played_ms IS NOT NULL".to_string();
+    let mut date_conditions = String::new();
     let mut params = Vec::new();
 
-    DateFilter::new(start_date, end_date).apply(&mut base_where, &mut params);
+    // Skips store offsets the same way plays do
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut date_conditions, &mut params);
+    content.apply(&mut date_conditions);
 
+    // Each subquery applies the same date filter
+    let params = [params.clone(), params.clone(), params].concat();
     let param_refs = DateFilter::params_as_refs(&params);
 
-    // Total plays with duration
-    let total_query = format!("SELECT COUNT(*) FROM plays {base_where}");
-    let mut stmt = conn.prepare(&total_query)?;
-    let total: i64 = stmt.query_row(param_refs.as_slice(), |row| row.get(0))?;
+    let query = format!(
+        r"
+        SELECT
+            (SELECT COUNT(*) FROM skips WHERE reason = 'skipped' {date_conditions}),
+            (SELECT COUNT(*) FROM plays WHERE 1=1 {date_conditions})
+                + (SELECT COUNT(*) FROM skips WHERE 1=1 {date_conditions})
+        "
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let (skipped, total): (i64, i64) =
+        stmt.query_row(param_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
 
     if total == 0 {
         return Ok((0, 0, 0.0));
     }
 
-    // Skipped plays (< 50% completion)
-    let skip_query =
-        format!("SELECT COUNT(*) FROM plays {base_where} AND (played_ms * 1.0 / duration_ms) < 0.5");
-    let mut stmt = conn.prepare(&skip_query)?;
-    let skipped: i64 = stmt.query_row(param_refs.as_slice(), |row| row.get(0))?;
-
     let rate = (skipped as f64 / total as f64) * 100.0;
 
     Ok((skipped, total, rate))
 }
 
+/// Get listening session statistics
+pub fn get_session_stats(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<SessionInfo> {
+    let mut query = r"
+        SELECT
+            COUNT(*)::INTEGER,
+            COALESCE(AVG(epoch(end_time - start_time)), 0) / 60.0,
+            COALESCE(MAX(epoch(end_time - start_time)), 0) / 60.0,
+            COALESCE(SUM(total_ms), 0) / 60000.0
+        FROM sessions
+        WHERE end_time IS NOT NULL
+    "
+    .to_string();
+    let mut params = Vec::new();
+
+    DateFilter::new(start_date, end_date).apply_to_column("start_time", &mut query, &mut params);
+
+    let param_refs = DateFilter::params_as_refs(&params);
+    let mut stmt = conn.prepare(&query)?;
+    let info = stmt.query_row(param_refs.as_slice(), |row| {
+        Ok(SessionInfo {
+            total_sessions: row.get(0)?,
+            avg_session_minutes: row.get(1)?,
+            longest_session_minutes: row.get(2)?,
+            total_listening_minutes: row.get(3)?,
+        })
+    })?;
+
+    Ok(info)
+}
+
 /// Daily contribution data
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DailyContribution {
     /// Map of date string (YYYY-MM-DD) to play count
     pub days: std::collections::HashMap<String, i64>,
@@ -295,19 +363,24 @@ pub struct DailyContribution {
     pub total_plays: i64,
 }
 
-/// Get daily play counts for the contribution graph
+/// Get daily play counts for the contribution graph, in days starting at
+/// `day_start`
 pub fn get_daily_contributions(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
+    day_start: NaiveTime,
 ) -> Result<DailyContribution> {
-    // DuckDB uses CAST or strftime for date extraction
+    let day = local_time.day_sql(conn, day_start)?;
     let mut query =
-        "SELECT CAST(timestamp AS DATE) as play_date, COUNT(*) as count FROM plays WHERE 1=1"
-            .to_string();
+        format!("SELECT {day} as play_date, COUNT(*) as count FROM plays WHERE 1=1");
     let mut params = Vec::new();
 
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut params);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut params);
+    content.apply(&mut query);
 
     query.push_str(" GROUP BY play_date ORDER BY play_date");
 
@@ -336,3 +409,257 @@ pub fn get_daily_contributions(
         total_plays,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        schema::migrate(&conn, None).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_skip_rate_counts_skips_table() {
+        let conn = test_conn();
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, duration_ms, played_ms) VALUES ('a', 200000, 180000);
+            INSERT INTO plays (title, duration_ms, played_ms) VALUES ('b', 200000, 150000);
+            INSERT INTO skips (title, played_ms, reason) VALUES ('c', 5000, 'skipped');
+            INSERT INTO skips (title, played_ms, reason) VALUES ('d', 20000, 'auto_advanced');
+            ",
+        )
+        .unwrap();
+
+        let (skipped, total, rate) = get_skip_rate(
+            &conn,
+            None,
+            None,
+            ContentFilter::MusicOnly,
+            LocalTime::WherePlayed,
+        )
+        .unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(total, 4);
+        assert!((rate - 25.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_content_filter_defaults_to_music() {
+        let conn = test_conn();
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, played_ms) VALUES ('old', 180000);
+            INSERT INTO plays (title, played_ms, content_type) VALUES ('song', 180000, 'music');
+            INSERT INTO plays (title, played_ms, content_type) VALUES ('clip', 180000, 'video');
+            INSERT INTO skips (title, played_ms, reason, content_type)
+            VALUES ('episode', 5000, 'skipped', 'podcast');
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_skip_rate(
+                &conn,
+                None,
+                None,
+                ContentFilter::MusicOnly,
+                LocalTime::WherePlayed,
+            )
+            .unwrap(),
+            (0, 2, 0.0)
+        );
+        let (skipped, total, _) =
+            get_skip_rate(&conn, None, None, ContentFilter::All, LocalTime::WherePlayed).unwrap();
+        assert_eq!((skipped, total), (1, 4));
+    }
+
+    #[test]
+    fn test_session_stats() {
+        let conn = test_conn();
+        conn.execute_batch(
+            r"
+            INSERT INTO sessions (id, start_time, end_time, track_count, total_ms)
+            VALUES ('a', '2024-01-01 10:00:00', '2024-01-01 10:30:00', 8, 1500000);
+            INSERT INTO sessions (id, start_time, end_time, track_count, total_ms)
+            VALUES ('b', '2024-01-02 20:00:00', '2024-01-02 21:30:00', 20, 5100000);
+            ",
+        )
+        .unwrap();
+
+        let info = get_session_stats(&conn, None, None).unwrap();
+        assert_eq!(info.total_sessions, 2);
+        assert!((info.avg_session_minutes - 60.0).abs() < 1e-9);
+        assert!((info.longest_session_minutes - 90.0).abs() < 1e-9);
+        assert!((info.total_listening_minutes - 110.0).abs() < 1e-9);
+
+        let info = get_session_stats(&conn, Some("2024-01-02"), None).unwrap();
+        assert_eq!(info.total_sessions, 1);
+    }
+
+    #[test]
+    fn test_skip_rate_empty() {
+        let conn = test_conn();
+        assert_eq!(
+            get_skip_rate(
+                &conn,
+                None,
+                None,
+                ContentFilter::MusicOnly,
+                LocalTime::WherePlayed,
+            )
+            .unwrap(),
+            (0, 0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_buckets_follow_where_each_play_happened() {
+        use chrono::{Local, NaiveDateTime, TimeZone, Timelike};
+
+        let conn = test_conn();
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('tokyo', '2024-07-15 20:30:00', 540);
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('new york', '2024-07-16 02:30:00', -240);
+            ",
+        )
+        .unwrap();
+
+        let heatmap =
+            get_hourly_heatmap(&conn, None, None, ContentFilter::All, LocalTime::WherePlayed)
+                .unwrap();
+        assert_eq!(heatmap.hours, HashMap::from([(5, 1), (22, 1)]));
+        let days =
+            get_daily_contributions(
+                &conn,
+                None,
+                None,
+                ContentFilter::All,
+                LocalTime::WherePlayed,
+                NaiveTime::MIN,
+            )
+            .unwrap()
+            .days;
+        assert_eq!(
+            days,
+            HashMap::from([("2024-07-16".to_string(), 1), ("2024-07-15".to_string(), 1)])
+        );
+
+        // Six hours apart wherever we are now
+        let heatmap =
+            get_hourly_heatmap(&conn, None, None, ContentFilter::All, LocalTime::CurrentZone)
+                .unwrap();
+        let hour = |utc: &str| {
+            let utc = NaiveDateTime::parse_from_str(utc, "%Y-%m-%d %H:%M:%S").unwrap();
+            i32::try_from(Local.from_utc_datetime(&utc).hour()).unwrap()
+        };
+        let mut expected = HashMap::new();
+        for utc in ["2024-07-15 20:30:00", "2024-07-16 02:30:00"] {
+            *expected.entry(hour(utc)).or_insert(0) += 1;
+        }
+        assert_eq!(heatmap.hours, expected);
+    }
+
+    #[test]
+    fn test_date_range_holds_the_days_plays_count_toward() {
+        let conn = test_conn();
+        // Late on the 15th and early on the 16th in Tokyo, both the 15th in UTC
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('late', '2024-07-15 14:30:00', 540);
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('early', '2024-07-15 16:30:00', 540);
+            ",
+        )
+        .unwrap();
+        let days = |start, end| {
+            get_daily_contributions(
+                &conn,
+                Some(start),
+                Some(end),
+                ContentFilter::All,
+                LocalTime::WherePlayed,
+                NaiveTime::MIN,
+            )
+            .unwrap()
+            .days
+        };
+
+        assert_eq!(
+            days("2024-07-15", "2024-07-15 23:59:59"),
+            HashMap::from([("2024-07-15".to_string(), 1)])
+        );
+        assert_eq!(
+            days("2024-07-16", "2024-07-16 23:59:59"),
+            HashMap::from([("2024-07-16".to_string(), 1)])
+        );
+        let (_, total, _) = get_skip_rate(
+            &conn,
+            Some("2024-07-16"),
+            Some("2024-07-16 23:59:59"),
+            ContentFilter::All,
+            LocalTime::WherePlayed,
+        )
+        .unwrap();
+        assert_eq!(total, 1);
+    }
+
+    #[test]
+    fn test_days_start_at_day_start() {
+        let conn = test_conn();
+        // One evening, and the small hours after the next evening
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('evening', '2024-03-01 22:00:00', 0);
+            INSERT INTO plays (title, timestamp, utc_offset_minutes)
+            VALUES ('small hours', '2024-03-03 01:30:00', 0);
+            ",
+        )
+        .unwrap();
+        let four = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let days = |day_start| {
+            get_daily_contributions(
+                &conn,
+                None,
+                None,
+                ContentFilter::All,
+                LocalTime::WherePlayed,
+                day_start,
+            )
+            .unwrap()
+            .days
+        };
+        let streak = |day_start| {
+            get_listening_streaks(
+                &conn,
+                None,
+                None,
+                ContentFilter::All,
+                LocalTime::WherePlayed,
+                day_start,
+            )
+            .unwrap()
+            .longest_streak
+        };
+
+        assert_eq!(
+            days(NaiveTime::MIN),
+            HashMap::from([("2024-03-01".to_string(), 1), ("2024-03-03".to_string(), 1)])
+        );
+        assert_eq!(streak(NaiveTime::MIN), 1);
+
+        assert_eq!(
+            days(four),
+            HashMap::from([("2024-03-01".to_string(), 1), ("2024-03-02".to_string(), 1)])
+        );
+        assert_eq!(streak(four), 2);
+    }
+}
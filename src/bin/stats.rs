@@ -5,7 +5,7 @@
 use clap::Parser;
 use music_analytics::{
     config::Config,
-    db::Database,
+    db::{ContentFilter, Database, LocalTime},
     display::{
         build_date_range, display_overview, display_top_albums, display_top_artists,
         display_top_tracks, make_bar, print_section,
@@ -48,6 +48,15 @@ struct Args {
     /// Number of items to show in top lists
     #[arg(short, long, default_value = "10")]
     limit: u32,
+
+    /// Include podcasts, audiobooks and video (default: music only)
+    #[arg(long)]
+    all_content: bool,
+
+    /// Count days and hours in the current time zone (default: where each
+    /// play happened)
+    #[arg(long)]
+    current_zone: bool,
 }
 
 #[tokio::main]
@@ -62,12 +71,16 @@ async fn main() -> Result<()> {
     };
 
     // Initialize database
-    let data_dir = config.data_dir()?;
-    let db = Database::new(&config.database, &data_dir).await?;
+    let day_start = config.general.day_start()?;
+    let db = Database::open_read_only(&config.database_path()?)
+        .await?
+        .with_content_filter(ContentFilter::new(args.all_content))
+        .with_local_time(LocalTime::new(args.current_zone))
+        .with_day_start(day_start);
 
     // Determine date range
     let (start_date, end_date, period_name) =
-        build_date_range(args.all_time, args.week, args.month, args.year);
+        build_date_range(args.all_time, args.week, args.month, args.year, day_start);
 
     // Display header
     println!("\n{}", "*".repeat(50));
@@ -113,35 +126,7 @@ async fn main() -> Result<()> {
 
     // Advanced analytics
     if args.deep || args.full {
-        print_section("LISTENING PATTERNS");
-
-        // Streaks
-        let streaks = db
-            .get_listening_streaks(start_date.as_deref(), end_date.as_deref())
-            .await?;
-        println!("  Current streak:   {} days", streaks.current_streak);
-        println!("  Longest streak:   {} days", streaks.longest_streak);
-        if let (Some(start), Some(end)) = (&streaks.longest_streak_start, &streaks.longest_streak_end)
-        {
-            println!("    ({start} to {end})");
-        }
-
-        // Night owl
-        let night_owl = db
-            .get_night_owl_score(start_date.as_deref(), end_date.as_deref())
-            .await?;
-        println!(
-            "  Night owl score:  {:.1}% ({} plays midnight-6am)",
-            night_owl.percentage, night_owl.night_plays
-        );
-
-        // Skip rate
-        let (skipped, total, rate) = db
-            .get_skip_rate(start_date.as_deref(), end_date.as_deref())
-            .await?;
-        if total > 0 {
-            println!("  Skip rate:        {rate:.1}% ({skipped}/{total})");
-        }
+        display_patterns(&db, start_date.as_deref(), end_date.as_deref()).await?;
 
         // Hourly heatmap
         print_section("LISTENING BY TIME");
@@ -180,3 +165,44 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Print streaks, late-night listening, skips and sessions
+async fn display_patterns(
+    db: &Database,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<()> {
+    print_section("LISTENING PATTERNS");
+
+    // Streaks
+    let streaks = db.get_listening_streaks(start_date, end_date).await?;
+    println!("  Current streak:   {} days", streaks.current_streak);
+    println!("  Longest streak:   {} days", streaks.longest_streak);
+    if let (Some(start), Some(end)) = (&streaks.longest_streak_start, &streaks.longest_streak_end) {
+        println!("    ({start} to {end})");
+    }
+
+    // Night owl
+    let night_owl = db.get_night_owl_score(start_date, end_date).await?;
+    println!(
+        "  Night owl score:  {:.1}% ({} plays midnight-6am)",
+        night_owl.percentage, night_owl.night_plays
+    );
+
+    // Skip rate
+    let (skipped, total, rate) = db.get_skip_rate(start_date, end_date).await?;
+    if total > 0 {
+        println!("  Skip rate:        {rate:.1}% ({skipped}/{total})");
+    }
+
+    // Sessions
+    let sessions = db.get_session_stats(start_date, end_date).await?;
+    if sessions.total_sessions > 0 {
+        println!(
+            "  Sessions:         {} (avg {:.0} min, longest {:.0} min)",
+            sessions.total_sessions, sessions.avg_session_minutes, sessions.longest_session_minutes
+        );
+    }
+
+    Ok(())
+}
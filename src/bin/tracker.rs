@@ -23,6 +23,10 @@ struct Args {
     /// Run in foreground (default)
     #[arg(short, long)]
     foreground: bool,
+
+    /// Write every listen event to this file, for replaying later
+    #[arg(long, value_name = "FILE")]
+    record: Option<std::path::PathBuf>,
 }
 
 #[tokio::main]
@@ -47,6 +51,10 @@ async fn main() -> Result<()> {
     } else {
         Config::load()?
     };
+    let config_path = match args.config {
+        Some(path) => path,
+        None => Config::config_path()?,
+    };
 
     tracing::info!("Music tracker starting...");
 
@@ -54,19 +62,23 @@ async fn main() -> Result<()> {
     let data_dir = config.data_dir()?;
     std::fs::create_dir_all(&data_dir)?;
 
-    let db = Database::new(&config.database, &data_dir).await?;
-    tracing::info!("Database initialized at {:?}", config.database_path()?);
+    let db_path = config.database_path()?;
+    let db = Database::new(&db_path).await?;
+    tracing::info!("Database initialized at {:?}", db_path);
 
     // Create MPRIS monitor
-    let monitor = MprisMonitor::new(
-        config.players.clone(),
-        config.tracking.clone(),
-        db,
-    )
-    .await?;
+    let mut monitor = MprisMonitor::new(&config, db, &data_dir)
+        .await?
+        .with_config_path(config_path);
+    if let Some(path) = &args.record {
+        monitor = monitor.with_recording(path)?;
+    }
 
     // Handle shutdown signals
     let monitor = std::sync::Arc::new(monitor);
+
+    // Publish status and controls on the session bus
+    monitor.serve().await?;
     let monitor_clone = monitor.clone();
 
     tokio::spawn(async move {
@@ -0,0 +1,124 @@
+//! Time sources for listen tracking
+//!
+//! Listens measure play time against a [`Clock`] rather than reading the
+//! system time directly, so tests and replays can step time themselves.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+/// Source of monotonic and wall clock time
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Monotonic time, for measuring how long things take
+    fn now(&self) -> Instant;
+
+    /// Wall clock time, for timestamps
+    fn wall(&self) -> DateTime<Local>;
+}
+
+/// Clock shared between the listens that read it
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn wall(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Shared handle to the system clock
+#[must_use]
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Clock that only moves when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    start_wall: DateTime<Local>,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Create a clock reading `start_wall`
+    #[must_use]
+    pub fn new(start_wall: DateTime<Local>) -> Arc<Self> {
+        Arc::new(Self {
+            start: Instant::now(),
+            start_wall,
+            elapsed: Mutex::new(Duration::ZERO),
+        })
+    }
+
+    /// Time since the clock was created
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        *self
+            .elapsed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Move the clock forward by `duration`
+    #[cfg(test)]
+    pub fn advance(&self, duration: Duration) {
+        *self
+            .elapsed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) += duration;
+    }
+
+    /// Set the time since the clock was created; it never moves backwards
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        let mut current = self
+            .elapsed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *current = (*current).max(elapsed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn wall(&self) -> DateTime<Local> {
+        chrono::Duration::from_std(self.elapsed())
+            .ok()
+            .and_then(|elapsed| self.start_wall.checked_add_signed(elapsed))
+            .unwrap_or(self.start_wall)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let start_wall = Local::now();
+        let clock = ManualClock::new(start_wall);
+        let start = clock.now();
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+        assert_eq!(clock.wall() - start_wall, chrono::Duration::seconds(30));
+
+        // Never goes backwards
+        clock.set_elapsed(Duration::from_secs(10));
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+        clock.set_elapsed(Duration::from_secs(45));
+        assert_eq!(clock.elapsed(), Duration::from_secs(45));
+    }
+}
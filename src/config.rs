@@ -1,6 +1,8 @@
 //! Configuration management for music-analytics
 
+use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 
 use crate::error::{Error, Result};
@@ -20,6 +22,15 @@ pub struct Config {
 
     /// Player filtering
     pub players: PlayerConfig,
+
+    /// Internet radio handling
+    pub radio: RadioConfig,
+
+    /// Rules applied to plays before they are logged
+    pub rules: Vec<RuleConfig>,
+
+    /// Private listening
+    pub private: PrivateConfig,
 }
 
 /// General application settings
@@ -31,6 +42,10 @@ pub struct GeneralConfig {
 
     /// Data directory (default: ~/.local/share/music-analytics)
     pub data_dir: Option<PathBuf>,
+
+    /// Time the day starts for streaks and daily stats, `"HH:MM"`; listens
+    /// before it count toward the day before
+    pub day_starts_at: String,
 }
 
 /// Database configuration
@@ -63,8 +78,14 @@ pub struct TrackingConfig {
     /// Track listening context (time, active window, etc.)
     pub track_context: bool,
 
+    /// Seconds between MPRIS `Position` samples used to measure heard audio (0 = only on seeks)
+    pub position_poll_seconds: u64,
+
     /// Idle timeout in seconds (0 = never exit)
     pub idle_timeout_seconds: u64,
+
+    /// Seconds with nothing playing after which a listening session ends
+    pub session_gap_seconds: u64,
 }
 
 /// Player filtering configuration
@@ -79,6 +100,87 @@ pub struct PlayerConfig {
 
     /// Known local-only players (these don't stream)
     pub local_only_players: Vec<String>,
+
+    /// Players that re-export another player (e.g. playerctld); their listens
+    /// are dropped while the original player is tracked
+    pub proxy_players: Vec<String>,
+
+    /// Tracking settings for specific players, matched like `whitelist`
+    pub overrides: BTreeMap<String, TrackingOverride>,
+}
+
+/// Tracking settings overridden for one player; unset fields use `[tracking]`.
+///
+/// `idle_timeout_seconds` and `session_gap_seconds` apply to the whole
+/// tracker and can't be overridden.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackingOverride {
+    pub min_play_seconds: Option<u64>,
+    pub min_play_percent: Option<f64>,
+    pub local_only: Option<bool>,
+    pub track_seeks: Option<bool>,
+    pub track_volume: Option<bool>,
+    pub track_context: Option<bool>,
+    pub position_poll_seconds: Option<u64>,
+}
+
+/// Internet radio configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RadioConfig {
+    /// Split radio streams into per-song plays (logged even with `local_only`)
+    pub enabled: bool,
+
+    /// Stream title patterns, tried in order, e.g. `"{artist} - {title}"`
+    pub title_patterns: Vec<String>,
+}
+
+/// Private listening configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivateConfig {
+    /// Players whose listens are never recorded, matched like `whitelist`
+    pub players: Vec<String>,
+
+    /// Recurring times when nothing is recorded
+    pub schedule: Vec<PrivateWindowConfig>,
+}
+
+/// A recurring private time window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrivateWindowConfig {
+    /// Days the window starts on, e.g. `["sat", "sun"]` (empty = every day)
+    #[serde(default)]
+    pub days: Vec<String>,
+
+    /// Start time, `"HH:MM"`
+    pub start: String,
+
+    /// End time, `"HH:MM"`; before `start` means the next day
+    pub end: String,
+}
+
+/// A rule run on each play before it is logged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    /// Name shown in logs and `rules test`
+    pub name: String,
+
+    /// Conditions by field name; all must hold for the rule to fire
+    #[serde(rename = "match")]
+    pub conditions: BTreeMap<String, toml::Value>,
+
+    /// Drop the play instead of logging it
+    pub drop: bool,
+
+    /// Track fields to overwrite; `{field}` in a string inserts another field
+    pub set: BTreeMap<String, toml::Value>,
+
+    /// Tags to add to the play
+    pub tags: Vec<String>,
 }
 
 // Default implementations
@@ -88,6 +190,7 @@ impl Default for GeneralConfig {
         Self {
             log_level: "info".to_string(),
             data_dir: None,
+            day_starts_at: "00:00".to_string(),
         }
     }
 }
@@ -101,7 +204,9 @@ impl Default for TrackingConfig {
             track_seeks: true,
             track_volume: true,
             track_context: true,
+            position_poll_seconds: 10,
             idle_timeout_seconds: 30,
+            session_gap_seconds: 1800,
         }
     }
 }
@@ -125,6 +230,76 @@ impl Default for PlayerConfig {
                 "sayonara".to_string(),
                 "cantata".to_string(),
             ],
+            proxy_players: vec!["playerctld".to_string()],
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl GeneralConfig {
+    /// The time in `day_starts_at`
+    ///
+    /// # Errors
+    ///
+    /// Fails if it isn't a valid `"HH:MM"` time.
+    pub fn day_start(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.day_starts_at, "%H:%M").map_err(|_| {
+            Error::config(format!(
+                "Invalid day_starts_at {:?}, use HH:MM",
+                self.day_starts_at
+            ))
+        })
+    }
+}
+
+impl TrackingConfig {
+    /// These settings with a player's overrides applied
+    #[must_use]
+    pub fn with_override(&self, overrides: &TrackingOverride) -> Self {
+        Self {
+            min_play_seconds: overrides.min_play_seconds.unwrap_or(self.min_play_seconds),
+            min_play_percent: overrides.min_play_percent.unwrap_or(self.min_play_percent),
+            local_only: overrides.local_only.unwrap_or(self.local_only),
+            track_seeks: overrides.track_seeks.unwrap_or(self.track_seeks),
+            track_volume: overrides.track_volume.unwrap_or(self.track_volume),
+            track_context: overrides.track_context.unwrap_or(self.track_context),
+            position_poll_seconds: overrides
+                .position_poll_seconds
+                .unwrap_or(self.position_poll_seconds),
+            ..self.clone()
+        }
+    }
+}
+
+impl PlayerConfig {
+    /// Effective tracking settings for a player.
+    ///
+    /// Override keys match player IDs the same way as `whitelist`; when several
+    /// match, the longer (more specific) key wins.
+    #[must_use]
+    pub fn tracking_for(&self, player_id: &str, base: &TrackingConfig) -> TrackingConfig {
+        let mut matching: Vec<(&String, &TrackingOverride)> = self
+            .overrides
+            .iter()
+            .filter(|(key, _)| player_id.contains(key.as_str()))
+            .collect();
+        matching.sort_by_key(|(key, _)| key.len());
+
+        matching
+            .into_iter()
+            .fold(base.clone(), |tracking, (_, overrides)| tracking.with_override(overrides))
+    }
+}
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            title_patterns: vec![
+                "{artist} - {title}".to_string(),
+                "{artist} – {title}".to_string(),
+                "{title} by {artist}".to_string(),
+            ],
         }
     }
 }
@@ -186,38 +361,197 @@ impl Config {
         if let Some(ref path) = self.database.path {
             return Ok(PathBuf::from(path));
         }
-        Ok(self.data_dir()?.join("listens.db"))
+        Ok(self.data_dir()?.join("listens.duckdb"))
     }
 
     /// Validate configuration values.
     ///
     /// Call this after loading to ensure all values are within acceptable ranges.
     pub fn validate(&self) -> Result<()> {
-        // Validate min_play_percent is between 0.0 and 1.0
-        if !(0.0..=1.0).contains(&self.tracking.min_play_percent) {
+        Self::validate_tracking(&self.tracking, "")?;
+        for (player, overrides) in &self.players.overrides {
+            Self::validate_tracking(
+                &self.tracking.with_override(overrides),
+                &format!(" (players.overrides.{player:?})"),
+            )?;
+        }
+
+        // Validate log_level is a known level
+        let valid_levels = ["trace", "debug", "info", "warn", "error"];
+        if !valid_levels.contains(&self.general.log_level.to_lowercase().as_str()) {
             return Err(Error::config(format!(
-                "min_play_percent must be between 0.0 and 1.0, got {}",
-                self.tracking.min_play_percent
+                "log_level must be one of {:?}, got '{}'",
+                valid_levels, self.general.log_level
             )));
         }
 
-        // Validate min_play_seconds is reasonable
-        if self.tracking.min_play_seconds > 3600 {
+        self.general.day_start()?;
+
+        // Compile rules and radio patterns so mistakes are reported up front
+        crate::rules::RuleSet::new(&self.rules)?;
+        crate::radio::RadioParser::new(&self.radio)?;
+        crate::private::PrivateSchedule::new(&self.private)?;
+
+        Ok(())
+    }
+
+    /// Check tracking thresholds; `scope` names where they came from in errors
+    fn validate_tracking(tracking: &TrackingConfig, scope: &str) -> Result<()> {
+        // Validate min_play_percent is between 0.0 and 1.0
+        if !(0.0..=1.0).contains(&tracking.min_play_percent) {
             return Err(Error::config(format!(
-                "min_play_seconds should not exceed 3600 (1 hour), got {}",
-                self.tracking.min_play_seconds
+                "min_play_percent{scope} must be between 0.0 and 1.0, got {}",
+                tracking.min_play_percent
             )));
         }
 
-        // Validate log_level is a known level
-        let valid_levels = ["trace", "debug", "info", "warn", "error"];
-        if !valid_levels.contains(&self.general.log_level.to_lowercase().as_str()) {
+        // Validate min_play_seconds is reasonable
+        if tracking.min_play_seconds > 3600 {
             return Err(Error::config(format!(
-                "log_level must be one of {:?}, got '{}'",
-                valid_levels, self.general.log_level
+                "min_play_seconds{scope} should not exceed 3600 (1 hour), got {}",
+                tracking.min_play_seconds
             )));
         }
 
         Ok(())
     }
+
+    /// Describe each setting that differs in `new`, as `section.key: old -> new`
+    #[must_use]
+    pub fn changes(&self, new: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        if let (Ok(old), Ok(new)) = (toml::Value::try_from(self), toml::Value::try_from(new)) {
+            diff_values("", Some(&old), Some(&new), &mut changes);
+        }
+        changes
+    }
+}
+
+/// Collect the leaf values that differ between two TOML values
+fn diff_values(
+    path: &str,
+    old: Option<&toml::Value>,
+    new: Option<&toml::Value>,
+    changes: &mut Vec<String>,
+) {
+    if let (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) = (old, new) {
+        let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for key in keys {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            diff_values(&path, old.get(key), new.get(key), changes);
+        }
+    } else if old != new {
+        let show = |value: Option<&toml::Value>| {
+            value.map_or_else(|| "unset".to_string(), ToString::to_string)
+        };
+        changes.push(format!("{path}: {} -> {}", show(old), show(new)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+            [tracking]
+            min_play_seconds = 30
+            local_only = true
+
+            [players.overrides.spotify]
+            min_play_seconds = 60
+            local_only = false
+
+            [players.overrides."spotify.instance"]
+            track_context = false
+            min_play_seconds = 90
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let amberol = config
+            .players
+            .tracking_for("io.bassi.Amberol", &config.tracking);
+        assert_eq!(amberol.min_play_seconds, 30);
+        assert!(amberol.local_only);
+
+        let spotify = config.players.tracking_for("spotify", &config.tracking);
+        assert_eq!(spotify.min_play_seconds, 60);
+        assert!(!spotify.local_only);
+        assert!(spotify.track_context);
+
+        // The more specific key wins, fields it leaves unset still come from the others
+        let instance = config
+            .players
+            .tracking_for("spotify.instance123", &config.tracking);
+        assert_eq!(instance.min_play_seconds, 90);
+        assert!(!instance.local_only);
+        assert!(!instance.track_context);
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Config::default();
+        let new: Config = toml::from_str(
+            r#"
+            [tracking]
+            min_play_seconds = 60
+
+            [players]
+            blacklist = ["firefox"]
+
+            [players.overrides.vlc]
+            local_only = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            old.changes(&new),
+            vec![
+                r#"players.blacklist: [] -> ["firefox"]"#,
+                "players.overrides.vlc: unset -> { local_only = false }",
+                "tracking.min_play_seconds: 30 -> 60",
+            ]
+        );
+        assert!(new.changes(&new).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_overrides_are_rejected() {
+        let unknown = toml::from_str::<Config>("[players.overrides.vlc]\nidle_timeout_seconds = 5");
+        assert!(unknown.is_err());
+
+        let config: Config =
+            toml::from_str("[players.overrides.vlc]\nmin_play_percent = 2.0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_radio_patterns_are_rejected() {
+        let config: Config =
+            toml::from_str("[radio]\ntitle_patterns = [\"{artist}{title}\"]").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_day_start() {
+        assert_eq!(Config::default().general.day_start().unwrap(), NaiveTime::MIN);
+
+        let config: Config = toml::from_str("[general]\nday_starts_at = \"04:30\"").unwrap();
+        assert_eq!(
+            config.general.day_start().unwrap(),
+            NaiveTime::from_hms_opt(4, 30, 0).unwrap()
+        );
+
+        let config: Config = toml::from_str("[general]\nday_starts_at = \"4am\"").unwrap();
+        assert!(config.validate().is_err());
+    }
 }
@@ -0,0 +1,335 @@
+//! Content type classification
+//!
+//! With `local_only` off, browsers, video players and podcast apps publish
+//! their media over MPRIS too. Each track is classified from its genre tag,
+//! `mpris:trackid`, URL, player identity and duration so stats can stick to
+//! music.
+
+use serde::{Deserialize, Serialize};
+
+use crate::track::Track;
+
+/// Tracks at least this long with no album or track number are taken to be
+/// spoken word (45 minutes)
+const LONG_UNSORTED_DURATION_US: i64 = 45 * 60 * 1_000_000;
+
+/// Hosts, most specific first, and what they serve
+const HOSTS: &[(&str, ContentType)] = &[
+    ("music.youtube.com", ContentType::Music),
+    ("open.spotify.com", ContentType::Music),
+    ("soundcloud.com", ContentType::Music),
+    ("bandcamp.com", ContentType::Music),
+    ("music.apple.com", ContentType::Music),
+    ("deezer.com", ContentType::Music),
+    ("tidal.com", ContentType::Music),
+    ("podcasts.apple.com", ContentType::Podcast),
+    ("podcasts.google.com", ContentType::Podcast),
+    ("pocketcasts.com", ContentType::Podcast),
+    ("overcast.fm", ContentType::Podcast),
+    ("audible.com", ContentType::Audiobook),
+    ("librivox.org", ContentType::Audiobook),
+    ("youtube.com", ContentType::Video),
+    ("youtu.be", ContentType::Video),
+    ("twitch.tv", ContentType::Video),
+    ("vimeo.com", ContentType::Video),
+    ("dailymotion.com", ContentType::Video),
+    ("netflix.com", ContentType::Video),
+];
+
+/// File extensions that settle the content type on their own
+const EXTENSIONS: &[(&str, ContentType)] = &[
+    ("mp3", ContentType::Music),
+    ("flac", ContentType::Music),
+    ("ogg", ContentType::Music),
+    ("opus", ContentType::Music),
+    ("m4a", ContentType::Music),
+    ("wav", ContentType::Music),
+    ("aac", ContentType::Music),
+    ("wma", ContentType::Music),
+    ("ape", ContentType::Music),
+    ("m4b", ContentType::Audiobook),
+    ("aax", ContentType::Audiobook),
+    ("mp4", ContentType::Video),
+    ("mkv", ContentType::Video),
+    ("webm", ContentType::Video),
+    ("avi", ContentType::Video),
+    ("mov", ContentType::Video),
+    ("m4v", ContentType::Video),
+    ("wmv", ContentType::Video),
+    ("flv", ContentType::Video),
+];
+
+/// `mpris:trackid` fragments and what they identify
+const TRACK_ID_PATTERNS: &[(&str, ContentType)] = &[
+    ("spotify:episode:", ContentType::Podcast),
+    ("/com/spotify/episode/", ContentType::Podcast),
+    ("spotify:track:", ContentType::Music),
+    ("/com/spotify/track/", ContentType::Music),
+];
+
+/// Player name fragments and what the player is used for. General-purpose
+/// players like mpv and VLC play music as often as video, so they are left
+/// to the file extension.
+const PLAYERS: &[(&str, ContentType)] = &[
+    ("gpodder", ContentType::Podcast),
+    ("kasts", ContentType::Podcast),
+    ("podcasts", ContentType::Podcast),
+    ("vocal", ContentType::Podcast),
+    ("cozy", ContentType::Audiobook),
+    ("audiobookshelf", ContentType::Audiobook),
+    ("totem", ContentType::Video),
+    ("celluloid", ContentType::Video),
+    ("haruna", ContentType::Video),
+    ("smplayer", ContentType::Video),
+    ("clapper", ContentType::Video),
+    ("showtime", ContentType::Video),
+    ("dragonplayer", ContentType::Video),
+    ("kodi", ContentType::Video),
+    // Browsers: anything not recognised by its URL is most likely video
+    ("firefox", ContentType::Video),
+    ("librewolf", ContentType::Video),
+    ("chromium", ContentType::Video),
+    ("chrome", ContentType::Video),
+    ("brave", ContentType::Video),
+    ("vivaldi", ContentType::Video),
+    ("opera", ContentType::Video),
+    ("msedge", ContentType::Video),
+    ("epiphany", ContentType::Video),
+];
+
+/// Kind of media a track is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    #[default]
+    Music,
+    Podcast,
+    Audiobook,
+    Video,
+}
+
+impl ContentType {
+    /// Value stored in the `content_type` column
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Music => "music",
+            Self::Podcast => "podcast",
+            Self::Audiobook => "audiobook",
+            Self::Video => "video",
+        }
+    }
+}
+
+/// Classify a track played by `player_name`.
+///
+/// Signals are tried from most to least specific: genre tag, track ID, URL,
+/// player identity, then duration. Local files are never judged by their
+/// player, and anything unrecognised counts as music.
+#[must_use]
+pub fn classify(track: &Track, player_name: Option<&str>) -> ContentType {
+    if track.is_radio {
+        return ContentType::Music;
+    }
+
+    from_genre(track)
+        .or_else(|| from_track_id(track))
+        .or_else(|| from_url(track))
+        .or_else(|| {
+            player_name
+                .filter(|_| !is_local_file(track))
+                .and_then(from_player)
+        })
+        .or_else(|| from_duration(track))
+        .unwrap_or_default()
+}
+
+fn from_genre(track: &Track) -> Option<ContentType> {
+    let genre = track.genre.as_deref()?.to_lowercase();
+    if genre.contains("podcast") {
+        Some(ContentType::Podcast)
+    } else if genre.contains("audiobook") || genre.contains("audio book") {
+        Some(ContentType::Audiobook)
+    } else {
+        None
+    }
+}
+
+fn from_track_id(track: &Track) -> Option<ContentType> {
+    let track_id = track.track_id.as_deref()?;
+    TRACK_ID_PATTERNS
+        .iter()
+        .find(|(pattern, _)| track_id.contains(pattern))
+        .map(|&(_, content_type)| content_type)
+}
+
+fn from_url(track: &Track) -> Option<ContentType> {
+    let url = track.file_path.as_deref()?.to_lowercase();
+    let (scheme, rest) = url.split_once("://").unwrap_or(("file", url.as_str()));
+
+    if scheme != "file" {
+        let host = rest
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or_default()
+            .rsplit('@')
+            .next()
+            .unwrap_or_default()
+            .split(':')
+            .next()
+            .unwrap_or_default();
+        let by_host = HOSTS
+            .iter()
+            .find(|(known, _)| {
+                host == *known
+                    || host
+                        .strip_suffix(known)
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            })
+            .map(|&(_, content_type)| content_type);
+        if by_host.is_some() {
+            return by_host;
+        }
+    }
+
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    if path.contains("audiobook") {
+        return Some(ContentType::Audiobook);
+    }
+    if path.contains("podcast") {
+        return Some(ContentType::Podcast);
+    }
+
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    let extension = file_name.rsplit_once('.')?.1;
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| extension == *known)
+        .map(|&(_, content_type)| content_type)
+}
+
+fn is_local_file(track: &Track) -> bool {
+    track
+        .file_path
+        .as_deref()
+        .is_some_and(|path| path.starts_with("file://") || path.starts_with('/'))
+}
+
+fn from_player(player_name: &str) -> Option<ContentType> {
+    let player = player_name.to_lowercase();
+    PLAYERS
+        .iter()
+        .find(|(fragment, _)| player.contains(fragment))
+        .map(|&(_, content_type)| content_type)
+}
+
+fn from_duration(track: &Track) -> Option<ContentType> {
+    let long = track
+        .duration_us
+        .is_some_and(|duration| duration >= LONG_UNSORTED_DURATION_US);
+    (long && track.album.is_none() && track.track_number.is_none()).then_some(ContentType::Podcast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track_at(url: &str) -> Track {
+        Track {
+            title: Some("Title".to_string()),
+            file_path: Some(url.to_string()),
+            ..Track::default()
+        }
+    }
+
+    #[test]
+    fn test_browser_urls() {
+        let firefox = Some("firefox.instance_1_42");
+        assert_eq!(
+            classify(&track_at("https://www.youtube.com/watch?v=abc"), firefox),
+            ContentType::Video
+        );
+        assert_eq!(
+            classify(&track_at("https://music.youtube.com/watch?v=abc"), firefox),
+            ContentType::Music
+        );
+        assert_eq!(
+            classify(&track_at("https://artist.bandcamp.com/track/song"), firefox),
+            ContentType::Music
+        );
+        // Unknown sites in a browser are most likely video
+        assert_eq!(
+            classify(&track_at("https://example.com/clip"), firefox),
+            ContentType::Video
+        );
+    }
+
+    #[test]
+    fn test_local_files() {
+        assert_eq!(
+            classify(&track_at("file:///home/me/Music/song.flac"), Some("vlc")),
+            ContentType::Music
+        );
+        assert_eq!(
+            classify(&track_at("file:///home/me/Videos/film.mkv"), Some("vlc")),
+            ContentType::Video
+        );
+        // Unknown extensions don't fall back to the player
+        assert_eq!(
+            classify(&track_at("file:///home/me/Music/song.mka"), Some("totem")),
+            ContentType::Music
+        );
+        assert_eq!(
+            classify(&track_at("file:///home/me/Audiobooks/book/01.mp3"), None),
+            ContentType::Audiobook
+        );
+        assert_eq!(
+            classify(&track_at("file:///home/me/book.m4b"), None),
+            ContentType::Audiobook
+        );
+    }
+
+    #[test]
+    fn test_genre_and_track_id() {
+        let mut track = track_at("file:///home/me/Music/episode.mp3");
+        track.genre = Some("Podcast".to_string());
+        assert_eq!(classify(&track, None), ContentType::Podcast);
+
+        let spotify = Track {
+            track_id: Some("/com/spotify/episode/4rOoJ6Egrf8K2IrywzwOMk".to_string()),
+            ..Track::default()
+        };
+        assert_eq!(classify(&spotify, Some("spotify")), ContentType::Podcast);
+    }
+
+    #[test]
+    fn test_player_and_duration() {
+        let track = Track {
+            title: Some("Chapter 1".to_string()),
+            ..Track::default()
+        };
+        assert_eq!(
+            classify(&track, Some("com.github.geigi.cozy")),
+            ContentType::Audiobook
+        );
+        assert_eq!(classify(&track, Some("spotify")), ContentType::Music);
+        assert_eq!(classify(&track, Some("mpv")), ContentType::Music);
+        assert_eq!(
+            classify(&track, Some("org.gnome.Totem")),
+            ContentType::Video
+        );
+
+        let long = Track {
+            duration_us: Some(LONG_UNSORTED_DURATION_US + 1),
+            ..track
+        };
+        assert_eq!(classify(&long, Some("spotify")), ContentType::Podcast);
+    }
+
+    #[test]
+    fn test_radio_is_music() {
+        let mut track = track_at("https://stream.example.com/podcast-radio");
+        track.is_radio = true;
+        assert_eq!(classify(&track, Some("firefox")), ContentType::Music);
+    }
+}
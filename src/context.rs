@@ -1,6 +1,6 @@
 //! Listening context tracking (time, activity, power state)
 
-use chrono::{Datelike, Local, Timelike};
+use chrono::{DateTime, Datelike, Local, Timelike};
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 use std::time::Duration;
@@ -53,7 +53,6 @@ impl ListeningContext {
     /// to avoid blocking the async runtime.
     pub async fn capture() -> Self {
         let now = Local::now();
-        let weekday = now.weekday().num_days_from_monday() as i32;
 
         // Run blocking I/O operations in spawn_blocking with timeouts to avoid
         // indefinite hangs if external commands stall
@@ -64,13 +63,27 @@ impl ListeningContext {
         );
 
         Self {
-            hour_of_day: now.hour() as i32,
-            day_of_week: weekday,
-            is_weekend: weekday >= WEEKEND_START_DAY,
-            season: get_season(now.month()),
             active_window,
             screen_on,
             on_battery,
+            ..Self::at_time(now)
+        }
+    }
+
+    /// Time-based context only, for a play that started at `time`.
+    ///
+    /// Used for plays recovered after a crash, when the desktop state at the
+    /// time of the play is no longer known.
+    #[must_use]
+    pub fn at_time(time: DateTime<Local>) -> Self {
+        let weekday = time.weekday().num_days_from_monday() as i32;
+
+        Self {
+            hour_of_day: time.hour() as i32,
+            day_of_week: weekday,
+            is_weekend: weekday >= WEEKEND_START_DAY,
+            season: get_season(time.month()),
+            ..Self::default()
         }
     }
 
@@ -95,6 +108,18 @@ impl ListeningContext {
     }
 }
 
+/// IANA name of the system time zone, e.g. `Europe/Berlin`
+///
+/// Follows `TZ` when it's set, as the local clock does.
+#[must_use]
+pub fn timezone() -> Option<String> {
+    std::env::var("TZ")
+        .ok()
+        .map(|tz| tz.trim_start_matches(':').to_string())
+        .filter(|tz| !tz.is_empty())
+        .or_else(|| iana_time_zone::get_timezone().ok())
+}
+
 /// Get the season for a given month (Northern Hemisphere)
 fn get_season(month: u32) -> String {
     match month {
@@ -216,6 +241,7 @@ fn get_power_state() -> Option<bool> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn test_get_season() {
@@ -225,6 +251,17 @@ mod tests {
         assert_eq!(get_season(10), "fall");
     }
 
+    #[test]
+    fn test_context_at_time() {
+        let saturday = Local.with_ymd_and_hms(2024, 7, 13, 23, 30, 0).unwrap();
+        let ctx = ListeningContext::at_time(saturday);
+        assert_eq!(ctx.hour_of_day, 23);
+        assert_eq!(ctx.day_of_week, 5);
+        assert!(ctx.is_weekend);
+        assert_eq!(ctx.season, "summer");
+        assert!(ctx.active_window.is_none());
+    }
+
     #[test]
     fn test_context_capture_sync() {
         let ctx = ListeningContext::capture_sync();
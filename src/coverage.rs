@@ -0,0 +1,150 @@
+//! Coverage of track positions heard during a listen
+//!
+//! Built from MPRIS `Position` samples and `Seeked` signals, so seeks, stalls
+//! and skipped-over sections are not counted as heard audio.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Slack allowed between position progress and played time when deciding
+/// whether two samples belong to continuous playback (2 seconds).
+const CONTINUITY_SLACK_US: i64 = 2_000_000;
+
+const US_PER_SECOND: i64 = 1_000_000;
+
+/// Position ranges (in microseconds) heard during a listen
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionCoverage {
+    /// Covered ranges in the order they were heard (may overlap)
+    ranges: Vec<(i64, i64)>,
+}
+
+impl PositionCoverage {
+    /// Create an empty coverage
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { ranges: Vec::new() }
+    }
+
+    /// Record a range of the track as heard
+    pub fn add_range(&mut self, start_us: i64, end_us: i64) {
+        if end_us > start_us {
+            self.ranges.push((start_us.max(0), end_us));
+        }
+    }
+
+    /// Record progress between two position samples taken `played` apart.
+    ///
+    /// Progress that moves backwards or further than the played time allows is
+    /// treated as a jump (seek or track restart) and ignored. Returns whether
+    /// the progress was recorded.
+    pub fn add_progress(&mut self, from_us: i64, to_us: i64, played: Duration) -> bool {
+        let advanced = to_us - from_us;
+        let played_us = i64::try_from(played.as_micros()).unwrap_or(i64::MAX);
+
+        if advanced <= 0 || advanced > played_us.saturating_add(CONTINUITY_SLACK_US) {
+            return false;
+        }
+
+        self.add_range(from_us, to_us);
+        true
+    }
+
+    /// Total audio heard in microseconds, counting repeated sections each time
+    #[must_use]
+    pub fn heard_us(&self) -> i64 {
+        self.ranges.iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Total audio heard in milliseconds, counting repeated sections each time
+    #[must_use]
+    pub fn heard_ms(&self) -> i64 {
+        self.heard_us() / 1000
+    }
+
+    /// Covered ranges sorted and merged so that none overlap
+    #[must_use]
+    pub fn merged(&self) -> Vec<(i64, i64)> {
+        let mut sorted = self.ranges.clone();
+        sorted.sort_unstable();
+
+        let mut merged: Vec<(i64, i64)> = Vec::with_capacity(sorted.len());
+        for (start, end) in sorted {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// Number of distinct whole seconds of the track that were heard at least partly
+    #[must_use]
+    pub fn distinct_seconds(&self) -> i64 {
+        let mut count = 0;
+        let mut next_uncounted = i64::MIN;
+
+        for (start, end) in self.merged() {
+            let first = (start / US_PER_SECOND).max(next_uncounted);
+            let last = (end + US_PER_SECOND - 1) / US_PER_SECOND;
+            if last > first {
+                count += last - first;
+                next_uncounted = last;
+            }
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_progress_continuous() {
+        let mut coverage = PositionCoverage::new();
+        assert!(coverage.add_progress(0, 10_000_000, Duration::from_secs(10)));
+        assert!(coverage.add_progress(10_000_000, 20_000_000, Duration::from_secs(10)));
+        assert_eq!(coverage.heard_ms(), 20_000);
+        assert_eq!(coverage.distinct_seconds(), 20);
+    }
+
+    #[test]
+    fn test_add_progress_ignores_jumps() {
+        let mut coverage = PositionCoverage::new();
+        // Forward jump larger than the time played
+        assert!(!coverage.add_progress(0, 60_000_000, Duration::from_secs(10)));
+        // Backwards movement
+        assert!(!coverage.add_progress(60_000_000, 5_000_000, Duration::from_secs(10)));
+        assert_eq!(coverage.heard_us(), 0);
+    }
+
+    #[test]
+    fn test_add_progress_stall_counts_only_progress() {
+        let mut coverage = PositionCoverage::new();
+        // 10 seconds passed but the player only advanced 3 seconds
+        assert!(coverage.add_progress(0, 3_000_000, Duration::from_secs(10)));
+        assert_eq!(coverage.heard_ms(), 3_000);
+    }
+
+    #[test]
+    fn test_repeated_section_counts_heard_but_not_distinct() {
+        let mut coverage = PositionCoverage::new();
+        coverage.add_range(0, 30_000_000);
+        coverage.add_range(10_000_000, 30_000_000);
+        assert_eq!(coverage.heard_ms(), 50_000);
+        assert_eq!(coverage.merged(), vec![(0, 30_000_000)]);
+        assert_eq!(coverage.distinct_seconds(), 30);
+    }
+
+    #[test]
+    fn test_distinct_seconds_partial_boundaries() {
+        let mut coverage = PositionCoverage::new();
+        coverage.add_range(500_000, 1_500_000);
+        coverage.add_range(1_700_000, 2_200_000);
+        // Seconds 0, 1 and 2 were each heard at least partly
+        assert_eq!(coverage.distinct_seconds(), 3);
+    }
+}
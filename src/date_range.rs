@@ -8,7 +8,22 @@
 
 use std::fmt;
 
-use chrono::{Datelike, Local, NaiveDate};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// The day `time` counts toward when days start at `day_start`
+///
+/// With days starting at 04:00, listening at 01:30 counts toward the day
+/// before.
+#[must_use]
+pub fn day_of(time: NaiveDateTime, day_start: NaiveTime) -> NaiveDate {
+    (time - day_start.signed_duration_since(NaiveTime::MIN)).date()
+}
+
+/// The current day, for days that start at `day_start`
+#[must_use]
+pub fn current_day(day_start: NaiveTime) -> NaiveDate {
+    day_of(Local::now().naive_local(), day_start)
+}
 
 /// A date range for filtering statistics queries.
 ///
@@ -22,6 +37,8 @@ pub struct DateRange {
     pub end: Option<NaiveDate>,
     /// Human-readable name for this period (e.g., "Last 7 Days", "2024")
     pub display_name: String,
+    /// Time of day each day starts, see [`day_of`]
+    pub day_start: NaiveTime,
 }
 
 impl DateRange {
@@ -36,62 +53,69 @@ impl DateRange {
             start,
             end,
             display_name: display_name.into(),
+            day_start: NaiveTime::MIN,
         }
     }
 
+    /// Set the time each day starts (midnight by default).
+    #[must_use]
+    pub const fn with_day_start(mut self, day_start: NaiveTime) -> Self {
+        self.day_start = day_start;
+        self
+    }
+
     /// Create an unbounded date range (all time).
     #[must_use]
     pub fn all_time() -> Self {
         Self::new(None, None, "All Time")
     }
 
-    /// Create a date range for today only.
+    /// Create a date range for today only, for days starting at `day_start`.
     #[must_use]
-    pub fn today() -> Self {
-        let today = Local::now().date_naive();
-        Self::new(Some(today), Some(today), "Today")
+    pub fn today(day_start: NaiveTime) -> Self {
+        let today = current_day(day_start);
+        Self::new(Some(today), Some(today), "Today").with_day_start(day_start)
     }
 
     /// Create a date range for the last 7 days.
     #[must_use]
-    pub fn last_week() -> Self {
-        let today = Local::now().date_naive();
+    pub fn last_week(day_start: NaiveTime) -> Self {
+        let today = current_day(day_start);
         let start = today - chrono::Duration::days(7);
-        Self::new(Some(start), Some(today), "Last 7 Days")
+        Self::new(Some(start), Some(today), "Last 7 Days").with_day_start(day_start)
     }
 
     /// Create a date range for the current calendar month.
     #[must_use]
-    pub fn current_month() -> Self {
-        let now = Local::now();
-        let start = now.date_naive().with_day(1).expect("day 1 is always valid");
-        let display = now.format("%B %Y").to_string();
-        Self::new(Some(start), None, display)
+    pub fn current_month(day_start: NaiveTime) -> Self {
+        let today = current_day(day_start);
+        let start = today.with_day(1).expect("day 1 is always valid");
+        let display = today.format("%B %Y").to_string();
+        Self::new(Some(start), None, display).with_day_start(day_start)
     }
 
     /// Create a date range for the last 30 days (rolling month).
     #[must_use]
-    pub fn last_month() -> Self {
-        let today = Local::now().date_naive();
+    pub fn last_month(day_start: NaiveTime) -> Self {
+        let today = current_day(day_start);
         let start = today - chrono::Duration::days(30);
-        Self::new(Some(start), Some(today), "Past Month")
+        Self::new(Some(start), Some(today), "Past Month").with_day_start(day_start)
     }
 
     /// Create a date range for the current calendar year.
     #[must_use]
-    pub fn current_year() -> Self {
-        let now = Local::now();
-        let year = now.year();
+    pub fn current_year(day_start: NaiveTime) -> Self {
+        let year = current_day(day_start).year();
         let start = NaiveDate::from_ymd_opt(year, 1, 1).expect("January 1 is always valid");
-        Self::new(Some(start), None, year.to_string())
+        Self::new(Some(start), None, year.to_string()).with_day_start(day_start)
     }
 
     /// Create a date range for the last 365 days (rolling year).
     #[must_use]
-    pub fn last_year() -> Self {
-        let today = Local::now().date_naive();
+    pub fn last_year(day_start: NaiveTime) -> Self {
+        let today = current_day(day_start);
         let start = today - chrono::Duration::days(365);
-        Self::new(Some(start), Some(today), "Past Year")
+        Self::new(Some(start), Some(today), "Past Year").with_day_start(day_start)
     }
 
     /// Create a date range for a specific calendar year.
@@ -102,25 +126,39 @@ impl DateRange {
         Self::new(Some(start), Some(end), year.to_string())
     }
 
-    /// Get the start date as a SQL-friendly string (YYYY-MM-DD format).
+    /// Get the start date as a SQL-friendly string (YYYY-MM-DD format, with
+    /// the time the day starts unless that's midnight).
     #[must_use]
     pub fn start_sql(&self) -> Option<String> {
-        self.start.map(|d| d.format("%Y-%m-%d").to_string())
+        self.start.map(|d| self.format_day_start(d))
     }
 
-    /// Get the end date as a SQL-friendly string (YYYY-MM-DD format).
+    /// Get the end date as a SQL-friendly string (YYYY-MM-DD format, with
+    /// the time the day starts unless that's midnight).
     #[must_use]
     pub fn end_sql(&self) -> Option<String> {
-        self.end.map(|d| d.format("%Y-%m-%d").to_string())
+        self.end.map(|d| self.format_day_start(d))
     }
 
     /// Get the end date with time as a SQL-friendly string (YYYY-MM-DD HH:MM:SS format).
     ///
-    /// Uses 23:59:59 to include the entire end day.
+    /// Uses the second before the next day starts, 23:59:59 unless days
+    /// start later, to include the entire end day.
     #[must_use]
     pub fn end_sql_with_time(&self) -> Option<String> {
-        self.end
-            .map(|d| format!("{} 23:59:59", d.format("%Y-%m-%d")))
+        self.end.map(|d| {
+            let next_day = (d + Duration::days(1)).and_time(self.day_start);
+            (next_day - Duration::seconds(1)).format("%Y-%m-%d %H:%M:%S").to_string()
+        })
+    }
+
+    /// When `day` starts, as a SQL-friendly string
+    fn format_day_start(&self, day: NaiveDate) -> String {
+        if self.day_start == NaiveTime::MIN {
+            day.format("%Y-%m-%d").to_string()
+        } else {
+            day.and_time(self.day_start).format("%Y-%m-%d %H:%M:%S").to_string()
+        }
     }
 
     /// Convert to a tuple of optional SQL strings `(start, end)`.
@@ -162,23 +200,30 @@ impl fmt::Display for DateRange {
 /// * `week` - If true, returns last 7 days
 /// * `month` - If true, returns current calendar month
 /// * `year` - If Some, returns that specific year; if None with no other flags, returns current year
+/// * `day_start` - Time of day each day starts
 ///
 /// # Priority
 ///
 /// Flags are checked in order: all_time > week > month > year > default (current year)
 #[must_use]
-pub fn from_cli_flags(all_time: bool, week: bool, month: bool, year: Option<i32>) -> DateRange {
+pub fn from_cli_flags(
+    all_time: bool,
+    week: bool,
+    month: bool,
+    year: Option<i32>,
+    day_start: NaiveTime,
+) -> DateRange {
     if all_time {
         DateRange::all_time()
     } else if week {
-        DateRange::last_week()
+        DateRange::last_week(day_start)
     } else if month {
-        DateRange::current_month()
+        DateRange::current_month(day_start)
     } else if let Some(y) = year {
-        DateRange::year(y)
+        DateRange::year(y).with_day_start(day_start)
     } else {
         // Default: current year
-        DateRange::current_year()
+        DateRange::current_year(day_start)
     }
 }
 
@@ -201,14 +246,14 @@ pub enum DateFilter {
 }
 
 impl DateFilter {
-    /// Convert to a `DateRange`.
+    /// Convert to a `DateRange`, for days starting at `day_start`.
     #[must_use]
-    pub fn to_date_range(self) -> DateRange {
+    pub fn to_date_range(self, day_start: NaiveTime) -> DateRange {
         match self {
-            Self::Today => DateRange::today(),
-            Self::Week => DateRange::last_week(),
-            Self::Month => DateRange::last_month(),
-            Self::Year => DateRange::last_year(),
+            Self::Today => DateRange::today(day_start),
+            Self::Week => DateRange::last_week(day_start),
+            Self::Month => DateRange::last_month(day_start),
+            Self::Year => DateRange::last_year(day_start),
             Self::AllTime => DateRange::all_time(),
         }
     }
@@ -238,12 +283,6 @@ impl DateFilter {
     }
 }
 
-impl From<DateFilter> for DateRange {
-    fn from(filter: DateFilter) -> Self {
-        filter.to_date_range()
-    }
-}
-
 impl fmt::Display for DateFilter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.display_name())
@@ -273,7 +312,7 @@ mod tests {
 
     #[test]
     fn test_today() {
-        let range = DateRange::today();
+        let range = DateRange::today(NaiveTime::MIN);
         let today = Local::now().date_naive();
         assert_eq!(range.start, Some(today));
         assert_eq!(range.end, Some(today));
@@ -282,7 +321,7 @@ mod tests {
 
     #[test]
     fn test_last_week() {
-        let range = DateRange::last_week();
+        let range = DateRange::last_week(NaiveTime::MIN);
         let today = Local::now().date_naive();
         let week_ago = today - chrono::Duration::days(7);
         assert_eq!(range.start, Some(week_ago));
@@ -299,23 +338,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_day_start() {
+        let four = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let late_night = NaiveDate::from_ymd_opt(2024, 1, 2)
+            .unwrap()
+            .and_hms_opt(1, 30, 0)
+            .unwrap();
+        assert_eq!(day_of(late_night, four), NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(day_of(late_night, NaiveTime::MIN), late_night.date());
+
+        let range = DateRange::year(2024).with_day_start(four);
+        assert_eq!(range.start_sql(), Some("2024-01-01 04:00:00".to_string()));
+        assert_eq!(
+            range.end_sql_with_time(),
+            Some("2025-01-01 03:59:59".to_string())
+        );
+
+        let range = DateRange::today(four);
+        assert_eq!(range.start, Some(day_of(Local::now().naive_local(), four)));
+        assert_eq!(range.day_start, four);
+    }
+
     #[test]
     fn test_cli_flags_all_time() {
-        let range = from_cli_flags(true, false, false, None);
+        let range = from_cli_flags(true, false, false, None, NaiveTime::MIN);
         assert!(range.start.is_none());
         assert!(range.end.is_none());
     }
 
     #[test]
     fn test_cli_flags_week() {
-        let range = from_cli_flags(false, true, false, None);
+        let range = from_cli_flags(false, true, false, None, NaiveTime::MIN);
         assert!(range.start.is_some());
         assert_eq!(range.display_name, "Last 7 Days");
     }
 
     #[test]
     fn test_cli_flags_specific_year() {
-        let range = from_cli_flags(false, false, false, Some(2023));
+        let range = from_cli_flags(false, false, false, Some(2023), NaiveTime::MIN);
         assert_eq!(range.start_sql(), Some("2023-01-01".to_string()));
         assert_eq!(range.end_sql(), Some("2023-12-31".to_string()));
     }
@@ -323,23 +384,23 @@ mod tests {
     #[test]
     fn test_cli_flags_priority() {
         // all_time takes precedence
-        let range = from_cli_flags(true, true, true, Some(2023));
+        let range = from_cli_flags(true, true, true, Some(2023), NaiveTime::MIN);
         assert!(range.start.is_none());
 
         // week takes precedence over month and year
-        let range = from_cli_flags(false, true, true, Some(2023));
+        let range = from_cli_flags(false, true, true, Some(2023), NaiveTime::MIN);
         assert_eq!(range.display_name, "Last 7 Days");
     }
 
     #[test]
     fn test_date_filter_conversion() {
         let filter = DateFilter::AllTime;
-        let range = filter.to_date_range();
+        let range = filter.to_date_range(NaiveTime::MIN);
         assert!(range.start.is_none());
         assert!(range.end.is_none());
 
         let filter = DateFilter::Week;
-        let range = filter.to_date_range();
+        let range = filter.to_date_range(NaiveTime::MIN);
         assert!(range.start.is_some());
         assert!(range.end.is_some());
     }
@@ -1,6 +1,13 @@
 //! Query filter utilities for building parameterized SQL queries.
 
-use duckdb::ToSql;
+use std::fmt::Write as _;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike};
+use duckdb::{Connection, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::date_range;
+use crate::error::Result;
 
 /// Date range filter for queries.
 #[derive(Debug, Clone, Default)]
@@ -15,16 +22,63 @@ impl<'a> DateFilter<'a> {
         Self { start, end }
     }
 
-    /// Append date filter clauses to a query string.
-    /// DuckDB handles timezone conversion automatically when comparing timestamps.
-    pub fn apply(&self, query: &mut String, params: &mut Vec<String>) {
+    /// Append date filter clauses on the local time of plays or skips,
+    /// `local` being its SQL expression from [`LocalTime::timestamp_sql`].
+    ///
+    /// The bounds are local dates or times and are compared as they are, so
+    /// a range holds the plays of the days and hours stats bucket them into.
+    pub fn apply(&self, local: &str, query: &mut String, params: &mut Vec<String>) {
+        let bound = |bound: &str| {
+            local_bound(bound).map_or_else(|| bound.to_string(), |time| time.to_string())
+        };
+        if let Some(start) = self.start {
+            let _ = write!(query, " AND {local} >= ?");
+            params.push(bound(start));
+        }
+        if let Some(end) = self.end {
+            let _ = write!(query, " AND {local} <= ?");
+            params.push(bound(end));
+        }
+    }
+
+    /// Append date filter clauses on a `DATE` column of days that start at
+    /// `day_start`, matching the days the bounds fall on.
+    pub fn apply_to_days(
+        &self,
+        column: &str,
+        day_start: NaiveTime,
+        query: &mut String,
+        params: &mut Vec<String>,
+    ) {
+        let day = |bound: &str| {
+            local_bound(bound).map_or_else(
+                || bound.to_string(),
+                |time| date_range::day_of(time, day_start).to_string(),
+            )
+        };
+        if let Some(start) = self.start {
+            let _ = write!(query, " AND {column} >= CAST(? AS DATE)");
+            params.push(day(start));
+        }
+        if let Some(end) = self.end {
+            let _ = write!(query, " AND {column} <= CAST(? AS DATE)");
+            params.push(day(end));
+        }
+    }
+
+    /// Append date filter clauses on a UTC timestamp column stored without
+    /// offsets, such as the start of sessions.
+    ///
+    /// The bounds are local dates or times in the current zone, and are
+    /// converted to UTC.
+    pub fn apply_to_column(&self, column: &str, query: &mut String, params: &mut Vec<String>) {
         if let Some(start) = self.start {
-            query.push_str(" AND timestamp >= ?");
-            params.push(start.to_string());
+            let _ = write!(query, " AND {column} >= ?");
+            params.push(utc_bound(start, &Local));
         }
         if let Some(end) = self.end {
-            query.push_str(" AND timestamp <= ?");
-            params.push(end.to_string());
+            let _ = write!(query, " AND {column} <= ?");
+            params.push(utc_bound(end, &Local));
         }
     }
 
@@ -34,6 +88,179 @@ impl<'a> DateFilter<'a> {
     }
 }
 
+/// A `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` bound, dates meaning midnight
+fn local_bound(bound: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(bound, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(bound, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .ok()
+}
+
+/// A local bound in `zone`, as naive UTC; anything that isn't a date or
+/// time is passed through as it is
+fn utc_bound<Tz: TimeZone>(bound: &str, zone: &Tz) -> String {
+    let Some(local) = local_bound(bound) else {
+        return bound.to_string();
+    };
+    // A bound in the hour skipped when clocks go forward falls just after it
+    zone.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map_or_else(|| bound.to_string(), |time| time.naive_utc().to_string())
+}
+
+/// Which clock day and hour buckets follow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalTime {
+    /// The clock where each play happened, from the offset stored with it
+    #[default]
+    WherePlayed,
+    /// The clock of the zone the system is in now, whatever the zone was
+    /// at the time
+    CurrentZone,
+}
+
+impl LocalTime {
+    /// Where played, or the current zone if `current_zone` is set.
+    #[allow(dead_code)] // Used by `music-stats`
+    #[must_use]
+    pub const fn new(current_zone: bool) -> Self {
+        if current_zone {
+            Self::CurrentZone
+        } else {
+            Self::WherePlayed
+        }
+    }
+
+    /// SQL expression for a play's local time, to bucket by its date or hour.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the span of plays can't be read.
+    pub fn timestamp_sql(self, conn: &Connection) -> Result<String> {
+        let current = current_offset_sql(conn, "plays", "timestamp")?;
+        Ok(match self {
+            // Plays recorded before offsets were stored fall back to the
+            // current zone
+            Self::WherePlayed => {
+                format!("(timestamp + to_minutes(COALESCE(utc_offset_minutes, {current})))")
+            }
+            Self::CurrentZone => format!("(timestamp + to_minutes({current}))"),
+        })
+    }
+
+    /// SQL expression for the `YYYY-MM-DD` day a play counts toward, for
+    /// days that start at `day_start`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the span of plays can't be read.
+    pub fn day_sql(self, conn: &Connection, day_start: NaiveTime) -> Result<String> {
+        let local = self.timestamp_sql(conn)?;
+        let seconds = day_start.num_seconds_from_midnight();
+        Ok(format!("strftime({local} - to_seconds({seconds}), '%Y-%m-%d')"))
+    }
+}
+
+/// SQL expression for the current zone's UTC offset in minutes at the naive
+/// UTC `column`, following its clock changes over the span of `table`
+pub(super) fn current_offset_sql(conn: &Connection, table: &str, column: &str) -> Result<String> {
+    let (first, last): (Option<String>, Option<String>) = conn.query_row(
+        &format!(
+            "SELECT CAST(MIN({column}) AS VARCHAR), CAST(MAX({column}) AS VARCHAR) FROM {table}"
+        ),
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let parse = |time: Option<String>| {
+        time.and_then(|time| NaiveDateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S%.f").ok())
+    };
+    let now = Local::now().naive_utc();
+    let spans = offset_spans(parse(first).unwrap_or(now), parse(last).unwrap_or(now), |at| {
+        Local.offset_from_utc_datetime(&at).fix().local_minus_utc() / 60
+    });
+    Ok(offset_case(column, &spans))
+}
+
+/// The UTC offsets in minutes between `from` and `to`, each from the instant
+/// it took effect; the first applies before `from` too
+fn offset_spans(
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    offset_at: impl Fn(NaiveDateTime) -> i32,
+) -> Vec<(NaiveDateTime, i32)> {
+    let mut at = from.date().and_hms_opt(from.hour(), 0, 0).unwrap_or(from);
+    let mut spans = vec![(at, offset_at(at))];
+    let mut offset = spans[0].1;
+
+    // Clocks change on the hour, at most a few times a year
+    while at < to {
+        let next = at + Duration::days(1);
+        if offset_at(next) == offset {
+            at = next;
+            continue;
+        }
+        at += Duration::hours(1);
+        while offset_at(at) == offset {
+            at += Duration::hours(1);
+        }
+        offset = offset_at(at);
+        spans.push((at, offset));
+    }
+    spans
+}
+
+/// `CASE` expression picking the offset in effect at `column`
+fn offset_case(column: &str, spans: &[(NaiveDateTime, i32)]) -> String {
+    let Some(((_, first), rest)) = spans.split_first() else {
+        return "0".to_string();
+    };
+    if rest.is_empty() {
+        return first.to_string();
+    }
+
+    let mut sql = "CASE".to_string();
+    let mut offset = first;
+    for (from, next) in rest {
+        let _ = write!(sql, " WHEN {column} < TIMESTAMP '{from}' THEN {offset}");
+        offset = next;
+    }
+    let _ = write!(sql, " ELSE {offset} END");
+    sql
+}
+
+/// Content type filter for stats queries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFilter {
+    /// Music only; plays recorded before classification count as music
+    #[default]
+    MusicOnly,
+    /// Music, podcasts, audiobooks and video
+    All,
+}
+
+impl ContentFilter {
+    /// Music only, or everything if `include_all` is set.
+    #[must_use]
+    pub const fn new(include_all: bool) -> Self {
+        if include_all {
+            Self::All
+        } else {
+            Self::MusicOnly
+        }
+    }
+
+    /// Append the content type clause to a query string.
+    pub fn apply(self, query: &mut String) {
+        if self == Self::MusicOnly {
+            query.push_str(" AND COALESCE(content_type, 'music') = 'music'");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,7 +270,7 @@ mod tests {
         let filter = DateFilter::new(None, None);
         let mut query = "SELECT * FROM plays WHERE 1=1".to_string();
         let mut params = Vec::new();
-        filter.apply(&mut query, &mut params);
+        filter.apply("timestamp", &mut query, &mut params);
 
         assert_eq!(query, "SELECT * FROM plays WHERE 1=1");
         assert!(params.is_empty());
@@ -54,26 +281,78 @@ mod tests {
         let filter = DateFilter::new(Some("2024-01-01"), None);
         let mut query = "SELECT * FROM plays WHERE 1=1".to_string();
         let mut params = Vec::new();
-        filter.apply(&mut query, &mut params);
+        filter.apply("local", &mut query, &mut params);
+
+        assert_eq!(query, "SELECT * FROM plays WHERE 1=1 AND local >= ?");
+        assert_eq!(params, ["2024-01-01 00:00:00"]);
+    }
+
+    #[test]
+    fn test_date_filter_custom_column() {
+        let filter = DateFilter::new(Some("2024-01-01"), None);
+        let mut query = "SELECT * FROM sessions WHERE 1=1".to_string();
+        let mut params = Vec::new();
+        filter.apply_to_column("start_time", &mut query, &mut params);
+
+        assert_eq!(
+            query,
+            "SELECT * FROM sessions WHERE 1=1 AND start_time >= ?"
+        );
+        assert_eq!(params, vec![utc_bound("2024-01-01", &Local)]);
+    }
+
+    #[test]
+    fn test_content_filter() {
+        let mut query = "SELECT * FROM plays WHERE 1=1".to_string();
+        ContentFilter::new(true).apply(&mut query);
+        assert_eq!(query, "SELECT * FROM plays WHERE 1=1");
 
+        ContentFilter::default().apply(&mut query);
         assert_eq!(
             query,
-            "SELECT * FROM plays WHERE 1=1 AND timestamp >= ?"
+            "SELECT * FROM plays WHERE 1=1 AND COALESCE(content_type, 'music') = 'music'"
         );
-        assert_eq!(params, vec!["2024-01-01"]);
     }
 
     #[test]
     fn test_date_filter_both() {
-        let filter = DateFilter::new(Some("2024-01-01"), Some("2024-12-31"));
+        let filter = DateFilter::new(Some("2024-01-01"), Some("2024-12-31 23:59:59"));
         let mut query = "SELECT * FROM plays WHERE 1=1".to_string();
         let mut params = Vec::new();
-        filter.apply(&mut query, &mut params);
+        filter.apply("local", &mut query, &mut params);
 
         assert_eq!(
             query,
-            "SELECT * FROM plays WHERE 1=1 AND timestamp >= ? AND timestamp <= ?"
+            "SELECT * FROM plays WHERE 1=1 AND local >= ? AND local <= ?"
         );
-        assert_eq!(params, vec!["2024-01-01", "2024-12-31"]);
+        assert_eq!(params, ["2024-01-01 00:00:00", "2024-12-31 23:59:59"]);
+    }
+
+    #[test]
+    fn test_bounds_are_converted_to_utc() {
+        let berlin = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(utc_bound("2024-07-01", &berlin), "2024-06-30 22:00:00");
+        assert_eq!(
+            utc_bound("2024-07-01 23:59:59", &berlin),
+            "2024-07-01 21:59:59"
+        );
+        assert_eq!(utc_bound("not a date", &berlin), "not a date");
+    }
+
+    #[test]
+    fn test_offset_spans_follow_clock_changes() {
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        let change = at("2024-03-31 01:00:00");
+        let offset_at = |time: NaiveDateTime| if time < change { 60 } else { 120 };
+
+        let spans = offset_spans(at("2024-03-20 12:34:56"), at("2024-04-10 00:00:00"), offset_at);
+        assert_eq!(spans, [(at("2024-03-20 12:00:00"), 60), (change, 120)]);
+        assert_eq!(
+            offset_case("timestamp", &spans),
+            "CASE WHEN timestamp < TIMESTAMP '2024-03-31 01:00:00' THEN 60 ELSE 120 END"
+        );
+
+        let spans = offset_spans(at("2024-05-01 00:00:00"), at("2024-06-01 00:00:00"), offset_at);
+        assert_eq!(offset_case("timestamp", &spans), "120");
     }
 }
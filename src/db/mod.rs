@@ -5,80 +5,241 @@
 
 mod filter;
 mod queries;
-mod schema;
+mod reader;
+pub mod schema;
+mod worker;
 
-pub use filter::DateFilter;
+pub use filter::{ContentFilter, DateFilter, LocalTime};
 
+use chrono::{DateTime, Local, NaiveTime};
 use duckdb::Connection;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
-use crate::config::DatabaseConfig;
 use crate::context::ListeningContext;
-use crate::error::Result;
-use crate::track::TrackState;
+use crate::error::{Error, Result};
+use crate::track::{SkipReason, TrackState};
+
+use reader::{expect_answer, Answer, Query, Request};
+use worker::{Worker, Write};
 
 /// Database wrapper for music analytics using DuckDB
 #[derive(Clone)]
 pub struct Database {
-    conn: Arc<Mutex<Connection>>,
+    backend: Backend,
+    /// Content types included in stats
+    content: ContentFilter,
+    /// Clock that days and hours in stats follow
+    local_time: LocalTime,
+    /// Time of day that days in stats start
+    day_start: NaiveTime,
+}
+
+/// How a [`Database`] reaches the file
+#[derive(Clone)]
+enum Backend {
+    /// Read-write connection on threads of its own; only one process can
+    /// hold one
+    Worker(Arc<Worker>),
+    /// Reader of the file at this path, see [`Database::open_read_only`]
+    Reader(Arc<PathBuf>),
 }
 
 impl Database {
-    /// Create a new database connection
-    ///
-    /// # Arguments
-    /// * `config` - Database configuration
-    /// * `data_dir` - Default data directory for local DB
-    pub async fn new(config: &DatabaseConfig, data_dir: &Path) -> Result<Self> {
-        let db_path = if let Some(ref path) = config.path {
-            let path = Path::new(path);
-            if let Some(parent) = path.parent() {
-                if !parent.as_os_str().is_empty() {
-                    std::fs::create_dir_all(parent)?;
-                }
+    /// Open the database file at `path`, as given by
+    /// [`Config::database_path`](crate::config::Config::database_path),
+    /// creating it and its directory if needed
+    pub async fn new(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
             }
-            path.to_path_buf()
-        } else {
-            std::fs::create_dir_all(data_dir)?;
-            data_dir.join("listens.duckdb")
-        };
+        }
+        let db_path = path.to_path_buf();
 
-        // Open DuckDB connection (synchronous, so we use spawn_blocking)
-        let db_path_str = db_path.to_string_lossy().to_string();
-        let conn = tokio::task::spawn_blocking(move || Connection::open(&db_path_str))
-            .await
-            .map_err(|e| crate::error::Error::other(e.to_string()))??;
+        // Open DuckDB connection and bring its schema up to date
+        // (synchronous, so we use spawn_blocking)
+        let worker = tokio::task::spawn_blocking(move || -> Result<Worker> {
+            let conn = Connection::open(&db_path)?;
+            schema::migrate(&conn, Some(&db_path))?;
+            Worker::start(conn)
+        })
+        .await
+        .map_err(|e| Error::other(e.to_string()))??;
+
+        Ok(Self {
+            backend: Backend::Worker(Arc::new(worker)),
+            content: ContentFilter::default(),
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        })
+    }
+
+    /// Open the database for stats only, alongside a running tracker.
+    ///
+    /// DuckDB lets only one process open the file at a time for writing, and
+    /// the tracker holds it while it runs, so queries go to the tracker over
+    /// D-Bus. When no tracker is running, each query opens the file
+    /// read-only. Logging listens through a reader fails.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no tracker is running and the file can't be created or
+    /// brought up to date.
+    pub async fn open_read_only(path: &Path) -> Result<Self> {
+        // A tracker brings the schema up to date itself when it starts
+        if !crate::mpris::is_tracker_running().await && !schema::pending_at(path)?.is_empty() {
+            drop(Self::new(path).await?);
+        }
+
+        Ok(Self {
+            backend: Backend::Reader(Arc::new(path.to_path_buf())),
+            content: ContentFilter::default(),
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        })
+    }
+
+    /// Set which content types stats include (music only by default)
+    #[must_use]
+    pub const fn with_content_filter(mut self, content: ContentFilter) -> Self {
+        self.content = content;
+        self
+    }
+
+    /// Set which clock days and hours in stats follow (where each play
+    /// happened by default)
+    #[allow(dead_code)] // Used by `music-stats`
+    #[must_use]
+    pub const fn with_local_time(mut self, local_time: LocalTime) -> Self {
+        self.local_time = local_time;
+        self
+    }
+
+    /// Set the time of day that days in stats start (midnight by default)
+    #[must_use]
+    pub const fn with_day_start(mut self, day_start: NaiveTime) -> Self {
+        self.day_start = day_start;
+        self
+    }
+
+    /// The database threads, which readers don't have
+    fn worker(&self) -> Result<&Worker> {
+        match &self.backend {
+            Backend::Worker(worker) => Ok(worker),
+            Backend::Reader(_) => Err(Error::other("Database is open read-only")),
+        }
+    }
 
-        let instance = Self {
-            conn: Arc::new(Mutex::new(conn)),
+    /// Run a stats query over the content types this database includes,
+    /// by its clock and day start.
+    ///
+    /// Dropping the future cancels the query.
+    async fn ask(&self, query: Query) -> Result<Answer> {
+        let request = Request {
+            query,
+            content: self.content,
+            local_time: self.local_time,
+            day_start: self.day_start,
         };
+        match &self.backend {
+            Backend::Worker(worker) => worker.query(request).await,
+            Backend::Reader(path) => reader::ask(path, &request).await,
+        }
+    }
 
-        // Initialize schema
-        instance.init().await?;
+    /// Answer a JSON stats query from a reader
+    ///
+    /// Dropping the future cancels the query.
+    pub(crate) async fn answer_query(&self, request: &str) -> Result<String> {
+        let request = serde_json::from_str(request)?;
+        let answer = self.worker()?.query(request).await?;
+        Ok(serde_json::to_string(&answer)?)
+    }
 
-        Ok(instance)
+    /// Run a query and collect its rows, for tests
+    #[cfg(test)]
+    pub(crate) async fn query_rows<T: Send + 'static>(
+        &self,
+        sql: &str,
+        mut row: impl FnMut(&duckdb::Row<'_>) -> duckdb::Result<T> + Send + 'static,
+    ) -> Vec<T> {
+        let sql = sql.to_string();
+        let (reply, rows) = tokio::sync::oneshot::channel();
+        let run = move |conn: &Connection| {
+            let mut stmt = conn.prepare(&sql).unwrap();
+            let rows = stmt
+                .query_map([], |r| row(r))
+                .unwrap()
+                .collect::<duckdb::Result<_>>()
+                .unwrap();
+            let _ = reply.send(rows);
+        };
+        self.worker().unwrap().write(Write::Run(Box::new(run))).await.unwrap();
+        rows.await.unwrap()
     }
 
-    /// Initialize database schema
-    async fn init(&self) -> Result<()> {
-        let conn = self.conn.lock().await;
-        // Run schema initialization synchronously
-        schema::init_schema(&conn)?;
-        Ok(())
+    /// Log a play that ended at `ended_at` as part of a listening session
+    ///
+    /// # Errors
+    ///
+    /// Fails if the play or its session can't be written.
+    pub async fn log_play(
+        &self,
+        state: &TrackState,
+        context: &ListeningContext,
+        session_id: &str,
+        ended_at: DateTime<Local>,
+    ) -> Result<()> {
+        self.worker()?
+            .write(Write::Play {
+                state: Box::new(state.clone()),
+                context: Box::new(context.clone()),
+                session_id: session_id.to_string(),
+                ended_at,
+            })
+            .await
+    }
+
+    /// Log a listen that fell short of the play thresholds
+    ///
+    /// # Errors
+    ///
+    /// Fails if the skip can't be written.
+    pub async fn log_skip(
+        &self,
+        state: &TrackState,
+        reason: SkipReason,
+        ended_at: DateTime<Local>,
+    ) -> Result<()> {
+        self.worker()?
+            .write(Write::Skip {
+                state: Box::new(state.clone()),
+                reason,
+                ended_at,
+            })
+            .await
     }
 
-    /// Log a completed play to the database
-    pub async fn log_play(&self, state: &TrackState, context: &ListeningContext) -> Result<()> {
-        let conn = self.conn.lock().await;
-        queries::insert_play(&conn, state, context)
+    /// Count a play made in private mode without recording it, toward the
+    /// day it started in for days starting at `day_start`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the day's private play count can't be updated.
+    pub async fn log_private_play(&self, state: &TrackState, day_start: NaiveTime) -> Result<()> {
+        self.worker()?
+            .write(Write::PrivatePlay {
+                state: Box::new(state.clone()),
+                day_start,
+            })
+            .await
     }
 
     /// Get total play count
     pub async fn get_play_count(&self) -> Result<i64> {
-        let conn = self.conn.lock().await;
-        queries::get_play_count(&conn)
+        expect_answer!(self.ask(Query::PlayCount).await?, PlayCount)
     }
 
     /// Get top artists by play count
@@ -88,11 +249,12 @@ impl Database {
         end_date: Option<&str>,
         limit: u32,
     ) -> Result<Vec<ArtistStats>> {
-        // Clone the date strings to avoid lifetime issues
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        queries::get_top_artists(&conn, start.as_deref(), end.as_deref(), limit)
+        let query = Query::TopArtists {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+            limit,
+        };
+        expect_answer!(self.ask(query).await?, TopArtists)
     }
 
     /// Get top albums by play count
@@ -102,10 +264,12 @@ impl Database {
         end_date: Option<&str>,
         limit: u32,
     ) -> Result<Vec<AlbumStats>> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        queries::get_top_albums(&conn, start.as_deref(), end.as_deref(), limit)
+        let query = Query::TopAlbums {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+            limit,
+        };
+        expect_answer!(self.ask(query).await?, TopAlbums)
     }
 
     /// Get top tracks by play count
@@ -115,10 +279,12 @@ impl Database {
         end_date: Option<&str>,
         limit: u32,
     ) -> Result<Vec<TrackStats>> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        queries::get_top_tracks(&conn, start.as_deref(), end.as_deref(), limit)
+        let query = Query::TopTracks {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+            limit,
+        };
+        expect_answer!(self.ask(query).await?, TopTracks)
     }
 
     /// Get listening stats overview
@@ -127,97 +293,103 @@ impl Database {
         start_date: Option<&str>,
         end_date: Option<&str>,
     ) -> Result<OverviewStats> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        queries::get_overview_stats(&conn, start.as_deref(), end.as_deref())
+        let query = Query::Overview {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, Overview)
     }
 
     // The following methods are public API for binaries (GUI, music-stats)
     // but not used within the library itself.
 
     /// Get listening streaks (current and longest)
-    #[allow(dead_code)]
+    #[allow(dead_code)] // Used by `music-stats` and the GUI
     pub async fn get_listening_streaks(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
     ) -> Result<crate::analytics::StreakInfo> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_listening_streaks(&conn, start.as_deref(), end.as_deref())
+        let query = Query::ListeningStreaks {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, ListeningStreaks)
     }
 
     /// Get night owl score (percentage of plays between midnight and 6am)
-    #[allow(dead_code)]
+    #[allow(dead_code)] // Used by `music-stats` and the GUI
     pub async fn get_night_owl_score(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
     ) -> Result<crate::analytics::NightOwlScore> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_night_owl_score(&conn, start.as_deref(), end.as_deref())
+        let query = Query::NightOwlScore {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, NightOwlScore)
     }
 
     /// Get hourly listening heatmap
-    #[allow(dead_code)]
+    #[allow(dead_code)] // Used by `music-stats` and the GUI
     pub async fn get_hourly_heatmap(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
     ) -> Result<crate::analytics::HourlyHeatmap> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_hourly_heatmap(&conn, start.as_deref(), end.as_deref())
+        let query = Query::HourlyHeatmap {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, HourlyHeatmap)
     }
 
-    /// Get genre statistics
-    #[allow(dead_code)]
-    pub async fn get_genre_stats(
+    /// Get skip rate (percentage of started tracks the user skipped)
+    #[allow(dead_code)] // Used by `music-stats` and the GUI
+    pub async fn get_skip_rate(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
-        limit: u32,
-    ) -> Result<Vec<(String, i64, i64)>> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_genre_stats(&conn, start.as_deref(), end.as_deref(), limit)
+    ) -> Result<(i64, i64, f64)> {
+        let query = Query::SkipRate {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, SkipRate)
     }
 
-    /// Get skip rate (percentage of plays with less than 50% completion)
-    #[allow(dead_code)]
-    pub async fn get_skip_rate(
+    /// Get listening session statistics
+    #[allow(dead_code)] // Used by `music-stats` and the GUI
+    pub async fn get_session_stats(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
-    ) -> Result<(i64, i64, f64)> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_skip_rate(&conn, start.as_deref(), end.as_deref())
+    ) -> Result<crate::analytics::SessionInfo> {
+        let query = Query::SessionStats {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, SessionStats)
     }
 
     /// Get daily contribution data for the contribution graph
-    #[allow(dead_code)]
+    #[allow(dead_code)] // Used by the GUI
     pub async fn get_daily_contributions(
         &self,
         start_date: Option<&str>,
         end_date: Option<&str>,
     ) -> Result<crate::analytics::DailyContribution> {
-        let start = start_date.map(String::from);
-        let end = end_date.map(String::from);
-        let conn = self.conn.lock().await;
-        crate::analytics::get_daily_contributions(&conn, start.as_deref(), end.as_deref())
+        let query = Query::DailyContributions {
+            start: start_date.map(String::from),
+            end: end_date.map(String::from),
+        };
+        expect_answer!(self.ask(query).await?, DailyContributions)
     }
 }
 
 /// Aggregated statistics for an artist.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArtistStats {
     /// Artist name.
     pub artist: String,
@@ -228,7 +400,7 @@ pub struct ArtistStats {
 }
 
 /// Aggregated statistics for an album.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlbumStats {
     /// Album name.
     pub album: String,
@@ -244,7 +416,7 @@ pub struct AlbumStats {
 }
 
 /// Aggregated statistics for a track.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrackStats {
     /// Track title.
     pub title: String,
@@ -260,7 +432,7 @@ pub struct TrackStats {
 }
 
 /// Overview statistics for a time period.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OverviewStats {
     /// Total number of plays.
     pub total_plays: i64,
@@ -272,4 +444,6 @@ pub struct OverviewStats {
     pub unique_albums: i64,
     /// Count of unique tracks.
     pub unique_tracks: i64,
+    /// Plays made in private mode, counted without details.
+    pub private_plays: i64,
 }
@@ -1,19 +1,23 @@
 //! Database query implementations for DuckDB
 
+use chrono::{DateTime, Local, NaiveTime};
 use duckdb::{params, Connection};
 
 use crate::context::ListeningContext;
+use crate::date_range;
 use crate::error::Result;
-use crate::track::TrackState;
+use crate::track::{SkipReason, TrackState};
 
-use super::filter::DateFilter;
+use super::filter::{ContentFilter, DateFilter, LocalTime};
 use super::{AlbumStats, ArtistStats, OverviewStats, TrackStats};
 
-/// Insert a play record into the database
+/// Insert a play record into the database; `ended_at` is stored as its timestamp
 pub fn insert_play(
     conn: &Connection,
     state: &TrackState,
     context: &ListeningContext,
+    session_id: &str,
+    ended_at: DateTime<Local>,
 ) -> Result<()> {
     let track = &state.track;
 
@@ -26,7 +30,10 @@ pub fn insert_play(
             seek_count, intro_skipped, seek_forward_ms, seek_backward_ms,
             app_volume, system_volume, effective_volume,
             hour_of_day, day_of_week, is_weekend, season,
-            active_window, screen_on, on_battery, player_name, is_local
+            active_window, screen_on, on_battery, player_name, is_local,
+            pause_count, paused_ms, heard_ms, distinct_seconds_heard,
+            is_repeat, loop_status, shuffle, playback_rate,
+            session_id, source, station, content_type, tags, timestamp, utc_offset_minutes, timezone
         )
         VALUES (
             ?1, ?2, ?3, ?4, ?5, ?6,
@@ -35,7 +42,10 @@ pub fn insert_play(
             ?17, ?18, ?19, ?20,
             ?21, ?22, ?23,
             ?24, ?25, ?26, ?27,
-            ?28, ?29, ?30, ?31, ?32
+            ?28, ?29, ?30, ?31, ?32,
+            ?33, ?34, ?35, ?36,
+            ?37, ?38, ?39, ?40,
+            ?41, ?42, ?43, ?44, ?45, CAST(?46 AS TIMESTAMP), ?47, ?48
         )
         ",
         params![
@@ -56,7 +66,7 @@ pub fn insert_play(
             track.composer.as_deref(),
             track.musicbrainz_track_id.as_deref(),
             if state.seek_count > 0 {
-                Some(state.seek_count as i64)
+                Some(i64::from(state.seek_count))
             } else {
                 None
             },
@@ -76,19 +86,162 @@ pub fn insert_play(
             state.effective_volume(),
             context.hour_of_day,
             context.day_of_week,
-            if context.is_weekend { 1i64 } else { 0i64 },
+            i64::from(context.is_weekend),
             context.season.as_str(),
             context.active_window.as_deref(),
-            context.screen_on.map(|b| if b { 1i64 } else { 0i64 }),
-            context.on_battery.map(|b| if b { 1i64 } else { 0i64 }),
+            context.screen_on.map(i64::from),
+            context.on_battery.map(i64::from),
             state.player_name.as_deref(),
-            if state.is_local { 1i64 } else { 0i64 },
+            i64::from(state.is_local),
+            if state.pause_count > 0 {
+                Some(i64::from(state.pause_count))
+            } else {
+                None
+            },
+            if state.pause_count > 0 {
+                Some(state.paused_ms())
+            } else {
+                None
+            },
+            Some(state.heard_ms()).filter(|&ms| ms > 0),
+            Some(state.distinct_seconds_heard()).filter(|&secs| secs > 0),
+            i64::from(state.is_repeat),
+            state.loop_status.as_deref(),
+            state.shuffle.map(i64::from),
+            state.rate,
+            session_id,
+            track.source(),
+            track.station.as_deref(),
+            track.content_type.as_str(),
+            Some(state.tags.join(",")).filter(|tags| !tags.is_empty()),
+            db_timestamp(ended_at),
+            utc_offset_minutes(ended_at),
+            state.timezone.as_deref(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Add a play that ended at `ended_at` to its listening session, creating
+/// the session on its first play
+pub fn record_session_play(
+    conn: &Connection,
+    session_id: &str,
+    state: &TrackState,
+    ended_at: DateTime<Local>,
+) -> Result<()> {
+    // Wall-clock span of the listen, so a new session starts when its first play did
+    let listen_ms = state
+        .start_timestamp
+        .map_or(0, |start| (ended_at - start).num_milliseconds().max(0));
+
+    conn.execute(
+        r"
+        INSERT INTO sessions (id, start_time, end_time, track_count, total_ms, player_name)
+        VALUES (
+            ?1,
+            CAST(?5 AS TIMESTAMP) - to_milliseconds(?2),
+            CAST(?5 AS TIMESTAMP),
+            1,
+            ?3,
+            ?4
+        )
+        ON CONFLICT (id) DO UPDATE SET
+            end_time = excluded.end_time,
+            track_count = sessions.track_count + 1,
+            total_ms = sessions.total_ms + excluded.total_ms
+        ",
+        params![
+            session_id,
+            listen_ms,
+            state.played_ms(),
+            state.player_name.as_deref(),
+            db_timestamp(ended_at),
         ],
     )?;
 
     Ok(())
 }
 
+/// Insert a skip record into the database; `ended_at` is stored as its timestamp
+pub fn insert_skip(
+    conn: &Connection,
+    state: &TrackState,
+    reason: SkipReason,
+    ended_at: DateTime<Local>,
+) -> Result<()> {
+    let track = &state.track;
+
+    conn.execute(
+        r"
+        INSERT INTO skips (
+            title, artist, album, album_artist,
+            duration_ms, played_ms, position_ms, reason,
+            file_path, player_name, is_local, content_type, timestamp,
+            utc_offset_minutes, timezone
+        )
+        VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, CAST(?13 AS TIMESTAMP),
+            ?14, ?15
+        )
+        ",
+        params![
+            track.title.as_deref(),
+            track.artist.as_deref(),
+            track.album.as_deref(),
+            track.album_artist.as_deref(),
+            track.duration_us.map(|d| d / 1000), // Convert to ms
+            state.played_ms(),
+            state.estimated_position_us() / 1000,
+            reason.as_str(),
+            track.file_path.as_deref(),
+            state.player_name.as_deref(),
+            i64::from(state.is_local),
+            track.content_type.as_str(),
+            db_timestamp(ended_at),
+            utc_offset_minutes(ended_at),
+            state.timezone.as_deref(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Format a time the way `current_timestamp` is stored: naive UTC
+fn db_timestamp(time: DateTime<Local>) -> String {
+    time.naive_utc().to_string()
+}
+
+/// Offset of a local time from UTC, as stored alongside its timestamp
+const fn utc_offset_minutes(time: DateTime<Local>) -> i32 {
+    time.offset().local_minus_utc() / 60
+}
+
+/// Count a play made in private mode, without recording what was played,
+/// toward the day it started in for days starting at `day_start`
+pub fn insert_private_play(
+    conn: &Connection,
+    state: &TrackState,
+    day_start: NaiveTime,
+) -> Result<()> {
+    let started = state.start_timestamp.unwrap_or_else(Local::now);
+    let day = date_range::day_of(started.naive_local(), day_start).to_string();
+
+    conn.execute(
+        r"
+        INSERT INTO private_plays (day, content_type, play_count, total_ms)
+        VALUES (CAST(?1 AS DATE), ?2, 1, ?3)
+        ON CONFLICT (day, content_type) DO UPDATE SET
+            play_count = play_count + 1,
+            total_ms = total_ms + excluded.total_ms
+        ",
+        params![day, state.track.content_type.as_str(), state.played_ms()],
+    )?;
+
+    Ok(())
+}
+
 /// Get total play count
 pub fn get_play_count(conn: &Connection) -> Result<i64> {
     let mut stmt = conn.prepare("SELECT COUNT(*) FROM plays")?;
@@ -126,12 +279,16 @@ pub fn get_top_artists(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
     limit: u32,
 ) -> Result<Vec<ArtistStats>> {
     // Build date filter conditions
     let mut date_conditions = String::new();
     let mut param_values = Vec::new();
-    DateFilter::new(start_date, end_date).apply(&mut date_conditions, &mut param_values);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut date_conditions, &mut param_values);
+    content.apply(&mut date_conditions);
 
     // Strategy:
     // 1. Find "independent artists" - those with plays where album_artist has no " & " or ", "
@@ -252,6 +409,8 @@ pub fn get_top_albums(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
     limit: u32,
 ) -> Result<Vec<AlbumStats>> {
     // Use album_artist if available, otherwise use the most frequent artist for the album
@@ -272,7 +431,9 @@ pub fn get_top_albums(
     .to_string();
 
     let mut param_values = Vec::new();
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut param_values);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut param_values);
+    content.apply(&mut query);
 
     query.push_str(&format!(
         " GROUP BY LOWER(album) ORDER BY play_count DESC LIMIT {limit}"
@@ -303,6 +464,8 @@ pub fn get_top_tracks(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
     limit: u32,
 ) -> Result<Vec<TrackStats>> {
     // Normalize artist names to aggregate tracks with featuring artists
@@ -321,7 +484,9 @@ pub fn get_top_tracks(
     );
 
     let mut param_values = Vec::new();
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut param_values);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut param_values);
+    content.apply(&mut query);
 
     query.push_str(&format!(
         " GROUP BY LOWER(title), LOWER({PRIMARY_ARTIST_SQL}) ORDER BY play_count DESC LIMIT {limit}"
@@ -352,6 +517,9 @@ pub fn get_overview_stats(
     conn: &Connection,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    content: ContentFilter,
+    local_time: LocalTime,
+    day_start: NaiveTime,
 ) -> Result<OverviewStats> {
     let mut query = r"
         SELECT
@@ -366,7 +534,9 @@ pub fn get_overview_stats(
     .to_string();
 
     let mut param_values = Vec::new();
-    DateFilter::new(start_date, end_date).apply(&mut query, &mut param_values);
+    let local = local_time.timestamp_sql(conn)?;
+    DateFilter::new(start_date, end_date).apply(&local, &mut query, &mut param_values);
+    content.apply(&mut query);
 
     let params = DateFilter::params_as_refs(&param_values);
     let mut stmt = conn.prepare(&query)?;
@@ -377,11 +547,88 @@ pub fn get_overview_stats(
             unique_artists: row.get(2)?,
             unique_albums: row.get(3)?,
             unique_tracks: row.get(4)?,
+            private_plays: 0,
         })
     });
 
-    match result {
-        Ok(stats) => Ok(stats),
-        Err(_) => Ok(OverviewStats::default()),
+    let mut stats = result.unwrap_or_default();
+    stats.private_plays =
+        get_private_play_count(conn, start_date, end_date, content, day_start)?;
+    Ok(stats)
+}
+
+/// Count plays made in private mode, on the days the range covers
+fn get_private_play_count(
+    conn: &Connection,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    content: ContentFilter,
+    day_start: NaiveTime,
+) -> Result<i64> {
+    let mut query =
+        "SELECT COALESCE(SUM(play_count), 0) FROM private_plays WHERE 1=1".to_string();
+
+    let mut param_values = Vec::new();
+    DateFilter::new(start_date, end_date).apply_to_days(
+        "day",
+        day_start,
+        &mut query,
+        &mut param_values,
+    );
+    content.apply(&mut query);
+
+    let params = DateFilter::params_as_refs(&param_values);
+    let count = conn
+        .prepare(&query)?
+        .query_row(params.as_slice(), |row| row.get(0))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{TimeDelta, Timelike};
+
+    use crate::db::schema::migrate;
+    use crate::journal::ListenCheckpoint;
+
+    #[test]
+    fn test_recovered_play_keeps_its_time() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn, None).unwrap();
+
+        // A listen checkpointed yesterday, before the tracker was killed
+        let mut state = TrackState::new();
+        state.track.title = Some("Song".into());
+        state.start_playing();
+        let mut checkpoint = ListenCheckpoint::capture(&state, "session").unwrap();
+        let saved_at = (Local::now() - TimeDelta::days(1))
+            .with_nanosecond(0)
+            .unwrap();
+        checkpoint.start_timestamp = saved_at - TimeDelta::minutes(3);
+        checkpoint.saved_at = saved_at;
+        // Taken somewhere else than the tracker is now
+        checkpoint.timezone = Some("Asia/Tokyo".into());
+        checkpoint.played_ms = 180_000;
+        let state = checkpoint.into_state();
+
+        insert_play(&conn, &state, &ListeningContext::default(), "session", saved_at).unwrap();
+        record_session_play(&conn, "session", &state, saved_at).unwrap();
+
+        let query = "SELECT CAST(timestamp AS VARCHAR), utc_offset_minutes, timezone FROM plays";
+        let (timestamp, offset, timezone): (String, i32, Option<String>) = conn
+            .query_row(query, [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        assert_eq!(timestamp, db_timestamp(saved_at));
+        assert_eq!(offset, saved_at.offset().local_minus_utc() / 60);
+        assert_eq!(timezone.as_deref(), Some("Asia/Tokyo"));
+
+        let query = "SELECT CAST(start_time AS VARCHAR), CAST(end_time AS VARCHAR) FROM sessions";
+        let (start, end): (String, String) = conn
+            .query_row(query, [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(start, db_timestamp(saved_at - TimeDelta::minutes(3)));
+        assert_eq!(end, db_timestamp(saved_at));
     }
 }
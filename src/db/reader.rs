@@ -0,0 +1,301 @@
+//! Reading the database alongside the tracker
+//!
+//! Only one process can open the database file read-write, and while it
+//! does, no other process can open it at all. The tracker keeps the database open for
+//! as long as it runs, so readers such as the GUI and `music-stats` send
+//! their queries to it over D-Bus instead. When no tracker is running they
+//! open the file read-only for each query, so a tracker starting up is never
+//! locked out for long. Either way, dropping a query's future cancels it.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::NaiveTime;
+use duckdb::{AccessMode, Connection, InterruptHandle};
+use serde::{Deserialize, Serialize};
+
+use crate::analytics::{
+    self, DailyContribution, HourlyHeatmap, NightOwlScore, SessionInfo, StreakInfo,
+};
+use crate::error::{Error, Result};
+use crate::mpris;
+
+use super::{
+    queries, AlbumStats, ArtistStats, ContentFilter, LocalTime, OverviewStats, TrackStats,
+};
+
+/// A stats query, with the date range it covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub(super) enum Query {
+    PlayCount,
+    TopArtists {
+        start: Option<String>,
+        end: Option<String>,
+        limit: u32,
+    },
+    TopAlbums {
+        start: Option<String>,
+        end: Option<String>,
+        limit: u32,
+    },
+    TopTracks {
+        start: Option<String>,
+        end: Option<String>,
+        limit: u32,
+    },
+    Overview {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    ListeningStreaks {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    NightOwlScore {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    HourlyHeatmap {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    SkipRate {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    SessionStats {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    DailyContributions {
+        start: Option<String>,
+        end: Option<String>,
+    },
+}
+
+/// A query, the content types it covers and how it buckets days and hours
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Request {
+    pub query: Query,
+    pub content: ContentFilter,
+    #[serde(default)]
+    pub local_time: LocalTime,
+    /// Time of day that days start
+    #[serde(default)]
+    pub day_start: NaiveTime,
+}
+
+/// The result of a [`Query`], in the variant of the same name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "answer", content = "value", rename_all = "snake_case")]
+pub(super) enum Answer {
+    PlayCount(i64),
+    TopArtists(Vec<ArtistStats>),
+    TopAlbums(Vec<AlbumStats>),
+    TopTracks(Vec<TrackStats>),
+    Overview(OverviewStats),
+    ListeningStreaks(StreakInfo),
+    NightOwlScore(NightOwlScore),
+    HourlyHeatmap(HourlyHeatmap),
+    SkipRate((i64, i64, f64)),
+    SessionStats(SessionInfo),
+    DailyContributions(DailyContribution),
+}
+
+/// Take the value out of an [`Answer`], which must be the `$variant` one
+macro_rules! expect_answer {
+    ($answer:expr, $variant:ident) => {
+        match $answer {
+            $crate::db::reader::Answer::$variant(value) => Ok(value),
+            other => Err($crate::error::Error::other(format!(
+                "Expected {} answer, got {other:?}",
+                stringify!($variant)
+            ))),
+        }
+    };
+}
+pub(super) use expect_answer;
+
+/// Run a query against an open connection
+pub(super) fn answer(conn: &Connection, request: &Request) -> Result<Answer> {
+    let content = request.content;
+    let local_time = request.local_time;
+    let day_start = request.day_start;
+    Ok(match &request.query {
+        Query::PlayCount => Answer::PlayCount(queries::get_play_count(conn)?),
+        Query::TopArtists { start, end, limit } => Answer::TopArtists(queries::get_top_artists(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+            content,
+            local_time,
+            *limit,
+        )?),
+        Query::TopAlbums { start, end, limit } => Answer::TopAlbums(queries::get_top_albums(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+            content,
+            local_time,
+            *limit,
+        )?),
+        Query::TopTracks { start, end, limit } => Answer::TopTracks(queries::get_top_tracks(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+            content,
+            local_time,
+            *limit,
+        )?),
+        Query::Overview { start, end } => Answer::Overview(queries::get_overview_stats(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+            content,
+            local_time,
+            day_start,
+        )?),
+        Query::ListeningStreaks { start, end } => {
+            Answer::ListeningStreaks(analytics::get_listening_streaks(
+                conn,
+                start.as_deref(),
+                end.as_deref(),
+                content,
+                local_time,
+                day_start,
+            )?)
+        }
+        Query::NightOwlScore { start, end } => {
+            Answer::NightOwlScore(analytics::get_night_owl_score(
+                conn,
+                start.as_deref(),
+                end.as_deref(),
+                content,
+                local_time,
+            )?)
+        }
+        Query::HourlyHeatmap { start, end } => {
+            Answer::HourlyHeatmap(analytics::get_hourly_heatmap(
+                conn,
+                start.as_deref(),
+                end.as_deref(),
+                content,
+                local_time,
+            )?)
+        }
+        Query::SkipRate { start, end } => Answer::SkipRate(analytics::get_skip_rate(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+            content,
+            local_time,
+        )?),
+        Query::SessionStats { start, end } => Answer::SessionStats(analytics::get_session_stats(
+            conn,
+            start.as_deref(),
+            end.as_deref(),
+        )?),
+        Query::DailyContributions { start, end } => {
+            Answer::DailyContributions(analytics::get_daily_contributions(
+                conn,
+                start.as_deref(),
+                end.as_deref(),
+                content,
+                local_time,
+                day_start,
+            )?)
+        }
+    })
+}
+
+/// Answer a request from a reader: by the running tracker if there is one,
+/// otherwise from the file at `path`
+pub(super) async fn ask(path: &Path, request: &Request) -> Result<Answer> {
+    if let Some(answer) = mpris::query_tracker(&serde_json::to_string(request)?).await? {
+        return Ok(serde_json::from_str(&answer)?);
+    }
+
+    let path = path.to_path_buf();
+    let conn = tokio::task::spawn_blocking(move || -> Result<Connection> {
+        let config = duckdb::Config::default().access_mode(AccessMode::ReadOnly)?;
+        Ok(Connection::open_with_flags(&path, config)?)
+    })
+    .await
+    .map_err(|e| Error::other(e.to_string()))??;
+
+    // The connection closes when the query ends, after which interrupting it
+    // does nothing
+    let _cancel = InterruptOnDrop(conn.interrupt_handle());
+    let request = request.clone();
+    tokio::task::spawn_blocking(move || answer(&conn, &request))
+        .await
+        .map_err(|e| Error::other(e.to_string()))?
+}
+
+/// Interrupts a connection's query if its caller stops waiting
+struct InterruptOnDrop(Arc<InterruptHandle>);
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        self.0.interrupt();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::schema;
+
+    #[test]
+    fn test_answer_json_round_trip() {
+        // As the tracker answers readers
+        let answer_json = |conn: &Connection, request: &str| -> Result<String> {
+            let request: Request = serde_json::from_str(request)?;
+            Ok(serde_json::to_string(&answer(conn, &request)?)?)
+        };
+
+        let conn = Connection::open_in_memory().unwrap();
+        schema::migrate(&conn, None).unwrap();
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, artist, played_ms) VALUES ('One', 'A', 1000);
+            INSERT INTO plays (title, artist, played_ms) VALUES ('Two', 'A', 2000);
+            INSERT INTO plays (title, artist, played_ms) VALUES ('Three', 'B', 3000);
+            ",
+        )
+        .unwrap();
+
+        let request = Request {
+            query: Query::TopArtists {
+                start: None,
+                end: None,
+                limit: 10,
+            },
+            content: ContentFilter::default(),
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        };
+        let json = answer_json(&conn, &serde_json::to_string(&request).unwrap()).unwrap();
+        let artists: Vec<ArtistStats> =
+            expect_answer!(serde_json::from_str(&json).unwrap(), TopArtists).unwrap();
+        let summary: Vec<(&str, i64)> = artists
+            .iter()
+            .map(|artist| (artist.artist.as_str(), artist.play_count))
+            .collect();
+        assert_eq!(summary, [("A", 2), ("B", 1)]);
+
+        let heatmap = Request {
+            query: Query::HourlyHeatmap {
+                start: None,
+                end: None,
+            },
+            content: ContentFilter::All,
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        };
+        let json = answer_json(&conn, &serde_json::to_string(&heatmap).unwrap()).unwrap();
+        assert!(expect_answer!(serde_json::from_str(&json).unwrap(), PlayCount).is_err());
+    }
+}
@@ -1,114 +1,656 @@
-//! Database schema initialization for DuckDB
+//! Database schema for DuckDB
+//!
+//! The schema is an ordered list of [`Migration`]s. The `schema_version`
+//! table records which have been applied, and [`migrate`] applies the rest in
+//! order, each in its own transaction. Add changes as new migrations at the
+//! end of [`MIGRATIONS`]; never edit one that has shipped.
 
-use duckdb::Connection;
+use std::path::{Path, PathBuf};
 
-use crate::error::Result;
+use duckdb::{params, AccessMode, Connection};
+use tracing::info;
 
-/// Initialize the database schema
-pub fn init_schema(conn: &Connection) -> Result<()> {
-    // Create main plays table
-    // DuckDB uses sequences for auto-increment
-    conn.execute_batch(
-        r"
-        CREATE SEQUENCE IF NOT EXISTS plays_id_seq;
-
-        CREATE TABLE IF NOT EXISTS plays (
-            id INTEGER PRIMARY KEY DEFAULT nextval('plays_id_seq'),
-            timestamp TIMESTAMP DEFAULT current_timestamp,
-            title VARCHAR NOT NULL,
-            artist VARCHAR,
-            album VARCHAR,
-            duration_ms BIGINT,
-            played_ms BIGINT,
-            file_path VARCHAR,
-
-            -- Extended metadata
-            genre VARCHAR,
-            album_artist VARCHAR,
-            track_number INTEGER,
-            disc_number INTEGER,
-            release_date VARCHAR,
-            art_url VARCHAR,
-            user_rating DOUBLE,
-            bpm INTEGER,
-            composer VARCHAR,
-            musicbrainz_track_id VARCHAR,
-
-            -- Seek tracking
-            seek_count INTEGER,
-            intro_skipped INTEGER,
-            seek_forward_ms BIGINT,
-            seek_backward_ms BIGINT,
-
-            -- Volume tracking
-            app_volume DOUBLE,
-            system_volume DOUBLE,
-            effective_volume DOUBLE,
-
-            -- Context tracking
-            hour_of_day INTEGER,
-            day_of_week INTEGER,
-            is_weekend INTEGER,
-            season VARCHAR,
-            active_window VARCHAR,
-            screen_on INTEGER,
-            on_battery INTEGER,
-
-            -- Player info
-            player_name VARCHAR,
-            is_local INTEGER
-        );
-        ",
-    )?;
+use crate::error::{Error, Result};
 
-    // Create indexes for common queries
-    // DuckDB handles IF NOT EXISTS for indexes
-    conn.execute_batch(
-        r"
-        CREATE INDEX IF NOT EXISTS idx_plays_timestamp ON plays(timestamp);
-        CREATE INDEX IF NOT EXISTS idx_plays_artist ON plays(artist);
-        CREATE INDEX IF NOT EXISTS idx_plays_album ON plays(album);
-        CREATE INDEX IF NOT EXISTS idx_plays_genre ON plays(genre);
-        CREATE INDEX IF NOT EXISTS idx_plays_title ON plays(title);
-        ",
+use super::filter::current_offset_sql;
+
+/// A schema change, applied once
+#[derive(Debug)]
+pub struct Migration {
+    /// Position in the migration order, starting at 1
+    pub version: u32,
+    /// What the migration changes
+    pub description: &'static str,
+    /// Whether it rewrites or drops existing data, so the database file is
+    /// backed up before it runs
+    pub destructive: bool,
+    sql: &'static str,
+    /// Run after `sql`, for changes SQL alone can't make
+    backfill: Option<fn(&Connection) -> Result<()>>,
+}
+
+/// Every migration, in order
+///
+/// Version 1 is the schema as it stood before versioning. It only creates
+/// what is missing, so unversioned databases from older releases adopt it.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        destructive: false,
+        sql: r"
+            -- Main plays table; DuckDB uses sequences for auto-increment
+            CREATE SEQUENCE IF NOT EXISTS plays_id_seq;
+
+            CREATE TABLE IF NOT EXISTS plays (
+                id INTEGER PRIMARY KEY DEFAULT nextval('plays_id_seq'),
+                timestamp TIMESTAMP DEFAULT current_timestamp,
+                title VARCHAR NOT NULL,
+                artist VARCHAR,
+                album VARCHAR,
+                duration_ms BIGINT,
+                played_ms BIGINT,
+                file_path VARCHAR,
+
+                -- Extended metadata
+                genre VARCHAR,
+                album_artist VARCHAR,
+                track_number INTEGER,
+                disc_number INTEGER,
+                release_date VARCHAR,
+                art_url VARCHAR,
+                user_rating DOUBLE,
+                bpm INTEGER,
+                composer VARCHAR,
+                musicbrainz_track_id VARCHAR,
+
+                -- Seek tracking
+                seek_count INTEGER,
+                intro_skipped INTEGER,
+                seek_forward_ms BIGINT,
+                seek_backward_ms BIGINT,
+
+                -- Volume tracking
+                app_volume DOUBLE,
+                system_volume DOUBLE,
+                effective_volume DOUBLE,
+
+                -- Context tracking
+                hour_of_day INTEGER,
+                day_of_week INTEGER,
+                is_weekend INTEGER,
+                season VARCHAR,
+                active_window VARCHAR,
+                screen_on INTEGER,
+                on_battery INTEGER,
+
+                -- Player info
+                player_name VARCHAR,
+                is_local INTEGER,
+
+                -- Pause tracking
+                pause_count INTEGER,
+                paused_ms BIGINT,
+
+                -- Heard audio (from playback position)
+                heard_ms BIGINT,
+                distinct_seconds_heard INTEGER,
+
+                -- Repeat tracking
+                is_repeat INTEGER,
+
+                -- Player playback options
+                loop_status VARCHAR,
+                shuffle INTEGER,
+                playback_rate DOUBLE,
+
+                -- Listening session
+                session_id VARCHAR,
+
+                -- Where the play came from ('radio' for songs split out of a stream)
+                source VARCHAR,
+                station VARCHAR,
+
+                -- Kind of media: music, podcast, audiobook or video
+                content_type VARCHAR,
+
+                -- Comma-separated tags added by rules
+                tags VARCHAR
+            );
+
+            -- Add columns introduced before versioning to existing databases
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS pause_count INTEGER;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS paused_ms BIGINT;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS heard_ms BIGINT;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS distinct_seconds_heard INTEGER;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS is_repeat INTEGER;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS loop_status VARCHAR;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS shuffle INTEGER;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS playback_rate DOUBLE;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS session_id VARCHAR;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS source VARCHAR;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS station VARCHAR;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS content_type VARCHAR;
+            ALTER TABLE plays ADD COLUMN IF NOT EXISTS tags VARCHAR;
+
+            -- Create indexes for common queries
+            -- DuckDB handles IF NOT EXISTS for indexes
+            CREATE INDEX IF NOT EXISTS idx_plays_timestamp ON plays(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_plays_artist ON plays(artist);
+            CREATE INDEX IF NOT EXISTS idx_plays_album ON plays(album);
+            CREATE INDEX IF NOT EXISTS idx_plays_genre ON plays(genre);
+            CREATE INDEX IF NOT EXISTS idx_plays_title ON plays(title);
+
+            -- Create skips table for listens that fell short of the play thresholds.
+            -- Kept separate from plays so top lists never see them.
+            CREATE SEQUENCE IF NOT EXISTS skips_id_seq;
+
+            CREATE TABLE IF NOT EXISTS skips (
+                id INTEGER PRIMARY KEY DEFAULT nextval('skips_id_seq'),
+                timestamp TIMESTAMP DEFAULT current_timestamp,
+                title VARCHAR NOT NULL,
+                artist VARCHAR,
+                album VARCHAR,
+                album_artist VARCHAR,
+                duration_ms BIGINT,
+                played_ms BIGINT,
+                position_ms BIGINT,
+                reason VARCHAR NOT NULL,
+                file_path VARCHAR,
+                player_name VARCHAR,
+                is_local INTEGER,
+                content_type VARCHAR
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_skips_timestamp ON skips(timestamp);
+
+            ALTER TABLE skips ADD COLUMN IF NOT EXISTS content_type VARCHAR;
+
+            -- Create private plays table: anonymous daily counts of listens made in
+            -- private mode, so gaps in the history show up as intentional
+            CREATE TABLE IF NOT EXISTS private_plays (
+                day DATE NOT NULL,
+                content_type VARCHAR NOT NULL,
+                play_count INTEGER NOT NULL DEFAULT 0,
+                total_ms BIGINT NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, content_type)
+            );
+
+            -- Create audio features table for future audio analysis
+            CREATE TABLE IF NOT EXISTS audio_features (
+                file_path VARCHAR PRIMARY KEY,
+                tempo DOUBLE,
+                energy DOUBLE,
+                danceability DOUBLE,
+                valence DOUBLE,
+                acousticness DOUBLE,
+                instrumentalness DOUBLE,
+                speechiness DOUBLE,
+                loudness DOUBLE,
+                key INTEGER,
+                mode INTEGER,
+                time_signature INTEGER,
+                analyzed_at TIMESTAMP DEFAULT current_timestamp
+            );
+
+            -- Create sessions table for session tracking
+            CREATE TABLE IF NOT EXISTS sessions (
+                id VARCHAR PRIMARY KEY,
+                start_time TIMESTAMP NOT NULL,
+                end_time TIMESTAMP,
+                track_count INTEGER DEFAULT 0,
+                total_ms BIGINT DEFAULT 0,
+                player_name VARCHAR
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_sessions_start ON sessions(start_time);
+            CREATE INDEX IF NOT EXISTS idx_plays_session ON plays(session_id);
+            ",
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        description: "Store the UTC offset and time zone of plays and skips",
+        destructive: true,
+        sql: r"
+            -- Timestamps are UTC; these give the local time where they happened
+            ALTER TABLE plays ADD COLUMN utc_offset_minutes INTEGER;
+            ALTER TABLE plays ADD COLUMN timezone VARCHAR;
+            ALTER TABLE skips ADD COLUMN utc_offset_minutes INTEGER;
+            ALTER TABLE skips ADD COLUMN timezone VARCHAR;
+            ",
+        backfill: Some(backfill_offsets),
+    },
+];
+
+/// Give plays and skips recorded before offsets were stored the offset and
+/// zone of the current zone at the time, the best guess there is
+fn backfill_offsets(conn: &Connection) -> Result<()> {
+    let timezone = crate::context::timezone();
+    for table in ["plays", "skips"] {
+        let offset = current_offset_sql(conn, table, "timestamp")?;
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET utc_offset_minutes = {offset}, timezone = ?
+                 WHERE utc_offset_minutes IS NULL"
+            ),
+            params![timezone],
+        )?;
+    }
+    Ok(())
+}
+
+/// Schema version this build brings databases up to
+#[must_use]
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Schema version of the database, 0 if it's new or predates versioning
+///
+/// # Errors
+///
+/// Fails if the version table can't be read.
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    let versioned: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM duckdb_tables()
+         WHERE database_name = current_database() AND table_name = 'schema_version'",
+        [],
+        |row| row.get(0),
     )?;
+    if !versioned {
+        return Ok(0);
+    }
+    Ok(conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| {
+        row.get(0)
+    })?)
+}
+
+/// Migrations the database hasn't had yet, in order
+///
+/// # Errors
+///
+/// Fails if the schema version can't be read, or the database was written by
+/// a newer build than this one.
+pub fn pending(conn: &Connection) -> Result<&'static [Migration]> {
+    pending_in(conn, MIGRATIONS)
+}
+
+fn pending_in<'a>(conn: &Connection, migrations: &'a [Migration]) -> Result<&'a [Migration]> {
+    let current = current_version(conn)?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if current > latest {
+        return Err(Error::other(format!(
+            "Database schema version {current} is newer than this build supports ({latest})"
+        )));
+    }
+    Ok(&migrations[migrations.partition_point(|migration| migration.version <= current)..])
+}
+
+/// Migrations the database file at `path` needs, without changing it
+///
+/// # Errors
+///
+/// Fails if an existing file can't be opened or its schema version read.
+pub fn pending_at(path: &Path) -> Result<&'static [Migration]> {
+    if !path.exists() {
+        return Ok(MIGRATIONS);
+    }
+    let config = duckdb::Config::default().access_mode(AccessMode::ReadOnly)?;
+    pending(&Connection::open_with_flags(path, config)?)
+}
+
+/// Bring the schema up to date, returning the migrations applied
+///
+/// If `path` is the database file and a pending migration is destructive, the
+/// file is copied next to itself first, e.g. `listens.duckdb.v1.bak`.
+///
+/// # Errors
+///
+/// Fails if the backup or a migration fails. The failed migration is rolled
+/// back; those before it stay applied.
+pub fn migrate(conn: &Connection, path: Option<&Path>) -> Result<&'static [Migration]> {
+    apply(conn, path, MIGRATIONS)
+}
+
+/// Create the schema of a new database and bring in the plays of an older
+/// one attached as `source`, such as a SQLite file from before DuckDB,
+/// returning how many were imported
+///
+/// The plays are imported under the initial schema and the later migrations
+/// run after, so they fill in what the old plays lack, such as UTC offsets,
+/// as they would for a database upgraded in place.
+///
+/// # Errors
+///
+/// Fails if the plays can't be copied or a migration fails.
+#[allow(dead_code)] // Used by `migrate-db`
+pub fn import_plays(conn: &Connection, source: &str) -> Result<i64> {
+    apply(conn, None, &MIGRATIONS[..1])?;
+
+    // By name, since the old table predates the newer columns
+    conn.execute(&format!("INSERT INTO plays BY NAME SELECT * FROM {source}.plays"), [])?;
+
+    // DuckDB can't ALTER SEQUENCE, so move the ID sequence past the imported
+    // IDs by drawing from it
+    let max_id: i64 =
+        conn.query_row("SELECT COALESCE(MAX(id), 0) FROM plays", [], |row| row.get(0))?;
+    if max_id > 0 {
+        conn.execute(
+            &format!("SELECT nextval('plays_id_seq') FROM generate_series(1, {max_id})"),
+            [],
+        )?;
+    }
+
+    migrate(conn, None)?;
+    Ok(conn.query_row("SELECT COUNT(*) FROM plays", [], |row| row.get(0))?)
+}
+
+fn apply<'a>(
+    conn: &Connection,
+    path: Option<&Path>,
+    migrations: &'a [Migration],
+) -> Result<&'a [Migration]> {
+    let pending = pending_in(conn, migrations)?;
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    if let Some(path) = path {
+        if pending.iter().any(|migration| migration.destructive) && has_tables(conn)? {
+            let backup = back_up(conn, path, current_version(conn)?)?;
+            info!("Backed up database to {}", backup.display());
+        }
+    }
 
-    // Create audio features table for future audio analysis
     conn.execute_batch(
         r"
-        CREATE TABLE IF NOT EXISTS audio_features (
-            file_path VARCHAR PRIMARY KEY,
-            tempo DOUBLE,
-            energy DOUBLE,
-            danceability DOUBLE,
-            valence DOUBLE,
-            acousticness DOUBLE,
-            instrumentalness DOUBLE,
-            speechiness DOUBLE,
-            loudness DOUBLE,
-            key INTEGER,
-            mode INTEGER,
-            time_signature INTEGER,
-            analyzed_at TIMESTAMP DEFAULT current_timestamp
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description VARCHAR NOT NULL,
+            applied_at TIMESTAMP DEFAULT current_timestamp
         );
         ",
     )?;
 
-    // Create sessions table for session tracking
-    conn.execute_batch(
-        r"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id VARCHAR PRIMARY KEY,
-            start_time TIMESTAMP NOT NULL,
-            end_time TIMESTAMP,
-            track_count INTEGER DEFAULT 0,
-            total_ms BIGINT DEFAULT 0,
-            player_name VARCHAR
-        );
+    for migration in pending {
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        if let Err(e) = run(conn, migration) {
+            conn.execute_batch("ROLLBACK")?;
+            return Err(Error::other(format!(
+                "Schema migration {} ({}) failed: {e}",
+                migration.version, migration.description
+            )));
+        }
+        conn.execute_batch("COMMIT")?;
+        info!("Applied schema migration {}: {}", migration.version, migration.description);
+    }
 
-        CREATE INDEX IF NOT EXISTS idx_sessions_start ON sessions(start_time);
-        ",
-    )?;
+    // Write the changes into the file, so read-only connections never have to
+    // replay schema changes from the write-ahead log
+    conn.execute_batch("CHECKPOINT")?;
 
+    Ok(pending)
+}
+
+/// Make a migration's changes and record it as applied
+fn run(conn: &Connection, migration: &Migration) -> Result<()> {
+    conn.execute_batch(migration.sql)?;
+    if let Some(backfill) = migration.backfill {
+        backfill(conn)?;
+    }
+    conn.execute(
+        "INSERT INTO schema_version (version, description) VALUES (?, ?)",
+        params![migration.version, migration.description],
+    )?;
     Ok(())
 }
+
+/// Whether the database holds anything worth backing up
+fn has_tables(conn: &Connection) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) > 0 FROM duckdb_tables() WHERE database_name = current_database()",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Copy the database file, tagged with the schema version it's at
+fn back_up(conn: &Connection, path: &Path, version: u32) -> Result<PathBuf> {
+    // Flush the write-ahead log so the file alone holds everything
+    conn.execute_batch("CHECKPOINT")?;
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{version}.bak"));
+    let backup = path.with_file_name(name);
+    std::fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db() -> PathBuf {
+        std::env::temp_dir().join(format!("schema-test-{}.duckdb", uuid::Uuid::new_v4()))
+    }
+
+    fn remove(path: &Path) {
+        for suffix in ["", ".wal", ".v1.bak"] {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(suffix);
+            let _ = std::fs::remove_file(name);
+        }
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT column_name FROM duckdb_columns() WHERE table_name = ?")
+            .unwrap();
+        stmt.query_map([table], |row| row.get(0))
+            .unwrap()
+            .collect::<duckdb::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_database_gets_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(pending(&conn).unwrap().len(), MIGRATIONS.len());
+
+        let applied = migrate(&conn, None).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        // Nothing left to do the second time
+        assert!(migrate(&conn, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unversioned_database_is_upgraded() {
+        // Plays table as written by a release before versioning
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r"
+            CREATE SEQUENCE plays_id_seq;
+            CREATE TABLE plays (
+                id INTEGER PRIMARY KEY DEFAULT nextval('plays_id_seq'),
+                timestamp TIMESTAMP DEFAULT current_timestamp,
+                title VARCHAR NOT NULL,
+                artist VARCHAR,
+                album VARCHAR,
+                genre VARCHAR
+            );
+            CREATE INDEX idx_plays_timestamp ON plays(timestamp);
+            INSERT INTO plays (title, artist) VALUES ('Song', 'Artist');
+            ",
+        )
+        .unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        migrate(&conn, None).unwrap();
+
+        let plays = columns(&conn, "plays");
+        assert!(plays.iter().any(|column| column == "tags"));
+        assert!(plays.iter().any(|column| column == "session_id"));
+        let title: String = conn
+            .query_row("SELECT title FROM plays", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(title, "Song");
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_offsets_are_backfilled() {
+        use chrono::{Local, NaiveDateTime, Offset, TimeZone};
+
+        let conn = Connection::open_in_memory().unwrap();
+        apply(&conn, None, &MIGRATIONS[..1]).unwrap();
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, timestamp) VALUES ('Winter', '2024-01-15 12:00:00');
+            INSERT INTO plays (title, timestamp) VALUES ('Summer', '2024-07-15 12:00:00');
+            ",
+        )
+        .unwrap();
+
+        migrate(&conn, None).unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT CAST(timestamp AS VARCHAR), utc_offset_minutes, timezone FROM plays")
+            .unwrap();
+        let rows: Vec<(String, i32, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<duckdb::Result<_>>()
+            .unwrap();
+        for (timestamp, offset, timezone) in rows {
+            let utc = NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%d %H:%M:%S").unwrap();
+            let expected = Local.offset_from_utc_datetime(&utc).fix().local_minus_utc() / 60;
+            assert_eq!(offset, expected, "offset at {timestamp}");
+            assert_eq!(timezone, crate::context::timezone());
+        }
+    }
+
+    #[test]
+    fn test_imported_plays_get_offsets() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        // A SQLite file when DuckDB's extension for it is installed, otherwise
+        // a database of the same shape
+        let sqlite = std::env::temp_dir().join(format!("schema-test-{}.db", uuid::Uuid::new_v4()));
+        if conn.execute_batch("LOAD sqlite;").is_ok() {
+            conn.execute(&format!("ATTACH '{}' AS legacy (TYPE sqlite)", sqlite.display()), [])
+                .unwrap();
+        } else {
+            eprintln!("DuckDB sqlite extension not installed, importing from DuckDB");
+            conn.execute_batch("ATTACH ':memory:' AS legacy").unwrap();
+        }
+
+        // Plays table as written by a release before DuckDB
+        conn.execute_batch(
+            r"
+            CREATE TABLE legacy.plays (
+                id INTEGER PRIMARY KEY,
+                timestamp TIMESTAMP,
+                title VARCHAR NOT NULL,
+                artist VARCHAR
+            );
+            INSERT INTO legacy.plays VALUES (1, '2024-01-15 12:00:00', 'Winter', 'Artist');
+            INSERT INTO legacy.plays VALUES (7, '2024-07-15 12:00:00', 'Summer', 'Artist');
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(import_plays(&conn, "legacy").unwrap(), 2);
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        let mut stmt = conn
+            .prepare("SELECT utc_offset_minutes, timezone FROM plays ORDER BY id")
+            .unwrap();
+        let rows: Vec<(Option<i32>, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<duckdb::Result<_>>()
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        for (offset, timezone) in rows {
+            assert!(offset.is_some());
+            assert_eq!(timezone, crate::context::timezone());
+        }
+
+        // New plays don't reuse imported IDs
+        conn.execute("INSERT INTO plays (title) VALUES ('New')", []).unwrap();
+        let id: i64 = conn
+            .query_row("SELECT id FROM plays WHERE title = 'New'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(id, 8);
+
+        conn.execute_batch("DETACH legacy").unwrap();
+        let _ = std::fs::remove_file(sqlite);
+    }
+
+    #[test]
+    fn test_destructive_migration_backs_up_and_rolls_back() {
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "Create table",
+                destructive: false,
+                sql: "CREATE TABLE t (a INTEGER, b INTEGER); INSERT INTO t VALUES (1, 2);",
+                backfill: None,
+            },
+            Migration {
+                version: 2,
+                description: "Drop column",
+                destructive: true,
+                sql: "ALTER TABLE t DROP COLUMN b; SELECT * FROM missing_table;",
+                backfill: None,
+            },
+        ];
+        let path = temp_db();
+        let conn = Connection::open(&path).unwrap();
+
+        apply(&conn, Some(&path), &migrations[..1]).unwrap();
+        assert!(apply(&conn, Some(&path), &migrations).is_err());
+
+        // The failed migration left no trace
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert_eq!(columns(&conn, "t"), ["a", "b"]);
+
+        // And the file was backed up before it ran
+        let mut backup = path.as_os_str().to_os_string();
+        backup.push(".v1.bak");
+        let backup = Connection::open(PathBuf::from(backup)).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 1);
+        drop(backup);
+
+        drop(conn);
+        remove(&path);
+    }
+
+    #[test]
+    fn test_newer_database_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn, None).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?, 'From the future')",
+            [latest_version() + 1],
+        )
+        .unwrap();
+        assert!(migrate(&conn, None).is_err());
+    }
+
+    #[test]
+    fn test_pending_at_leaves_file_alone() {
+        let path = temp_db();
+        assert_eq!(pending_at(&path).unwrap().len(), MIGRATIONS.len());
+        assert!(!path.exists());
+
+        let conn = Connection::open(&path).unwrap();
+        migrate(&conn, Some(&path)).unwrap();
+        drop(conn);
+        assert!(pending_at(&path).unwrap().is_empty());
+        remove(&path);
+    }
+}
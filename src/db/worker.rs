@@ -0,0 +1,335 @@
+//! Database threads
+//!
+//! Database calls block, so the connection is driven from threads of its own
+//! rather than the async runtime. Writes and stats queries each get their own
+//! connection and thread, and the database runs the two side by side, so a
+//! play is logged straight away even while a long query is running. A query whose
+//! caller has given up is skipped if it hasn't started yet, and interrupted
+//! if it has.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Local, NaiveTime};
+use duckdb::{Connection, InterruptHandle};
+use tokio::sync::oneshot;
+
+use crate::context::ListeningContext;
+use crate::error::{Error, Result};
+use crate::track::{SkipReason, TrackState};
+
+use super::queries;
+use super::reader::{self, Answer, Request};
+
+/// A change to the database
+pub(super) enum Write {
+    /// A play that ended at `ended_at`, as part of a listening session
+    Play {
+        state: Box<TrackState>,
+        context: Box<ListeningContext>,
+        session_id: String,
+        ended_at: DateTime<Local>,
+    },
+    /// A listen that fell short of the play thresholds
+    Skip {
+        state: Box<TrackState>,
+        reason: SkipReason,
+        ended_at: DateTime<Local>,
+    },
+    /// A play made in private mode, counted without details toward its day
+    PrivatePlay {
+        state: Box<TrackState>,
+        day_start: NaiveTime,
+    },
+    /// Anything else, for tests
+    #[cfg(test)]
+    Run(Box<dyn FnOnce(&Connection) + Send>),
+}
+
+struct WriteJob {
+    write: Write,
+    reply: oneshot::Sender<Result<()>>,
+}
+
+struct QueryJob {
+    id: u64,
+    request: Request,
+    reply: oneshot::Sender<Result<Answer>>,
+}
+
+/// The query thread's connection and what it's running
+struct Running {
+    interrupt: Arc<InterruptHandle>,
+    /// ID of the query in progress, 0 if none
+    query: Mutex<u64>,
+    /// Told the ID of each query as it starts, for tests
+    #[cfg(test)]
+    started: Mutex<Option<tokio::sync::mpsc::UnboundedSender<u64>>>,
+}
+
+/// Handle to the database threads; they stop once every handle is dropped
+pub(super) struct Worker {
+    writes: mpsc::Sender<WriteJob>,
+    queries: mpsc::Sender<QueryJob>,
+    running: Arc<Running>,
+    next_id: AtomicU64,
+}
+
+impl Worker {
+    /// Start the threads, writing through `conn` and querying through a
+    /// second connection to the same database
+    pub(super) fn start(conn: Connection) -> Result<Self> {
+        let query_conn = conn.try_clone()?;
+        let running = Arc::new(Running {
+            interrupt: query_conn.interrupt_handle(),
+            query: Mutex::new(0),
+            #[cfg(test)]
+            started: Mutex::new(None),
+        });
+
+        let (writes, write_jobs) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("db-writer".into())
+            .spawn(move || run_writes(&conn, &write_jobs))?;
+
+        let (queries, query_jobs) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("db-queries".into())
+            .spawn({
+                let running = Arc::clone(&running);
+                move || run_queries(&query_conn, &query_jobs, &running)
+            })?;
+
+        Ok(Self {
+            writes,
+            queries,
+            running,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Apply a change, waiting until it's written
+    pub(super) async fn write(&self, write: Write) -> Result<()> {
+        let (reply, response) = oneshot::channel();
+        self.writes
+            .send(WriteJob { write, reply })
+            .map_err(|_| stopped())?;
+        response.await.map_err(|_| stopped())?
+    }
+
+    /// Run a stats query.
+    ///
+    /// Dropping the future cancels the query: it's skipped if it hasn't
+    /// started, and interrupted if it has.
+    pub(super) async fn query(&self, request: Request) -> Result<Answer> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, response) = oneshot::channel();
+        self.queries
+            .send(QueryJob { id, request, reply })
+            .map_err(|_| stopped())?;
+
+        let mut cancel = CancelOnDrop {
+            running: &self.running,
+            id,
+            answered: false,
+        };
+        let answer = response.await.map_err(|_| stopped());
+        cancel.answered = true;
+        answer?
+    }
+}
+
+/// Interrupts a query if its caller stops waiting while it runs
+struct CancelOnDrop<'a> {
+    running: &'a Running,
+    id: u64,
+    answered: bool,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if self.answered {
+            return;
+        }
+        // Held while interrupting, so the thread can't move on to another query
+        let query = self
+            .running
+            .query
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *query == self.id {
+            self.running.interrupt.interrupt();
+        }
+        drop(query);
+    }
+}
+
+fn stopped() -> Error {
+    Error::other("Database thread stopped")
+}
+
+fn run_writes(conn: &Connection, jobs: &mpsc::Receiver<WriteJob>) {
+    for job in jobs {
+        let _ = job.reply.send(apply(conn, job.write));
+    }
+}
+
+fn apply(conn: &Connection, write: Write) -> Result<()> {
+    match write {
+        Write::Play {
+            state,
+            context,
+            session_id,
+            ended_at,
+        } => {
+            queries::insert_play(conn, &state, &context, &session_id, ended_at)?;
+            queries::record_session_play(conn, &session_id, &state, ended_at)
+        }
+        Write::Skip {
+            state,
+            reason,
+            ended_at,
+        } => queries::insert_skip(conn, &state, reason, ended_at),
+        Write::PrivatePlay { state, day_start } => {
+            queries::insert_private_play(conn, &state, day_start)
+        }
+        #[cfg(test)]
+        Write::Run(run) => {
+            run(conn);
+            Ok(())
+        }
+    }
+}
+
+fn run_queries(conn: &Connection, jobs: &mpsc::Receiver<QueryJob>, running: &Running) {
+    for job in jobs {
+        // Caller gave up while it was queued
+        if job.reply.is_closed() {
+            continue;
+        }
+
+        *running.query.lock().unwrap_or_else(PoisonError::into_inner) = job.id;
+        #[cfg(test)]
+        if let Some(started) = &*running
+            .started
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            let _ = started.send(job.id);
+        }
+        let answer = reader::answer(conn, &job.request);
+        *running.query.lock().unwrap_or_else(PoisonError::into_inner) = 0;
+        let _ = job.reply.send(answer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    use crate::db::reader::Query;
+    use crate::db::{schema, ContentFilter, LocalTime};
+
+    /// Worker on a database big enough for slow queries, with the IDs of
+    /// queries as they start
+    fn worker() -> (Worker, tokio::sync::mpsc::UnboundedReceiver<u64>) {
+        let conn = Connection::open_in_memory().unwrap();
+        schema::migrate(&conn, None).unwrap();
+        // Enough plays that ranking artists takes a while
+        conn.execute_batch(
+            r"
+            INSERT INTO plays (title, artist, played_ms)
+            SELECT 'Track ' || (i % 5000), 'Artist ' || (i % 2000) || ', Guest ' || (i % 700), 1000
+            FROM range(200000) t(i);
+            ",
+        )
+        .unwrap();
+        let worker = Worker::start(conn).unwrap();
+        let (started, started_queries) = tokio::sync::mpsc::unbounded_channel();
+        *worker.running.started.lock().unwrap() = Some(started);
+        (worker, started_queries)
+    }
+
+    fn top_artists() -> Request {
+        Request {
+            query: Query::TopArtists {
+                start: None,
+                end: None,
+                limit: 10,
+            },
+            content: ContentFilter::All,
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        }
+    }
+
+    fn play(title: &str) -> Write {
+        let mut state = TrackState::new();
+        state.track.title = Some(title.to_string());
+        Write::Play {
+            state: Box::new(state),
+            context: Box::default(),
+            session_id: "session".to_string(),
+            ended_at: Local::now(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_writes_dont_wait_for_queries() {
+        let (worker, mut started_queries) = worker();
+        let worker = Arc::new(worker);
+
+        let started = Instant::now();
+        let query = tokio::spawn({
+            let worker = Arc::clone(&worker);
+            async move { worker.query(top_artists()).await }
+        });
+        // Write once the query is running
+        started_queries.recv().await.unwrap();
+
+        let written = Instant::now();
+        worker.write(play("Logged")).await.unwrap();
+        let write_took = written.elapsed();
+
+        query.await.unwrap().unwrap();
+        let query_took = started.elapsed();
+        assert!(
+            write_took < query_took / 2,
+            "write took {write_took:?} during a {query_took:?} query"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dropped_query_is_interrupted() {
+        let (worker, mut started_queries) = worker();
+
+        let started = Instant::now();
+        worker.query(top_artists()).await.unwrap();
+        let full = started.elapsed();
+        started_queries.recv().await.unwrap();
+
+        // Give up on queries once the first is running, with the rest queued
+        let cancelled = Instant::now();
+        let queries = futures::future::join_all((0..4).map(|_| worker.query(top_artists())));
+        tokio::select! {
+            _ = queries => panic!("queries finished before they were dropped"),
+            id = started_queries.recv() => assert!(id.is_some()),
+        }
+
+        let count = Request {
+            query: Query::PlayCount,
+            content: ContentFilter::All,
+            local_time: LocalTime::default(),
+            day_start: NaiveTime::MIN,
+        };
+        worker.query(count).await.unwrap();
+        let took = cancelled.elapsed();
+        assert!(
+            took < full * 2,
+            "cancelled queries held up the next for {took:?} (a full query takes {full:?})"
+        );
+    }
+}
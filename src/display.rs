@@ -17,6 +17,8 @@
 
 use std::borrow::Cow;
 
+use chrono::NaiveTime;
+
 use crate::date_range;
 use crate::db::{AlbumStats, ArtistStats, OverviewStats, TrackStats};
 
@@ -76,8 +78,9 @@ pub fn build_date_range(
     week: bool,
     month: bool,
     year: Option<i32>,
+    day_start: NaiveTime,
 ) -> (Option<String>, Option<String>, String) {
-    let range = date_range::from_cli_flags(all_time, week, month, year);
+    let range = date_range::from_cli_flags(all_time, week, month, year, day_start);
     let (start, end) = range.to_sql_tuple();
     (start, end, range.display_name)
 }
@@ -158,6 +161,9 @@ pub fn display_overview(overview: &OverviewStats) {
     println!("  Unique artists:   {:>10}", overview.unique_artists);
     println!("  Unique albums:    {:>10}", overview.unique_albums);
     println!("  Unique tracks:    {:>10}", overview.unique_tracks);
+    if overview.private_plays > 0 {
+        println!("  Private plays:    {:>10}", overview.private_plays);
+    }
 }
 
 // ============================================================================
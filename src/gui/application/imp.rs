@@ -60,19 +60,25 @@ impl AdwApplicationImpl for MusicAnalyticsApplication {}
 impl MusicAnalyticsApplication {
     /// Ensure the music-tracker daemon is running.
     /// Spawns it as a background process if not already active.
+    ///
+    /// Runs on the shared tokio runtime so startup never waits on the bus.
     fn ensure_tracker_running() {
-        // Check if tracker is already running via pgrep
-        let is_running = Command::new("pgrep")
-            .args(["-x", "music-tracker"])
-            .output()
-            .map(|o| o.status.success())
-            .unwrap_or(false);
-
-        if is_running {
-            info!("music-tracker is already running");
-            return;
-        }
+        crate::gui::runtime().spawn(async {
+            // A running tracker owns its service name on the session bus
+            if crate::mpris::is_tracker_running().await {
+                info!("music-tracker is already running");
+                return;
+            }
+
+            // systemctl blocks until the unit has started
+            if let Err(e) = tokio::task::spawn_blocking(Self::start_tracker).await {
+                warn!("Failed to start music-tracker: {e}");
+            }
+        });
+    }
 
+    /// Start the music-tracker daemon
+    fn start_tracker() {
         info!("Starting music-tracker daemon...");
 
         // Try to start via systemd first (preferred for native installs)
@@ -1,4 +1,4 @@
-//! Insights view showing listening streaks, night owl score, skip rate
+//! Insights view showing listening streaks, night owl score, skip rate, sessions
 
 use gtk4::glib;
 use gtk4::prelude::*;
@@ -24,6 +24,10 @@ mod imp {
         // Skip rate rows
         pub skip_rate_row: adw::ActionRow,
         pub skip_rate_bar: gtk4::ProgressBar,
+        // Session rows
+        pub session_count_row: adw::ActionRow,
+        pub avg_session_row: adw::ActionRow,
+        pub longest_session_row: adw::ActionRow,
     }
 
     impl Default for InsightsView {
@@ -37,6 +41,9 @@ mod imp {
                 night_owl_bar: gtk4::ProgressBar::new(),
                 skip_rate_row: adw::ActionRow::new(),
                 skip_rate_bar: gtk4::ProgressBar::new(),
+                session_count_row: adw::ActionRow::new(),
+                avg_session_row: adw::ActionRow::new(),
+                longest_session_row: adw::ActionRow::new(),
             }
         }
     }
@@ -140,6 +147,28 @@ mod imp {
 
             content.append(&skip_group);
 
+            // Sessions section
+            let session_group = adw::PreferencesGroup::new();
+            session_group.set_title("Listening Sessions");
+            session_group.set_description(Some("Stretches of listening without a long break"));
+
+            self.session_count_row.set_title("Sessions");
+            self.session_count_row.set_subtitle("—");
+            self.session_count_row.add_prefix(&gtk4::Image::from_icon_name("media-playlist-consecutive-symbolic"));
+            session_group.add(&self.session_count_row);
+
+            self.avg_session_row.set_title("Average Length");
+            self.avg_session_row.set_subtitle("—");
+            self.avg_session_row.add_prefix(&gtk4::Image::from_icon_name("preferences-system-time-symbolic"));
+            session_group.add(&self.avg_session_row);
+
+            self.longest_session_row.set_title("Longest Session");
+            self.longest_session_row.set_subtitle("—");
+            self.longest_session_row.add_prefix(&gtk4::Image::from_icon_name("starred-symbolic"));
+            session_group.add(&self.longest_session_row);
+
+            content.append(&session_group);
+
             clamp.set_child(Some(&content));
             scrolled.set_child(Some(&clamp));
             self.stack.add_named(&scrolled, Some("content"));
@@ -199,6 +228,13 @@ impl InsightsView {
         imp.skip_rate_row.set_subtitle(&skip_text);
         imp.skip_rate_bar.set_fraction(data.skip_rate);
 
+        // Sessions
+        imp.session_count_row.set_subtitle(&data.session_count.to_string());
+        imp.avg_session_row
+            .set_subtitle(&format!("{:.0} min", data.avg_session_minutes));
+        imp.longest_session_row
+            .set_subtitle(&format!("{:.0} min", data.longest_session_minutes));
+
         self.set_loading(false);
     }
 
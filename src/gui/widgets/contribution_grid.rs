@@ -1,6 +1,6 @@
 //! ContributionGrid widget for visualizing daily listening patterns (GitHub-style)
 
-use chrono::Datelike;
+use chrono::{Datelike, NaiveTime};
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
@@ -16,6 +16,8 @@ pub struct ContributionData {
     pub max_plays: i64,
     /// Total plays in the period
     pub total_plays: i64,
+    /// Time of day that days start
+    pub day_start: NaiveTime,
 }
 
 mod imp {
@@ -80,7 +82,7 @@ mod imp {
             let num_weeks = ((available_width / cell_total) as i32).min(53);
 
             // Get today's date and calculate the grid
-            let today = chrono::Local::now().date_naive();
+            let today = crate::date_range::current_day(data.day_start);
             let today_weekday = today.weekday().num_days_from_sunday() as i32;
 
             // Draw month labels at the top
@@ -5,9 +5,11 @@ mod stat_card;
 mod ranked_row;
 mod heatmap_grid;
 mod contribution_grid;
+mod tracker_status;
 
 pub use art_loader::{load_art_texture, placeholder_paintable, ArtLoadError};
 pub use stat_card::StatCard;
 pub use ranked_row::RankedRow;
 pub use heatmap_grid::HeatmapGrid;
 pub use contribution_grid::{ContributionGrid, ContributionData};
+pub use tracker_status::TrackerStatus;
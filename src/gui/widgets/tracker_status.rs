@@ -0,0 +1,186 @@
+//! TrackerStatus widget showing what the tracker is doing, with its controls
+
+use gtk4::glib;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use std::cell::RefCell;
+
+use crate::gui::window::TrackerData;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct TrackerStatus {
+        pub state_label: RefCell<Option<gtk4::Label>>,
+        pub plays_label: RefCell<Option<gtk4::Label>>,
+        pub players_box: RefCell<Option<gtk4::Box>>,
+        pub pause_button: RefCell<Option<gtk4::Button>>,
+        pub discard_button: RefCell<Option<gtk4::Button>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TrackerStatus {
+        const NAME: &'static str = "TrackerStatus";
+        type Type = super::TrackerStatus;
+        type ParentType = gtk4::Box;
+    }
+
+    impl ObjectImpl for TrackerStatus {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.setup_ui();
+        }
+    }
+
+    impl WidgetImpl for TrackerStatus {}
+
+    impl BoxImpl for TrackerStatus {}
+
+    impl TrackerStatus {
+        fn setup_ui(&self) {
+            let obj = self.obj();
+
+            obj.set_orientation(gtk4::Orientation::Vertical);
+            obj.set_spacing(6);
+            obj.set_margin_top(6);
+            obj.set_margin_bottom(6);
+            obj.set_margin_start(6);
+            obj.set_margin_end(6);
+
+            // Running / paused state
+            let state = gtk4::Label::new(None);
+            state.add_css_class("heading");
+            state.set_halign(gtk4::Align::Start);
+            obj.append(&state);
+            *self.state_label.borrow_mut() = Some(state);
+
+            // Plays logged this session
+            let plays = gtk4::Label::new(None);
+            plays.add_css_class("caption");
+            plays.add_css_class("dim-label");
+            plays.set_halign(gtk4::Align::Start);
+            obj.append(&plays);
+            *self.plays_label.borrow_mut() = Some(plays);
+
+            // One row per playing player
+            let players = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+            players.set_margin_top(6);
+            players.set_margin_bottom(6);
+            obj.append(&players);
+            *self.players_box.borrow_mut() = Some(players);
+
+            // Controls, handled by the window's actions
+            let pause = gtk4::Button::with_label("Pause Tracking");
+            pause.set_action_name(Some("win.toggle-tracking"));
+            obj.append(&pause);
+            *self.pause_button.borrow_mut() = Some(pause);
+
+            let discard = gtk4::Button::with_label("Discard Current Listen");
+            discard.set_action_name(Some("win.discard-listen"));
+            discard.add_css_class("destructive-action");
+            obj.append(&discard);
+            *self.discard_button.borrow_mut() = Some(discard);
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Tracker state, the players it is following, and pause/discard buttons
+    pub struct TrackerStatus(ObjectSubclass<imp::TrackerStatus>)
+        @extends gtk4::Box, gtk4::Widget,
+        @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget, gtk4::Orientable;
+}
+
+impl TrackerStatus {
+    /// Create an empty status panel
+    #[must_use]
+    pub fn new() -> Self {
+        let obj: Self = glib::Object::new();
+        obj.set_data(None);
+        obj
+    }
+
+    /// Show what the tracker reports, or that it isn't running
+    pub fn set_data(&self, data: Option<&TrackerData>) {
+        let imp = self.imp();
+
+        if let Some(label) = imp.state_label.borrow().as_ref() {
+            label.set_text(match data {
+                None => "Tracker not running",
+                Some(data) if data.paused => "Tracking paused",
+                Some(_) => "Tracking",
+            });
+        }
+
+        if let Some(label) = imp.plays_label.borrow().as_ref() {
+            match data {
+                Some(data) => {
+                    label.set_text(&format!("{} plays logged this session", data.plays_logged));
+                    label.set_visible(true);
+                }
+                None => label.set_visible(false),
+            }
+        }
+
+        if let Some(players) = imp.players_box.borrow().as_ref() {
+            while let Some(child) = players.first_child() {
+                players.remove(&child);
+            }
+
+            let playing: Vec<_> = data
+                .map(|data| data.players.iter().filter(|p| p.playing).collect())
+                .unwrap_or_default();
+            if playing.is_empty() {
+                let empty = gtk4::Label::new(Some("Nothing playing"));
+                empty.add_css_class("dim-label");
+                empty.set_halign(gtk4::Align::Start);
+                players.append(&empty);
+            }
+            for player in playing {
+                players.append(&player_row(player));
+            }
+            players.set_visible(data.is_some());
+        }
+
+        if let Some(button) = imp.pause_button.borrow().as_ref() {
+            let paused = data.is_some_and(|data| data.paused);
+            button.set_label(if paused {
+                "Resume Tracking"
+            } else {
+                "Pause Tracking"
+            });
+        }
+    }
+}
+
+impl Default for TrackerStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Two-line row with the track and the player playing it
+fn player_row(player: &crate::mpris::PlayerStatus) -> gtk4::Box {
+    let row = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+
+    // Private listens are reported without their track
+    let track = match (player.artist.as_str(), player.title.as_str()) {
+        (_, "") => "Private listen".to_string(),
+        ("", title) => title.to_string(),
+        (artist, title) => format!("{artist} – {title}"),
+    };
+    let track_label = gtk4::Label::new(Some(&track));
+    track_label.set_halign(gtk4::Align::Start);
+    track_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    track_label.set_max_width_chars(40);
+    row.append(&track_label);
+
+    let player_label = gtk4::Label::new(Some(&player.player));
+    player_label.add_css_class("caption");
+    player_label.add_css_class("dim-label");
+    player_label.set_halign(gtk4::Align::Start);
+    row.append(&player_label);
+
+    row
+}
@@ -1,8 +1,11 @@
 //! GObject implementation for MusicAnalyticsWindow
 
 use std::cell::{Cell, RefCell};
+use std::time::Duration;
 
 use async_channel::{Receiver, Sender};
+use chrono::NaiveTime;
+use futures::StreamExt;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
@@ -12,10 +15,14 @@ use libadwaita::subclass::prelude::*;
 
 use crate::db::{AlbumStats, ArtistStats, OverviewStats, TrackStats};
 use crate::gui::views::{HeatmapView, InsightsView, OverviewView, TopListsView};
-use crate::gui::widgets::ContributionData;
-use crate::gui::window::{DateFilter, HeatmapData, InsightsData};
+use crate::gui::widgets::{ContributionData, TrackerStatus};
+use crate::gui::window::{DateFilter, HeatmapData, InsightsData, TrackerData};
+use crate::mpris::TrackerProxy;
 use crate::{Config, Database};
 
+/// How often the tracker's status is refreshed
+const TRACKER_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Messages sent from the async data loading thread
 #[derive(Debug)]
 pub enum DataMessage {
@@ -26,14 +33,28 @@ pub enum DataMessage {
     Insights(InsightsData),
     Heatmap(HeatmapData),
     Contribution(ContributionData),
+    /// Tracker status, None when it isn't running
+    Tracker(Option<TrackerData>),
+    /// The tracker wrote a play, so the stats are out of date
+    PlayLogged,
     Error(String),
 }
 
+/// Request sent to the running tracker from the window's actions
+#[derive(Debug, Clone, Copy)]
+enum TrackerControl {
+    Pause,
+    Resume,
+    Discard,
+}
+
 pub struct MusicAnalyticsWindow {
     // UI components
     pub(super) view_stack: adw::ViewStack,
     pub(super) date_dropdown: gtk4::DropDown,
     pub(super) toast_overlay: adw::ToastOverlay,
+    pub(super) tracker_button: gtk4::MenuButton,
+    pub(super) tracker_status: TrackerStatus,
 
     // Views
     pub(super) overview_view: RefCell<Option<OverviewView>>,
@@ -49,6 +70,9 @@ pub struct MusicAnalyticsWindow {
 
     // State
     pub(super) date_filter: Cell<DateFilter>,
+    pub(super) tracker_data: RefCell<Option<TrackerData>>,
+    /// Stats load in progress, aborted when a new one starts
+    pub(super) loading: RefCell<Option<tokio::task::JoinHandle<()>>>,
 }
 
 impl Default for MusicAnalyticsWindow {
@@ -65,6 +89,8 @@ impl Default for MusicAnalyticsWindow {
                 "All Time",
             ]),
             toast_overlay: adw::ToastOverlay::new(),
+            tracker_button: gtk4::MenuButton::new(),
+            tracker_status: TrackerStatus::new(),
 
             overview_view: RefCell::new(None),
             artists_view: RefCell::new(None),
@@ -77,6 +103,8 @@ impl Default for MusicAnalyticsWindow {
             receiver,
 
             date_filter: Cell::new(DateFilter::AllTime),
+            tracker_data: RefCell::new(None),
+            loading: RefCell::new(None),
         }
     }
 }
@@ -92,8 +120,11 @@ impl ObjectImpl for MusicAnalyticsWindow {
     fn constructed(&self) {
         self.parent_constructed();
         self.setup_ui();
+        self.setup_actions();
+        self.set_tracker_data(None);
         self.setup_data_channel();
         self.init_database();
+        self.watch_tracker();
     }
 }
 
@@ -139,6 +170,12 @@ impl MusicAnalyticsWindow {
         menu_button.set_menu_model(Some(&self.create_app_menu()));
         header_bar.pack_end(&menu_button);
 
+        // Tracker status and controls
+        let tracker_popover = gtk4::Popover::new();
+        tracker_popover.set_child(Some(&self.tracker_status));
+        self.tracker_button.set_popover(Some(&tracker_popover));
+        header_bar.pack_start(&self.tracker_button);
+
         toolbar_view.add_top_bar(&header_bar);
 
         // Create views and add to stack
@@ -240,6 +277,31 @@ impl MusicAnalyticsWindow {
         menu
     }
 
+    fn setup_actions(&self) {
+        let window = self.obj();
+
+        // Pause or resume, depending on what the tracker last reported
+        let toggle_action = gtk4::gio::ActionEntry::builder("toggle-tracking")
+            .activate(|window: &super::MusicAnalyticsWindow, _, _| {
+                let imp = window.imp();
+                let paused = imp.tracker_data.borrow().as_ref().is_some_and(|data| data.paused);
+                imp.control_tracker(if paused {
+                    TrackerControl::Resume
+                } else {
+                    TrackerControl::Pause
+                });
+            })
+            .build();
+
+        let discard_action = gtk4::gio::ActionEntry::builder("discard-listen")
+            .activate(|window: &super::MusicAnalyticsWindow, _, _| {
+                window.imp().control_tracker(TrackerControl::Discard);
+            })
+            .build();
+
+        window.add_action_entries([toggle_action, discard_action]);
+    }
+
     fn setup_data_channel(&self) {
         let receiver = self.receiver.clone();
 
@@ -292,12 +354,112 @@ impl MusicAnalyticsWindow {
                     view.set_contribution_data(data);
                 }
             }
+            DataMessage::Tracker(data) => {
+                self.set_tracker_data(data);
+            }
+            DataMessage::PlayLogged => {
+                self.reload_data();
+            }
             DataMessage::Error(err) => {
                 self.show_error(&err);
             }
         }
     }
 
+    fn set_tracker_data(&self, data: Option<TrackerData>) {
+        let window = self.obj();
+        let running = data.is_some();
+        let playing = data
+            .as_ref()
+            .is_some_and(|data| data.players.iter().any(|player| player.playing));
+
+        let (icon, tooltip) = match &data {
+            None => ("media-playback-stop-symbolic", "Tracker not running"),
+            Some(data) if data.paused => ("media-playback-pause-symbolic", "Tracking paused"),
+            Some(_) => ("media-playback-start-symbolic", "Tracking"),
+        };
+        self.tracker_status.set_data(data.as_ref());
+        self.tracker_button.set_icon_name(icon);
+        self.tracker_button.set_tooltip_text(Some(tooltip));
+
+        // Controls only work while the tracker is running
+        for (name, enabled) in [("toggle-tracking", running), ("discard-listen", playing)] {
+            if let Some(action) = window
+                .lookup_action(name)
+                .and_downcast::<gtk4::gio::SimpleAction>()
+            {
+                action.set_enabled(enabled);
+            }
+        }
+
+        *self.tracker_data.borrow_mut() = data;
+    }
+
+    /// Follow the tracker: poll its status, and reload the stats whenever
+    /// it logs a play. Reconnects if the tracker restarts.
+    fn watch_tracker(&self) {
+        let sender = self.sender.clone();
+
+        crate::gui::runtime().spawn(async move {
+            loop {
+                if let Some(tracker) = connect_tracker().await {
+                    if let Ok(mut play_logged) = tracker.receive_play_logged().await {
+                        let mut poll = tokio::time::interval(TRACKER_POLL_INTERVAL);
+                        loop {
+                            tokio::select! {
+                                _ = poll.tick() => {}
+                                Some(_) = play_logged.next() => {
+                                    if sender.send(DataMessage::PlayLogged).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+
+                            // The tracker went away; start over
+                            let data = load_tracker_data(&tracker).await;
+                            let lost = data.is_none();
+                            if sender.send(DataMessage::Tracker(data)).await.is_err() {
+                                return;
+                            }
+                            if lost {
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                if sender.send(DataMessage::Tracker(None)).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(TRACKER_POLL_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Send a request to the tracker, then refresh its status
+    fn control_tracker(&self, control: TrackerControl) {
+        let sender = self.sender.clone();
+
+        crate::gui::runtime().spawn(async move {
+            let Some(tracker) = connect_tracker().await else {
+                let _ = sender.send(DataMessage::Tracker(None)).await;
+                return;
+            };
+
+            let result = match control {
+                TrackerControl::Pause => tracker.pause_tracking(0).await,
+                TrackerControl::Resume => tracker.resume_tracking().await,
+                TrackerControl::Discard => tracker.discard_current_play("").await.map(|_| ()),
+            };
+            if let Err(e) = result {
+                let _ = sender.send(DataMessage::Error(format!("Tracker error: {e}"))).await;
+            }
+
+            let data = load_tracker_data(&tracker).await;
+            let _ = sender.send(DataMessage::Tracker(data)).await;
+        });
+    }
+
     fn init_database(&self) {
         // Load data immediately - reload_data handles database connection and errors
         self.reload_data();
@@ -306,8 +468,6 @@ impl MusicAnalyticsWindow {
     pub fn reload_data(&self) {
         let sender = self.sender.clone();
         let filter = self.date_filter.get();
-        let range = filter.to_date_range();
-        let (start_date, end_date) = range.to_sql_tuple_with_end_time();
 
         // Show loading state on views
         if let Some(view) = self.overview_view.borrow().as_ref() {
@@ -329,8 +489,12 @@ impl MusicAnalyticsWindow {
             view.set_loading(true);
         }
 
-        // Load data in tokio runtime
-        crate::gui::runtime().spawn(async move {
+        // Load data in tokio runtime. A load for the previous date filter is
+        // aborted, which cancels the query it's waiting on.
+        if let Some(previous) = self.loading.take() {
+            previous.abort();
+        }
+        let loading = crate::gui::runtime().spawn(async move {
             let config = match Config::load() {
                 Ok(c) => c,
                 Err(e) => {
@@ -339,16 +503,26 @@ impl MusicAnalyticsWindow {
                 }
             };
 
-            let data_dir = match config.data_dir() {
-                Ok(d) => d,
+            let db_path = match config.database_path() {
+                Ok(path) => path,
                 Err(e) => {
-                    let _ = sender.send(DataMessage::Error(format!("Data dir error: {e}"))).await;
+                    let _ = sender.send(DataMessage::Error(format!("Database path error: {e}"))).await;
+                    return;
+                }
+            };
+
+            let day_start = match config.general.day_start() {
+                Ok(t) => t,
+                Err(e) => {
+                    let _ = sender.send(DataMessage::Error(format!("Config error: {e}"))).await;
                     return;
                 }
             };
+            let range = filter.to_date_range(day_start);
+            let (start_date, end_date) = range.to_sql_tuple_with_end_time();
 
-            let db = match Database::new(&config.database, &data_dir).await {
-                Ok(db) => db,
+            let db = match Database::open_read_only(&db_path).await {
+                Ok(db) => db.with_day_start(day_start),
                 Err(e) => {
                     let _ = sender.send(DataMessage::Error(format!("Database error: {e}"))).await;
                     return;
@@ -400,11 +574,13 @@ impl MusicAnalyticsWindow {
             }
 
             // Load contribution data
-            let contribution_data = load_contribution_data(&db, start_date.as_deref(), end_date.as_deref()).await;
+            let contribution_data =
+                load_contribution_data(&db, start_date.as_deref(), end_date.as_deref(), day_start).await;
             if let Some(data) = contribution_data {
                 let _ = sender.send(DataMessage::Contribution(data)).await;
             }
         });
+        self.loading.replace(Some(loading));
     }
 
     pub fn date_filter(&self) -> DateFilter {
@@ -426,6 +602,7 @@ async fn load_insights_data(
     let streaks = db.get_listening_streaks(start_date, end_date).await.ok()?;
     let night_owl = db.get_night_owl_score(start_date, end_date).await.ok()?;
     let (skipped, total, skip_rate) = db.get_skip_rate(start_date, end_date).await.ok()?;
+    let sessions = db.get_session_stats(start_date, end_date).await.ok()?;
 
     Some(InsightsData {
         current_streak: streaks.current_streak,
@@ -434,6 +611,9 @@ async fn load_insights_data(
         skip_rate,
         skipped_count: skipped,
         total_count: total,
+        session_count: sessions.total_sessions,
+        avg_session_minutes: sessions.avg_session_minutes,
+        longest_session_minutes: sessions.longest_session_minutes,
     })
 }
 
@@ -462,6 +642,7 @@ async fn load_contribution_data(
     db: &Database,
     start_date: Option<&str>,
     end_date: Option<&str>,
+    day_start: NaiveTime,
 ) -> Option<ContributionData> {
     let contrib = db.get_daily_contributions(start_date, end_date).await.ok()?;
 
@@ -469,5 +650,30 @@ async fn load_contribution_data(
         days: contrib.days,
         max_plays: contrib.max_plays,
         total_plays: contrib.total_plays,
+        day_start,
+    })
+}
+
+/// Connect to the running tracker, if there is one.
+///
+/// Its properties don't announce changes, so they are read fresh every time.
+async fn connect_tracker() -> Option<TrackerProxy<'static>> {
+    if !crate::mpris::is_tracker_running().await {
+        return None;
+    }
+    let connection = zbus::Connection::session().await.ok()?;
+    TrackerProxy::builder(&connection)
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await
+        .ok()
+}
+
+/// Read the tracker's status, or None if it stopped answering
+async fn load_tracker_data(tracker: &TrackerProxy<'_>) -> Option<TrackerData> {
+    Some(TrackerData {
+        paused: tracker.paused().await.ok()?,
+        plays_logged: tracker.plays_logged().await.ok()?,
+        players: tracker.players().await.ok()?,
     })
 }
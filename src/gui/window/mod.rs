@@ -7,6 +7,7 @@ use gtk4::subclass::prelude::ObjectSubclassIsExt;
 use libadwaita as adw;
 
 use crate::gui::MusicAnalyticsApplication;
+use crate::mpris::PlayerStatus;
 
 // Re-export DateFilter from the shared date_range module
 pub use crate::date_range::DateFilter;
@@ -20,6 +21,9 @@ pub struct InsightsData {
     pub skip_rate: f64,
     pub skipped_count: i64,
     pub total_count: i64,
+    pub session_count: i32,
+    pub avg_session_minutes: f64,
+    pub longest_session_minutes: f64,
 }
 
 /// Data for the heatmap view
@@ -30,6 +34,14 @@ pub struct HeatmapData {
     pub peak_count: i64,
 }
 
+/// What the running tracker reports
+#[derive(Debug, Clone)]
+pub struct TrackerData {
+    pub paused: bool,
+    pub plays_logged: u32,
+    pub players: Vec<PlayerStatus>,
+}
+
 glib::wrapper! {
     /// The main application window
     pub struct MusicAnalyticsWindow(ObjectSubclass<imp::MusicAnalyticsWindow>)
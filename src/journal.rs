@@ -0,0 +1,242 @@
+//! Crash-safe journal of in-progress listens
+//!
+//! The monitor checkpoints every player's current listen to a small JSON file
+//! on each state transition. If the tracker dies before it can log them (e.g.
+//! SIGKILL or the session being torn down), the next start recovers the
+//! listens from the last checkpoint.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::coverage::PositionCoverage;
+use crate::error::Result;
+use crate::track::{PlayInterval, Track, TrackState};
+
+/// Journal file name inside the data directory
+const JOURNAL_FILE: &str = "journal.json";
+
+/// Snapshot of an unfinished listen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenCheckpoint {
+    /// Listening session the listen belongs to
+    pub session_id: String,
+    /// When the checkpoint was taken
+    pub saved_at: DateTime<Local>,
+    /// IANA time zone the checkpoint was taken in; journals written before
+    /// it was kept get the current zone
+    #[serde(default = "crate::context::timezone")]
+    pub timezone: Option<String>,
+    pub track: Track,
+    pub start_timestamp: DateTime<Local>,
+    pub played_ms: u64,
+    pub paused_ms: u64,
+    pub pause_count: u32,
+    pub is_local: bool,
+    pub is_repeat: bool,
+    pub loop_status: Option<String>,
+    pub shuffle: Option<bool>,
+    pub rate: Option<f64>,
+    pub player_name: Option<String>,
+    pub seek_count: u32,
+    pub intro_skipped: bool,
+    pub seek_forward_ms: i64,
+    pub seek_backward_ms: i64,
+    /// Estimated playback position when the checkpoint was taken
+    pub position_us: i64,
+    pub coverage: PositionCoverage,
+    pub app_volume: Option<f64>,
+    pub system_volume: Option<f64>,
+}
+
+impl ListenCheckpoint {
+    /// Snapshot a listen, or None if it hasn't started
+    #[must_use]
+    pub fn capture(state: &TrackState, session_id: &str) -> Option<Self> {
+        if !state.has_started() {
+            return None;
+        }
+
+        Some(Self {
+            session_id: session_id.to_string(),
+            saved_at: Local::now(),
+            timezone: crate::context::timezone(),
+            track: state.track.clone(),
+            start_timestamp: state.start_timestamp?,
+            played_ms: u64::try_from(state.played_duration().as_millis()).unwrap_or(u64::MAX),
+            paused_ms: u64::try_from(state.paused_duration.as_millis()).unwrap_or(u64::MAX),
+            pause_count: state.pause_count,
+            is_local: state.is_local,
+            is_repeat: state.is_repeat,
+            loop_status: state.loop_status.clone(),
+            shuffle: state.shuffle,
+            rate: state.rate,
+            player_name: state.player_name.clone(),
+            seek_count: state.seek_count,
+            intro_skipped: state.intro_skipped,
+            seek_forward_ms: state.seek_forward_ms,
+            seek_backward_ms: state.seek_backward_ms,
+            position_us: state.capped_estimated_position_us(),
+            coverage: state.heard_coverage(),
+            app_volume: state.app_volume,
+            system_volume: state.system_volume,
+        })
+    }
+
+    /// Rebuild a finished (not playing) listen from the checkpoint
+    #[must_use]
+    pub fn into_state(self) -> TrackState {
+        let mut state = TrackState::new();
+
+        // Represent the played time as a single closed segment ending now
+        let end = Instant::now();
+        let played = Duration::from_millis(self.played_ms);
+        state.intervals.push(PlayInterval {
+            start: end.checked_sub(played).unwrap_or(end),
+            end,
+        });
+
+        state.track = self.track;
+        state.start_timestamp = Some(self.start_timestamp);
+        state.paused_duration = Duration::from_millis(self.paused_ms);
+        state.pause_count = self.pause_count;
+        state.is_local = self.is_local;
+        state.is_repeat = self.is_repeat;
+        state.loop_status = self.loop_status;
+        state.shuffle = self.shuffle;
+        state.rate = self.rate;
+        state.player_name = self.player_name;
+        state.seek_count = self.seek_count;
+        state.intro_skipped = self.intro_skipped;
+        state.seek_forward_ms = self.seek_forward_ms;
+        state.seek_backward_ms = self.seek_backward_ms;
+        state.last_position_us = self.position_us;
+        state.position_anchor = state.played_duration();
+        state.coverage = self.coverage;
+        state.app_volume = self.app_volume;
+        state.system_volume = self.system_volume;
+        // The listen ended at the checkpoint, in the zone it was taken in
+        state.timezone = self.timezone;
+
+        state
+    }
+}
+
+/// Journal file holding the checkpoints of all in-progress listens
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Create a journal stored in `data_dir`
+    #[must_use]
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(JOURNAL_FILE),
+        }
+    }
+
+    /// Replace the journal contents with the given checkpoints.
+    ///
+    /// Written to a temporary file and renamed over the journal, so a crash
+    /// mid-write leaves the previous checkpoint intact.
+    pub fn save(&self, checkpoints: &[ListenCheckpoint]) -> Result<()> {
+        if checkpoints.is_empty() {
+            return self.clear();
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(checkpoints)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Read the checkpoints left by a previous run
+    pub fn load(&self) -> Result<Vec<ListenCheckpoint>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove the journal
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started_state() -> TrackState {
+        let mut state = TrackState::new();
+        state.track.title = Some("Song".into());
+        state.track.duration_us = Some(200_000_000);
+        state.player_name = Some("spotify".into());
+        state.start_playing();
+        state
+    }
+
+    #[test]
+    fn test_capture_requires_started_listen() {
+        let state = TrackState::new();
+        assert!(ListenCheckpoint::capture(&state, "session").is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let mut state = started_state();
+        state.start_time = Some(Instant::now() - Duration::from_secs(90));
+        state.close_segment();
+        state.pause_count = 2;
+        state.coverage.add_range(0, 90_000_000);
+        state.last_position_us = 90_000_000;
+        state.position_anchor = state.played_duration();
+
+        let checkpoint = ListenCheckpoint::capture(&state, "session").unwrap();
+        let recovered = checkpoint.into_state();
+
+        assert!(!recovered.is_playing);
+        assert_eq!(recovered.track.title.as_deref(), Some("Song"));
+        assert_eq!(recovered.start_timestamp, state.start_timestamp);
+        assert_eq!(recovered.played_ms() / 1000, 90);
+        assert_eq!(recovered.pause_count, 2);
+        assert_eq!(recovered.heard_ms(), 90_000);
+        assert_eq!(recovered.should_log(30, 0.5), state.should_log(30, 0.5));
+    }
+
+    #[test]
+    fn test_journal_save_load_clear() {
+        let dir = std::env::temp_dir().join(format!("journal-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let journal = Journal::new(&dir);
+
+        assert!(journal.load().unwrap().is_empty());
+
+        let checkpoint = ListenCheckpoint::capture(&started_state(), "session").unwrap();
+        journal.save(&[checkpoint]).unwrap();
+        let loaded = journal.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].session_id, "session");
+
+        // Saving nothing removes the journal
+        journal.save(&[]).unwrap();
+        assert!(!dir.join(JOURNAL_FILE).exists());
+        journal.clear().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
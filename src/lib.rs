@@ -20,17 +20,30 @@
 #![allow(clippy::module_name_repetitions)]
 
 pub(crate) mod analytics;
+pub(crate) mod clock;
+pub(crate) mod content;
 pub mod config;
 pub(crate) mod context;
+pub(crate) mod coverage;
 pub mod date_range;
 pub mod db;
 pub mod display;
 pub mod error;
 #[cfg(feature = "gui")]
 pub mod gui;
+pub(crate) mod journal;
+pub(crate) mod logind;
 pub mod mpris;
+pub(crate) mod private;
+pub(crate) mod radio;
+pub(crate) mod reload;
+pub(crate) mod rules;
+pub(crate) mod session;
+#[cfg(test)]
+mod test_bus;
 pub(crate) mod track;
 pub mod types;
+pub(crate) mod volume;
 
 pub use config::Config;
 pub use db::Database;
@@ -0,0 +1,141 @@
+//! systemd-logind integration
+//!
+//! Watches `PrepareForSleep`/`PrepareForShutdown` so in-progress plays can be
+//! logged before the system goes down, and holds a delay inhibitor lock so
+//! there is time to do so.
+
+use futures::{Stream, StreamExt};
+use tracing::debug;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::OwnedFd;
+use zbus::{Connection, MatchRule, MessageStream};
+
+use crate::error::Result;
+
+/// logind bus name
+pub const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+
+/// logind manager object path
+pub const LOGIND_PATH: &str = "/org/freedesktop/login1";
+
+/// logind manager interface
+const LOGIND_MANAGER_IFACE: &str = "org.freedesktop.login1.Manager";
+
+/// Who and why reported for the inhibitor lock
+pub const INHIBIT_WHO: &str = "music-analytics";
+pub const INHIBIT_WHY: &str = "Log in-progress plays";
+
+/// Power transition announced by logind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSignal {
+    /// `PrepareForSleep`: true before suspending, false after resuming
+    PrepareForSleep(bool),
+    /// `PrepareForShutdown`: true before shutting down, false if it was cancelled
+    PrepareForShutdown(bool),
+}
+
+/// Client for the logind manager on the system bus
+#[derive(Debug, Clone)]
+pub struct Logind {
+    connection: Connection,
+}
+
+impl Logind {
+    /// Connect to logind on the system bus
+    pub async fn connect() -> Result<Self> {
+        Ok(Self::with_connection(Connection::system().await?))
+    }
+
+    /// Use an existing bus connection
+    #[must_use]
+    pub const fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    /// Take a delay inhibitor lock on sleep and shutdown.
+    ///
+    /// The lock is held until the returned file descriptor is dropped.
+    pub async fn inhibit(&self) -> Result<OwnedFd> {
+        let reply = self
+            .connection
+            .call_method(
+                Some(LOGIND_SERVICE),
+                LOGIND_PATH,
+                Some(LOGIND_MANAGER_IFACE),
+                "Inhibit",
+                &("sleep:shutdown", INHIBIT_WHO, INHIBIT_WHY, "delay"),
+            )
+            .await?;
+
+        Ok(reply.body().deserialize::<OwnedFd>()?)
+    }
+
+    /// Stream of sleep and shutdown announcements
+    pub async fn power_signals(&self) -> Result<impl Stream<Item = PowerSignal>> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(LOGIND_MANAGER_IFACE)?
+            .path(LOGIND_PATH)?
+            .build();
+
+        let stream = MessageStream::for_match_rule(rule, &self.connection, Some(16)).await?;
+
+        Ok(stream.filter_map(|msg| async move {
+            let msg = msg.ok()?;
+            let header = msg.header();
+            let start = msg.body().deserialize::<bool>().ok()?;
+
+            match header.member().map(zbus::names::MemberName::as_str) {
+                Some("PrepareForSleep") => Some(PowerSignal::PrepareForSleep(start)),
+                Some("PrepareForShutdown") => Some(PowerSignal::PrepareForShutdown(start)),
+                other => {
+                    debug!("Ignoring logind signal {:?}", other);
+                    None
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use zbus::object_server::SignalEmitter;
+
+    use crate::test_bus::{PrivateBus, StubLogind};
+
+    #[tokio::test]
+    async fn test_inhibit_and_power_signals() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+
+        let (service, inhibit_calls) = StubLogind::serve(&bus).await;
+
+        let logind = Logind::with_connection(bus.connect().await);
+        let _lock = logind.inhibit().await.unwrap();
+        assert_eq!(inhibit_calls.load(Ordering::SeqCst), 1);
+
+        let mut signals = Box::pin(logind.power_signals().await.unwrap());
+        let emitter = SignalEmitter::new(&service, LOGIND_PATH).unwrap();
+        StubLogind::prepare_for_sleep(&emitter, true).await.unwrap();
+        StubLogind::prepare_for_shutdown(&emitter, false)
+            .await
+            .unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            tokio::time::timeout(timeout, signals.next()).await.unwrap(),
+            Some(PowerSignal::PrepareForSleep(true))
+        );
+        assert_eq!(
+            tokio::time::timeout(timeout, signals.next()).await.unwrap(),
+            Some(PowerSignal::PrepareForShutdown(false))
+        );
+    }
+}
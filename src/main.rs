@@ -6,18 +6,31 @@ use clap::{Parser, Subcommand};
 use tracing_subscriber::EnvFilter;
 
 mod analytics;
+mod clock;
 mod config;
+mod content;
 mod context;
+mod coverage;
 mod date_range;
 mod db;
 mod display;
 mod error;
+mod journal;
+mod logind;
 mod mpris;
+mod private;
+mod radio;
+mod reload;
+mod rules;
+mod session;
+#[cfg(test)]
+mod test_bus;
 mod track;
 mod types;
+mod volume;
 
 use config::Config;
-use db::Database;
+use db::{ContentFilter, Database};
 use error::Result;
 
 #[derive(Parser)]
@@ -39,7 +52,11 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Start the music tracker (runs in foreground)
-    Track,
+    Track {
+        /// Write every listen event to this file, for replaying later
+        #[arg(long, value_name = "FILE")]
+        record: Option<std::path::PathBuf>,
+    },
 
     /// Show listening statistics
     Stats {
@@ -62,6 +79,10 @@ enum Commands {
         /// Number of items to show in top lists
         #[arg(short, long, default_value = "10")]
         limit: u32,
+
+        /// Include podcasts, audiobooks and video (default: music only)
+        #[arg(long)]
+        all_content: bool,
     },
 
     /// Show or edit configuration
@@ -80,7 +101,109 @@ enum Commands {
         /// Show database path and stats
         #[arg(long)]
         info: bool,
+
+        #[command(subcommand)]
+        command: Option<DbCommand>,
+    },
+
+    /// Play rules from the configuration
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+
+    /// Query or control the running tracker
+    Tracker {
+        #[command(subcommand)]
+        command: TrackerCommand,
+    },
+
+    /// Private listening: plays are counted but not recorded
+    Private {
+        #[command(subcommand)]
+        command: PrivateCommand,
+    },
+
+    /// Show the plays and skips an event log from `track --record` produces
+    Replay {
+        /// Event log to replay
+        file: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Bring the database schema up to date (the tracker also does this on start)
+    Migrate {
+        /// Only list the migrations that would be applied
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// Show which rules fire for a sample play, e.g. `title="White Noise" player_name=spotify`
+    Test {
+        /// Play fields as `field=value` pairs
+        #[arg(required = true, value_parser = parse_field)]
+        fields: Vec<(String, String)>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrackerCommand {
+    /// Show what the running tracker is doing
+    Status,
+
+    /// Stop logging listens, e.g. `pause 30m` (until resumed if no duration is given)
+    Pause {
+        /// How long to pause for
+        #[arg(value_parser = humantime::parse_duration)]
+        duration: Option<std::time::Duration>,
+    },
+
+    /// Resume logging listens
+    Resume,
+
+    /// Drop the listen in progress so it isn't logged
+    Discard {
+        /// Player ID (default: every player)
+        player: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PrivateCommand {
+    /// Switch private mode on, e.g. `on --for 2h` or `on --player spotify --until-closed`
+    On {
+        /// Player ID (default: every player)
+        #[arg(long)]
+        player: Option<String>,
+        /// How long to stay private (default: until switched off)
+        #[arg(long = "for", value_parser = humantime::parse_duration)]
+        duration: Option<std::time::Duration>,
+        /// Stay private until the player closes
+        #[arg(long, requires = "player", conflicts_with = "duration")]
+        until_closed: bool,
     },
+
+    /// Switch private mode off
+    Off {
+        /// Player ID (default: every session)
+        #[arg(long)]
+        player: Option<String>,
+    },
+
+    /// Show whether private mode is on
+    Status,
+}
+
+/// Parse a `field=value` argument
+fn parse_field(arg: &str) -> std::result::Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(field, value)| (field.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected field=value, got {arg:?}"))
 }
 
 #[tokio::main]
@@ -108,7 +231,13 @@ async fn main() -> Result<()> {
     config.validate()?;
 
     match cli.command {
-        Some(Commands::Track) => run_tracker(config).await,
+        Some(Commands::Track { record }) => {
+            let config_path = match cli.config {
+                Some(path) => path,
+                None => Config::config_path()?,
+            };
+            run_tracker(config, config_path, record).await
+        }
 
         Some(Commands::Stats {
             week,
@@ -116,8 +245,18 @@ async fn main() -> Result<()> {
             year,
             all_time,
             limit,
+            all_content,
         }) => {
-            run_stats(config, week, month, year, all_time, limit).await
+            run_stats(
+                config,
+                week,
+                month,
+                year,
+                all_time,
+                limit,
+                ContentFilter::new(all_content),
+            )
+            .await
         }
 
         Some(Commands::Config { show, init }) => {
@@ -137,41 +276,63 @@ async fn main() -> Result<()> {
             Ok(())
         }
 
-        Some(Commands::Db { info }) => {
+        Some(Commands::Db {
+            command: Some(DbCommand::Migrate { dry_run }),
+            ..
+        }) => migrate_database(&config, dry_run).await,
+
+        Some(Commands::Db { info, command: None }) => {
             if info {
-                let data_dir = config.data_dir()?;
-                let db = Database::new(&config.database, &data_dir).await?;
+                let db_path = config.database_path()?;
+                let db = Database::open_read_only(&db_path).await?;
                 let count = db.get_play_count().await?;
-                println!("Database path: {}", config.database_path()?.display());
+                println!("Database path: {}", db_path.display());
                 println!("Total plays: {count}");
             }
             Ok(())
         }
 
+        Some(Commands::Rules {
+            command: RulesCommand::Test { fields },
+        }) => run_rules_test(&config, &fields),
+
+        Some(Commands::Tracker { command }) => run_tracker_command(command).await,
+
+        Some(Commands::Private { command }) => run_private_command(&config, command).await,
+
+        Some(Commands::Replay { file }) => run_replay(&config, &file),
+
         None => {
             // Default: show stats
-            run_stats(config, false, false, None, false, 10).await
+            run_stats(config, false, false, None, false, 10, ContentFilter::default()).await
         }
     }
 }
 
-async fn run_tracker(config: Config) -> Result<()> {
+async fn run_tracker(
+    config: Config,
+    config_path: std::path::PathBuf,
+    record: Option<std::path::PathBuf>,
+) -> Result<()> {
     use tokio::signal;
 
     let data_dir = config.data_dir()?;
     std::fs::create_dir_all(&data_dir)?;
 
-    let db = Database::new(&config.database, &data_dir).await?;
+    let db = Database::new(&config.database_path()?).await?;
 
-    let monitor = mpris::MprisMonitor::new(
-        config.players.clone(),
-        config.tracking.clone(),
-        db,
-    )
-    .await?;
+    let mut monitor = mpris::MprisMonitor::new(&config, db, &data_dir)
+        .await?
+        .with_config_path(config_path);
+    if let Some(path) = record {
+        monitor = monitor.with_recording(&path)?;
+    }
 
     // Handle shutdown signals
     let monitor_handle = std::sync::Arc::new(monitor);
+
+    // Publish status and controls on the session bus
+    monitor_handle.serve().await?;
     let monitor_clone = monitor_handle.clone();
 
     tokio::spawn(async move {
@@ -183,6 +344,264 @@ async fn run_tracker(config: Config) -> Result<()> {
     monitor_handle.run().await
 }
 
+/// Connect to the running tracker's D-Bus service
+async fn connect_tracker() -> Result<mpris::TrackerProxy<'static>> {
+    if !mpris::is_tracker_running().await {
+        return Err(error::Error::other("The tracker is not running"));
+    }
+    let connection = zbus::Connection::session().await?;
+    Ok(mpris::TrackerProxy::new(&connection).await?)
+}
+
+async fn run_tracker_command(command: TrackerCommand) -> Result<()> {
+    let tracker = connect_tracker().await?;
+
+    match command {
+        TrackerCommand::Status => {
+            let uptime = std::time::Duration::from_secs(tracker.uptime().await?);
+            let paused = if tracker.paused().await? {
+                " (paused)"
+            } else {
+                ""
+            };
+            println!(
+                "Tracker running for {}{}",
+                humantime::format_duration(uptime),
+                paused
+            );
+            let config_path = tracker.config_path().await?;
+            if !config_path.is_empty() {
+                println!("Configuration: {config_path}");
+            }
+            println!("Plays logged: {}", tracker.plays_logged().await?);
+
+            let players = tracker.players().await?;
+            if players.is_empty() {
+                println!("No players");
+            }
+            for player in players {
+                let status = if player.playing { "playing" } else { "paused" };
+                if player.title.is_empty() {
+                    println!("  {}: {}", player.player, status);
+                } else {
+                    println!(
+                        "  {}: {} {} - {} ({}s into listen)",
+                        player.player,
+                        status,
+                        if player.artist.is_empty() { "Unknown" } else { &player.artist },
+                        player.title,
+                        player.played_ms / 1000
+                    );
+                }
+            }
+        }
+        TrackerCommand::Pause { duration } => {
+            let seconds = duration.map_or(0, |duration| duration.as_secs().max(1));
+            tracker.pause_tracking(seconds).await?;
+            if let Some(duration) = duration {
+                println!("Tracking paused for {}", humantime::format_duration(duration));
+            } else {
+                println!("Tracking paused until resumed");
+            }
+        }
+        TrackerCommand::Resume => {
+            tracker.resume_tracking().await?;
+            println!("Tracking resumed");
+        }
+        TrackerCommand::Discard { player } => {
+            let discarded = tracker
+                .discard_current_play(player.as_deref().unwrap_or_default())
+                .await?;
+            println!("Discarded {discarded} listen(s)");
+        }
+    }
+
+    Ok(())
+}
+
+/// Switch private mode on or off through the running tracker.
+///
+/// Without a running tracker the saved sessions are edited directly; the
+/// tracker loads them when it starts.
+async fn run_private_command(config: &Config, command: PrivateCommand) -> Result<()> {
+    let tracker = if mpris::is_tracker_running().await {
+        Some(connect_tracker().await?)
+    } else {
+        None
+    };
+    let not_running = || println!("The tracker is not running; it will apply this when it starts");
+
+    match command {
+        PrivateCommand::On {
+            player,
+            duration,
+            until_closed,
+        } => {
+            let seconds = duration.map_or(0, |duration| duration.as_secs().max(1));
+            let player_arg = player.as_deref().unwrap_or_default();
+            if let Some(tracker) = &tracker {
+                tracker.start_private(player_arg, seconds, until_closed).await?;
+            } else {
+                let mut sessions = private::PrivateSessions::load(&config.data_dir()?)?;
+                sessions.start(private::PrivateSession::requested(
+                    player_arg,
+                    seconds,
+                    until_closed,
+                )?)?;
+                sessions.save()?;
+                not_running();
+            }
+            let who = player.as_deref().unwrap_or("all players");
+            if until_closed {
+                println!("Private mode on for {who} until it closes");
+            } else if let Some(duration) = duration {
+                println!(
+                    "Private mode on for {who} for {}",
+                    humantime::format_duration(duration)
+                );
+            } else {
+                println!("Private mode on for {who} until switched off");
+            }
+        }
+        PrivateCommand::Off { player } => {
+            let ended = if let Some(tracker) = &tracker {
+                tracker
+                    .stop_private(player.as_deref().unwrap_or_default())
+                    .await?
+            } else {
+                let mut sessions = private::PrivateSessions::load(&config.data_dir()?)?;
+                let ended = sessions.stop(player.as_deref()).len();
+                sessions.save()?;
+                not_running();
+                u32::try_from(ended).unwrap_or(u32::MAX)
+            };
+            println!("Switched off {ended} private session(s)");
+        }
+        PrivateCommand::Status => {
+            let (sessions, scheduled) = if let Some(tracker) = &tracker {
+                (
+                    tracker.private_sessions().await?,
+                    tracker.scheduled_private().await?,
+                )
+            } else {
+                let sessions = private::PrivateSessions::load(&config.data_dir()?)?;
+                let schedule = private::PrivateSchedule::new(&config.private)?;
+                (
+                    sessions
+                        .sessions()
+                        .iter()
+                        .map(mpris::PrivateStatus::from_session)
+                        .collect(),
+                    schedule.covers_time(chrono::Local::now()),
+                )
+            };
+
+            if sessions.is_empty() {
+                println!("Private mode off");
+            }
+            for session in sessions {
+                let who = if session.player.is_empty() {
+                    "all players"
+                } else {
+                    &session.player
+                };
+                let until = if session.until_closed {
+                    "until it closes".to_string()
+                } else if let Some(until) = chrono::DateTime::from_timestamp(session.until, 0)
+                    .filter(|_| session.until > 0)
+                {
+                    format!("until {}", until.with_timezone(&chrono::Local).format("%H:%M"))
+                } else {
+                    "until switched off".to_string()
+                };
+                println!("Private mode on for {who} {until}");
+            }
+            if scheduled {
+                println!("A scheduled private window is open");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_rules_test(config: &Config, fields: &[(String, String)]) -> Result<()> {
+    let rules = rules::RuleSet::new(&config.rules)?;
+    if rules.is_empty() {
+        println!("No rules configured");
+        return Ok(());
+    }
+
+    let (mut state, context) = rules::sample_play(fields)?;
+    let before = rules::play_fields(&state, &context);
+    let outcome = rules.apply(&mut state, &context);
+    println!("{outcome}");
+
+    if outcome.dropped_by.is_none() {
+        for (field, value) in rules::play_fields(&state, &context) {
+            if before.get(&field) != Some(&value) {
+                println!("  {field} = {value}");
+            }
+        }
+        if !state.tags.is_empty() {
+            println!("  tags = {}", state.tags.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn run_replay(config: &Config, path: &std::path::Path) -> Result<()> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let records = mpris::read_log(file)?;
+
+    for play in mpris::replay(config, &records)? {
+        let outcome = match play.verdict {
+            mpris::Verdict::Play => "play".to_string(),
+            mpris::Verdict::PrivatePlay => "private play".to_string(),
+            mpris::Verdict::Skip(reason) => format!("skip ({})", reason.as_str()),
+        };
+        println!(
+            "{}  {}: {} - {} ({}s played)",
+            play.ended_at.format("%Y-%m-%d %H:%M:%S"),
+            outcome,
+            play.state.track.artist.as_deref().unwrap_or("Unknown"),
+            play.state.track.title.as_deref().unwrap_or("Unknown"),
+            play.state.played_ms() / 1000
+        );
+    }
+
+    Ok(())
+}
+
+async fn migrate_database(config: &Config, dry_run: bool) -> Result<()> {
+    let path = config.database_path()?;
+    let pending = db::schema::pending_at(&path)?;
+    if pending.is_empty() {
+        println!(
+            "{} is up to date (schema version {})",
+            path.display(),
+            db::schema::latest_version()
+        );
+        return Ok(());
+    }
+
+    for migration in pending {
+        println!(
+            "{} {}: {}{}",
+            if dry_run { "Would apply" } else { "Applying" },
+            migration.version,
+            migration.description,
+            if migration.destructive { " (backs up the database first)" } else { "" }
+        );
+    }
+    if !dry_run {
+        Database::new(&path).await?;
+        println!("{} is now at schema version {}", path.display(), db::schema::latest_version());
+    }
+    Ok(())
+}
+
 async fn run_stats(
     config: Config,
     week: bool,
@@ -190,12 +609,16 @@ async fn run_stats(
     year: Option<i32>,
     all_time: bool,
     limit: u32,
+    content: ContentFilter,
 ) -> Result<()> {
-    let data_dir = config.data_dir()?;
-    let db = Database::new(&config.database, &data_dir).await?;
+    let day_start = config.general.day_start()?;
+    let db = Database::open_read_only(&config.database_path()?)
+        .await?
+        .with_content_filter(content)
+        .with_day_start(day_start);
 
     let (start_date, end_date, period_name) =
-        display::build_date_range(all_time, week, month, year);
+        display::build_date_range(all_time, week, month, year, day_start);
 
     // Get and display stats
     let overview = db
@@ -0,0 +1,128 @@
+//! Duplicate listen detection across players
+//!
+//! The same playback can show up under several MPRIS names: proxies such as
+//! playerctld re-export the active player, and some apps register one name
+//! per instance. A listen is a duplicate if another player already logged the
+//! same track over an overlapping stretch of time.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Local};
+
+/// How long logged listens are remembered for overlap checks
+const RETENTION_HOURS: i64 = 12;
+
+/// A listen that has been logged
+#[derive(Debug, Clone)]
+struct LoggedListen {
+    player: String,
+    identity: String,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+}
+
+/// Recently logged listens, used to drop the same listen seen via another player
+#[derive(Debug, Default)]
+pub struct DuplicateFilter {
+    recent: VecDeque<LoggedListen>,
+}
+
+impl DuplicateFilter {
+    /// Create an empty filter
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Claim a finished listen for logging.
+    ///
+    /// Returns false if another player already logged the same track over an
+    /// overlapping time span; otherwise remembers the listen and returns true.
+    pub fn claim(
+        &mut self,
+        player: &str,
+        identity: &str,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> bool {
+        let cutoff = end - Duration::hours(RETENTION_HOURS);
+        self.recent.retain(|listen| listen.end >= cutoff);
+
+        let duplicate = self.recent.iter().any(|listen| {
+            listen.player != player
+                && listen.identity == identity
+                && listen.start < end
+                && start < listen.end
+        });
+
+        if !duplicate {
+            self.recent.push_back(LoggedListen {
+                player: player.to_string(),
+                identity: identity.to_string(),
+                start,
+                end,
+            });
+        }
+
+        !duplicate
+    }
+}
+
+/// Check if a player is a known proxy that mirrors other players
+#[must_use]
+pub fn is_proxy_player(proxy_players: &[String], player_name: &str) -> bool {
+    proxy_players
+        .iter()
+        .any(|proxy| player_name.contains(proxy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Local> {
+        DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .with_timezone(&Local)
+            + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn test_overlapping_listen_from_other_player_is_duplicate() {
+        let mut filter = DuplicateFilter::new();
+        assert!(filter.claim("spotify", "a\u{1f}song", at(0), at(4)));
+        assert!(!filter.claim("spotify.instance42", "a\u{1f}song", at(1), at(4)));
+    }
+
+    #[test]
+    fn test_same_player_repeat_is_not_duplicate() {
+        let mut filter = DuplicateFilter::new();
+        assert!(filter.claim("spotify", "a\u{1f}song", at(0), at(4)));
+        assert!(filter.claim("spotify", "a\u{1f}song", at(4), at(8)));
+    }
+
+    #[test]
+    fn test_non_overlapping_or_different_track_is_kept() {
+        let mut filter = DuplicateFilter::new();
+        assert!(filter.claim("spotify", "a\u{1f}song", at(0), at(4)));
+        assert!(filter.claim("vlc", "a\u{1f}song", at(10), at(14)));
+        assert!(filter.claim("mpv", "b\u{1f}other", at(11), at(13)));
+    }
+
+    #[test]
+    fn test_old_listens_are_forgotten() {
+        let mut filter = DuplicateFilter::new();
+        assert!(filter.claim("spotify", "a\u{1f}song", at(0), at(4)));
+        assert!(filter.claim("vlc", "b\u{1f}other", at(24 * 60), at(24 * 60 + 3)));
+        assert_eq!(filter.recent.len(), 1);
+    }
+
+    #[test]
+    fn test_is_proxy_player() {
+        let proxies = vec!["playerctld".to_string()];
+        assert!(is_proxy_player(&proxies, "playerctld"));
+        assert!(!is_proxy_player(&proxies, "spotify"));
+    }
+}
@@ -0,0 +1,1046 @@
+//! Listen state machine
+//!
+//! Follows what each player is playing and decides which listens end, and
+//! whether they are logged as plays or skips. It reads no system state: time
+//! comes from the clock the listens run on, and everything learned over D-Bus
+//! arrives as a [`ListenEvent`]. A recorded event log therefore replays to
+//! the same plays (see [`super::replay`]).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::clock::SharedClock;
+use crate::config::{Config, TrackingConfig};
+use crate::content;
+use crate::context::ListeningContext;
+use crate::error::Result;
+use crate::private::{PrivateSchedule, PrivateSessions};
+use crate::radio::RadioParser;
+use crate::rules::RuleSet;
+use crate::track::{SkipReason, Track, TrackState};
+use crate::volume::VolumeLevels;
+
+use super::dedup::{is_proxy_player, DuplicateFilter};
+
+/// Configuration listens are tracked with, replaced as a whole on reload
+pub struct Settings {
+    pub config: Config,
+    /// Splits radio stream titles into songs
+    pub radio: RadioParser,
+    /// User rules run on each play before it is logged
+    pub rules: RuleSet,
+    /// Players and times that are always private
+    pub private: PrivateSchedule,
+}
+
+impl Settings {
+    /// Compile the patterns, rules and private schedule in `config`
+    pub fn new(config: Config) -> Result<Self> {
+        Ok(Self {
+            radio: RadioParser::new(&config.radio)?,
+            rules: RuleSet::new(&config.rules)?,
+            private: PrivateSchedule::new(&config.private)?,
+            config,
+        })
+    }
+
+    /// Effective tracking settings for a player ID, falling back to `[tracking]`
+    #[must_use]
+    pub fn tracking_for(&self, player_name: Option<&str>) -> TrackingConfig {
+        let config = &self.config;
+        player_name.map_or_else(
+            || config.tracking.clone(),
+            |player_id| config.players.tracking_for(player_id, &config.tracking),
+        )
+    }
+}
+
+/// Player state read over D-Bus when a player is attached
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    /// Player ID (bus name suffix)
+    pub name: String,
+    /// Process ID owning the player's bus name
+    pub pid: Option<u32>,
+    /// Track metadata, unless the player reported none
+    pub track: Option<Track>,
+    /// Whether the player reported `Playing`
+    pub playing: bool,
+    pub position_us: Option<i64>,
+    pub loop_status: Option<String>,
+    pub shuffle: Option<bool>,
+    pub rate: Option<f64>,
+    /// Player `Volume` property
+    pub volume: Option<f64>,
+}
+
+/// Something that happened to a player or to tracking.
+///
+/// Players are identified by unique bus name (e.g., `:1.500`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ListenEvent {
+    /// A player was attached
+    PlayerAdded {
+        player: String,
+        snapshot: PlayerSnapshot,
+    },
+    /// A player went away
+    PlayerRemoved { player: String },
+    /// The player's metadata changed
+    TrackChanged { player: String, track: Track },
+    /// Playback started
+    Playing { player: String },
+    /// Playback paused
+    Paused { player: String },
+    /// Playback stopped
+    Stopped { player: String },
+    /// The player announced a seek
+    Seeked { player: String, position_us: i64 },
+    /// A playing player's position was sampled
+    Position { player: String, position_us: i64 },
+    /// Player `LoopStatus`, `Shuffle` or `Rate` changed
+    OptionsChanged {
+        player: String,
+        loop_status: Option<String>,
+        shuffle: Option<bool>,
+        rate: Option<f64>,
+    },
+    /// Player `Volume` changed
+    VolumeChanged { player: String, volume: f64 },
+    /// Sound server volume was sampled
+    VolumeSampled {
+        player: String,
+        levels: VolumeLevels,
+    },
+    /// The system is going to sleep or shutting down
+    Suspended,
+    /// The player was still playing after the system resumed
+    Resumed {
+        player: String,
+        position_us: Option<i64>,
+    },
+    /// Tracking was paused, for `seconds` or until resumed
+    TrackingPaused { seconds: Option<u64> },
+    /// Tracking was resumed
+    TrackingResumed,
+    /// The listen in progress on a player ID (empty = every player) was dropped
+    Discarded { player: String },
+}
+
+impl ListenEvent {
+    /// Copy of the event without track metadata, for private listens
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        let mut event = self.clone();
+        match &mut event {
+            Self::PlayerAdded { snapshot, .. } => {
+                snapshot.track = snapshot.track.as_ref().map(|_| Track::default());
+            }
+            Self::TrackChanged { track, .. } => *track = Track::default(),
+            _ => {}
+        }
+        event
+    }
+}
+
+/// Why a listen ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenEnd {
+    /// The track changed, stopped or looped
+    Moved,
+    /// The player went away
+    PlayerClosed,
+    /// Cut off by pausing tracking, system sleep or the tracker exiting
+    Interrupted,
+}
+
+/// How a finished listen is recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenKind {
+    Play,
+    Skip,
+}
+
+/// What to write for a finished listen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Log a play
+    Play,
+    /// Count a play without recording what it was
+    PrivatePlay,
+    /// Log a skip
+    Skip(SkipReason),
+}
+
+/// A listen that has ended, with its played time frozen
+#[derive(Debug, Clone)]
+pub struct EndedListen {
+    pub state: TrackState,
+    pub end: ListenEnd,
+    pub ended_at: DateTime<Local>,
+    /// Whether tracking was paused when it ended
+    paused: bool,
+}
+
+impl EndedListen {
+    fn new(mut state: TrackState, end: ListenEnd, paused: bool) -> Self {
+        state.close_segment();
+        let ended_at = state.clock.wall();
+        state.timezone = crate::context::timezone();
+        Self {
+            state,
+            end,
+            ended_at,
+            paused,
+        }
+    }
+
+    /// A listen recovered from the journal, which ended at its last checkpoint
+    #[must_use]
+    pub const fn recovered(state: TrackState, ended_at: DateTime<Local>) -> Self {
+        Self {
+            state,
+            end: ListenEnd::Interrupted,
+            ended_at,
+            paused: false,
+        }
+    }
+
+    /// Whether the listen is logged as a play or a skip, or not at all
+    #[must_use]
+    pub fn kind(&self, tracking: &TrackingConfig) -> Option<ListenKind> {
+        let state = &self.state;
+        if qualifies_for_log(tracking, state) {
+            Some(ListenKind::Play)
+        } else if state.has_started() && passes_local_filter(tracking, state) {
+            Some(ListenKind::Skip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tracking paused over D-Bus
+#[derive(Debug, Clone, Copy)]
+struct TrackingPause {
+    /// When tracking resumes by itself (None = until resumed)
+    until: Option<Instant>,
+}
+
+/// Listens in progress on every tracked player
+#[derive(Debug)]
+pub struct Listens {
+    clock: SharedClock,
+    /// Track state by unique bus name
+    players: HashMap<String, TrackState>,
+    /// Set while tracking is paused; listens aren't logged
+    paused: Option<TrackingPause>,
+    /// Recently logged listens, to drop the same playback seen via another player
+    recent: DuplicateFilter,
+}
+
+impl Listens {
+    /// Create an empty set of listens timed by `clock`
+    #[must_use]
+    pub fn new(clock: SharedClock) -> Self {
+        Self {
+            clock,
+            players: HashMap::new(),
+            paused: None,
+            recent: DuplicateFilter::new(),
+        }
+    }
+
+    /// Track state by unique bus name
+    #[must_use]
+    pub const fn players(&self) -> &HashMap<String, TrackState> {
+        &self.players
+    }
+
+    /// Mutable track state by unique bus name
+    pub const fn players_mut(&mut self) -> &mut HashMap<String, TrackState> {
+        &mut self.players
+    }
+
+    /// Check if tracking is paused
+    #[must_use]
+    pub const fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Check if a timed pause has run out
+    #[must_use]
+    pub fn pause_expired(&self) -> bool {
+        matches!(
+            self.paused,
+            Some(TrackingPause { until: Some(until) }) if until <= self.clock.now()
+        )
+    }
+
+    /// Apply an event, returning the listens it ended
+    pub fn apply(
+        &mut self,
+        event: &ListenEvent,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+    ) -> Vec<EndedListen> {
+        let paused = self.is_paused();
+
+        match event {
+            ListenEvent::PlayerAdded { player, snapshot } => {
+                if !self.players.contains_key(player) {
+                    let state = self.attach(snapshot, settings, sessions);
+                    self.players.insert(player.clone(), state);
+                }
+                Vec::new()
+            }
+            ListenEvent::PlayerRemoved { player } => self
+                .players
+                .remove(player)
+                .filter(TrackState::has_started)
+                .map(|state| EndedListen::new(state, ListenEnd::PlayerClosed, paused))
+                .into_iter()
+                .collect(),
+            ListenEvent::TrackChanged { player, track } => self
+                .change_track(player, track, settings, sessions)
+                .into_iter()
+                .collect(),
+            ListenEvent::Playing { player } => {
+                self.start_playing(player, settings, sessions);
+                Vec::new()
+            }
+            ListenEvent::Paused { player } => {
+                self.pause(player);
+                Vec::new()
+            }
+            ListenEvent::Stopped { player } => self.stop(player).into_iter().collect(),
+            ListenEvent::Seeked {
+                player,
+                position_us,
+            } => self
+                .seek(player, *position_us, settings)
+                .into_iter()
+                .collect(),
+            ListenEvent::Position {
+                player,
+                position_us,
+            } => self
+                .follow_position(player, *position_us)
+                .into_iter()
+                .collect(),
+            ListenEvent::OptionsChanged {
+                player,
+                loop_status,
+                shuffle,
+                rate,
+            } => {
+                self.set_options(player, loop_status.as_deref(), *shuffle, *rate);
+                Vec::new()
+            }
+            ListenEvent::VolumeChanged { player, volume } => {
+                self.set_volume(player, *volume, settings);
+                Vec::new()
+            }
+            ListenEvent::VolumeSampled { player, levels } => {
+                if let Some(state) = self.players.get_mut(player) {
+                    state.record_volume(*levels);
+                }
+                Vec::new()
+            }
+            ListenEvent::Suspended => self.suspend(),
+            ListenEvent::Resumed {
+                player,
+                position_us,
+            } => {
+                self.resume(player, *position_us, settings, sessions);
+                Vec::new()
+            }
+            ListenEvent::TrackingPaused { seconds } => self.pause_tracking(*seconds),
+            ListenEvent::TrackingResumed => {
+                self.resume_tracking();
+                Vec::new()
+            }
+            ListenEvent::Discarded { player } => {
+                self.discard(player);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Start a new listen on a player's track change, returning the one it
+    /// ended.
+    ///
+    /// Late or cosmetic updates (art, length, album) for the same track are
+    /// merged into the current listen instead.
+    fn change_track(
+        &mut self,
+        player: &str,
+        track: &Track,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+    ) -> Option<EndedListen> {
+        let now = self.clock.wall();
+        let paused = self.is_paused();
+        let state = self.players.get_mut(player)?;
+        let player_name = state.player_name.clone().unwrap_or_default();
+        let mut track = track.clone();
+        settings.radio.apply(&mut track);
+        track.content_type = content::classify(&track, Some(&player_name));
+        let is_local = track.is_local_source(
+            &settings.config.players.local_only_players,
+            Some(&player_name),
+        );
+
+        if state.track.is_same_track(&track) {
+            debug!("[{}] Metadata updated", player_name);
+            state.track = track;
+            state.is_local = is_local;
+            return None;
+        }
+
+        // The previous listen is logged as a play or skip
+        let ended = state
+            .has_started()
+            .then(|| EndedListen::new(state.clone(), ListenEnd::Moved, paused));
+
+        // Start a fresh listen (resets play time, pauses and seeks)
+        state.track = track;
+        state.is_local = is_local;
+        state.reset_listen();
+        state.private = player_is_private(&player_name, settings, sessions, now);
+
+        let tracking = settings.tracking_for(Some(&player_name));
+        let local_info = if passes_local_filter(&tracking, state) {
+            ""
+        } else {
+            " (non-local, won't track)"
+        };
+        info!(
+            "[{}] Track changed: {}{}",
+            player_name,
+            describe(state),
+            local_info
+        );
+
+        ended
+    }
+
+    /// Start or resume playback on a player
+    fn start_playing(&mut self, player: &str, settings: &Settings, sessions: &PrivateSessions) {
+        let now = self.clock.wall();
+        if let Some(state) = self
+            .players
+            .get_mut(player)
+            .filter(|state| !state.is_playing)
+        {
+            let player_name = state.player_name.clone().unwrap_or_default();
+            state.start_playing();
+            state.private |= player_is_private(&player_name, settings, sessions, now);
+            info!("[{}] Playing: {}", player_name, describe(state));
+        }
+    }
+
+    /// Pause a player.
+    ///
+    /// Pausing only closes the current segment; the listen is logged once the
+    /// track changes, stops, or the player goes away.
+    fn pause(&mut self, player: &str) {
+        if let Some(state) = self
+            .players
+            .get_mut(player)
+            .filter(|state| state.is_playing)
+        {
+            state.stop_playing();
+            info!(
+                "[{}] Paused",
+                state.player_name.as_deref().unwrap_or(player)
+            );
+        }
+    }
+
+    /// Stop a player, returning the listen it ended
+    fn stop(&mut self, player: &str) -> Option<EndedListen> {
+        let paused = self.is_paused();
+        let state = self.players.get_mut(player)?;
+        let ended = state
+            .has_started()
+            .then(|| EndedListen::new(state.clone(), ListenEnd::Moved, paused));
+        state.stop_playing();
+        state.reset_listen();
+        info!(
+            "[{}] Stopped",
+            state.player_name.as_deref().unwrap_or(player)
+        );
+        ended
+    }
+
+    /// Follow a seek the player announced, returning the listen it ended if
+    /// it jumped back to the start of a looping track
+    fn seek(&mut self, player: &str, position_us: i64, settings: &Settings) -> Option<EndedListen> {
+        let paused = self.is_paused();
+        let state = self.players.get_mut(player)?;
+        let player_name = state.player_name.clone().unwrap_or_default();
+        if state.is_loop_restart(position_us) {
+            info!("[{}] Track looped", player_name);
+            let finished = state.restart_loop(position_us);
+            return Some(EndedListen::new(finished, ListenEnd::Moved, paused));
+        }
+
+        if settings.tracking_for(Some(&player_name)).track_seeks {
+            state.on_seeked(position_us);
+            debug!(
+                "[{}] Seeked to {}s (total seeks: {})",
+                player_name,
+                position_us / 1_000_000,
+                state.seek_count
+            );
+        } else {
+            // Still follow the position so heard audio stays accurate
+            state.reposition(position_us);
+        }
+        None
+    }
+
+    /// Follow a playing player's sampled position, returning the listen it
+    /// ended if the track looped
+    fn follow_position(&mut self, player: &str, position_us: i64) -> Option<EndedListen> {
+        let paused = self.is_paused();
+        let state = self
+            .players
+            .get_mut(player)
+            .filter(|state| state.is_playing)?;
+        // Players looping a single track often only reveal it through Position
+        if state.is_loop_restart(position_us) {
+            info!(
+                "[{}] Track looped",
+                state.player_name.as_deref().unwrap_or(player)
+            );
+            let finished = state.restart_loop(position_us);
+            return Some(EndedListen::new(finished, ListenEnd::Moved, paused));
+        }
+        state.on_position(position_us);
+        None
+    }
+
+    /// Record the player's loop status, shuffle and rate that changed
+    fn set_options(
+        &mut self,
+        player: &str,
+        loop_status: Option<&str>,
+        shuffle: Option<bool>,
+        rate: Option<f64>,
+    ) {
+        let Some(state) = self.players.get_mut(player) else {
+            return;
+        };
+        if let Some(loop_status) = loop_status {
+            debug!("[{}] Loop status: {}", player, loop_status);
+            state.loop_status = Some(loop_status.to_string());
+        }
+        if let Some(shuffle) = shuffle {
+            debug!("[{}] Shuffle: {}", player, shuffle);
+            state.shuffle = Some(shuffle);
+        }
+        if let Some(rate) = rate {
+            debug!("[{}] Rate: {}", player, rate);
+            state.set_rate(rate);
+        }
+    }
+
+    /// Record the player's own volume, if volume is tracked for it
+    fn set_volume(&mut self, player: &str, volume: f64, settings: &Settings) {
+        if let Some(state) = self.players.get_mut(player) {
+            if settings
+                .tracking_for(state.player_name.as_deref())
+                .track_volume
+            {
+                debug!("[{}] Volume: {:.2}", player, volume);
+                state.mpris_volume = Some(volume);
+            }
+        }
+    }
+
+    /// End every listen as the system goes to sleep, returning them.
+    ///
+    /// Players are left paused with a fresh listen; [`ListenEvent::Resumed`]
+    /// restarts the ones still playing afterwards.
+    fn suspend(&mut self) -> Vec<EndedListen> {
+        let paused = self.is_paused();
+        self.players
+            .values_mut()
+            .filter_map(|state| {
+                let ended = state
+                    .has_started()
+                    .then(|| EndedListen::new(state.clone(), ListenEnd::Interrupted, paused));
+                state.stop_playing();
+                state.reset_listen();
+                ended
+            })
+            .collect()
+    }
+
+    /// Restart a player that was still playing after the system resumed
+    fn resume(
+        &mut self,
+        player: &str,
+        position_us: Option<i64>,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+    ) {
+        let now = self.clock.wall();
+        let Some(state) = self.players.get_mut(player) else {
+            return;
+        };
+        let player_name = state.player_name.clone().unwrap_or_default();
+        if let Some(position_us) = position_us {
+            state.reposition(position_us);
+        }
+        state.start_playing();
+        state.private = player_is_private(&player_name, settings, sessions, now);
+        info!(
+            "[{}] Still playing after resume: {}",
+            player_name,
+            describe(state)
+        );
+    }
+
+    /// Pause tracking for `seconds` or until resumed, returning the listens
+    /// heard so far, which are logged first
+    fn pause_tracking(&mut self, seconds: Option<u64>) -> Vec<EndedListen> {
+        let ended = self.restart_listens(self.is_paused());
+        let duration = seconds.map(Duration::from_secs);
+        self.paused = Some(TrackingPause {
+            until: duration.map(|duration| self.clock.now() + duration),
+        });
+        if let Some(seconds) = seconds {
+            info!("Tracking paused for {}s", seconds);
+        } else {
+            info!("Tracking paused");
+        }
+        ended
+    }
+
+    /// Resume tracking; playback during the pause is not counted
+    fn resume_tracking(&mut self) {
+        if self.paused.take().is_some() {
+            self.restart_listens(true);
+            info!("Tracking resumed");
+        }
+    }
+
+    /// Track state for a newly attached player
+    fn attach(
+        &self,
+        snapshot: &PlayerSnapshot,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+    ) -> TrackState {
+        let name = snapshot.name.as_str();
+        let tracking = settings.tracking_for(Some(name));
+
+        let mut state = TrackState::with_clock(self.clock.clone());
+        state.player_name = Some(name.to_string());
+        state.player_pid = snapshot.pid;
+
+        if let Some(track) = &snapshot.track {
+            let mut track = track.clone();
+            settings.radio.apply(&mut track);
+            track.content_type = content::classify(&track, Some(name));
+            state.is_local =
+                track.is_local_source(&settings.config.players.local_only_players, Some(name));
+            state.track = track;
+        }
+        if let Some(position_us) = snapshot.position_us {
+            state.last_position_us = position_us;
+        }
+        state.loop_status.clone_from(&snapshot.loop_status);
+        state.shuffle = snapshot.shuffle;
+        state.rate = snapshot.rate;
+        if tracking.track_volume {
+            state.mpris_volume = snapshot.volume;
+        }
+
+        if snapshot.playing {
+            state.start_playing();
+            state.private = player_is_private(name, settings, sessions, self.clock.wall());
+            info!("[{}] Already playing: {}", name, describe(&state));
+        }
+        state
+    }
+
+    /// Drop the listen in progress on `player` (empty = every player).
+    ///
+    /// Returns how many listens were discarded.
+    pub fn discard(&mut self, player: &str) -> u32 {
+        let mut discarded = 0;
+        for state in self.players.values_mut() {
+            let selected = player.is_empty() || state.player_name.as_deref() == Some(player);
+            if selected && state.has_started() {
+                info!(
+                    "[{}] Discarding current listen: {}",
+                    state.player_name.as_deref().unwrap_or("unknown"),
+                    describe(state)
+                );
+                state.reset_listen();
+                discarded += 1;
+            }
+        }
+        discarded
+    }
+
+    /// Start fresh listens on every player, returning the started listens they replace
+    fn restart_listens(&mut self, paused: bool) -> Vec<EndedListen> {
+        self.players
+            .values_mut()
+            .filter_map(|state| {
+                let ended = state
+                    .has_started()
+                    .then(|| EndedListen::new(state.clone(), ListenEnd::Interrupted, paused));
+                state.reset_listen();
+                ended
+            })
+            .collect()
+    }
+
+    /// Listens in progress, ended as if the tracker exited now
+    #[must_use]
+    pub fn finish(&self) -> Vec<EndedListen> {
+        let paused = self.is_paused();
+        self.players
+            .values()
+            .filter(|state| state.has_started())
+            .map(|state| EndedListen::new(state.clone(), ListenEnd::Interrupted, paused))
+            .collect()
+    }
+
+    /// Flag listens private mode covers now, so status and logs hide them
+    pub fn mark_private(&mut self, settings: &Settings, sessions: &PrivateSessions) {
+        let now = self.clock.wall();
+        for state in self.players.values_mut() {
+            if (state.is_playing || state.has_started())
+                && listen_is_private(state, &settings.private, sessions, now)
+            {
+                state.private = true;
+            }
+        }
+    }
+
+    /// Check if the listen an event concerns is private, so a recording of
+    /// it must leave out what was playing
+    #[must_use]
+    pub fn event_is_private(
+        &self,
+        event: &ListenEvent,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+    ) -> bool {
+        let now = self.clock.wall();
+        match event {
+            ListenEvent::PlayerAdded { snapshot, .. } => {
+                player_is_private(&snapshot.name, settings, sessions, now)
+            }
+            ListenEvent::TrackChanged { player, .. } => {
+                self.players.get(player).is_some_and(|state| {
+                    let player_name = state.player_name.as_deref().unwrap_or_default();
+                    state.private || player_is_private(player_name, settings, sessions, now)
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// Decide how a finished listen is written.
+    ///
+    /// Every ended listen comes through here, so duplicates, private mode and
+    /// rules are handled the same way for plays and skips. Rules may edit
+    /// the listen.
+    pub fn judge(
+        &mut self,
+        listen: &mut EndedListen,
+        settings: &Settings,
+        sessions: &PrivateSessions,
+        context: &ListeningContext,
+    ) -> Option<Verdict> {
+        let tracking = settings.tracking_for(listen.state.player_name.as_deref());
+        let kind = listen.kind(&tracking)?;
+        if listen.state.track.title.is_none() || !self.claim(listen, settings) {
+            return None;
+        }
+
+        if listen_is_private(&listen.state, &settings.private, sessions, listen.ended_at) {
+            if kind == ListenKind::Play {
+                info!("Private mode, counting play without recording it");
+                return Some(Verdict::PrivatePlay);
+            }
+            debug!("Private mode, not logging skip");
+            return None;
+        }
+
+        let outcome = settings.rules.apply(&mut listen.state, context);
+        if let Some(rule) = outcome.dropped_by {
+            info!(
+                "Dropping listen (rule {:?}): {}",
+                rule,
+                describe(&listen.state)
+            );
+            return None;
+        }
+        for rule in outcome.fired() {
+            debug!("Rule {:?} applied", rule);
+        }
+
+        Some(match kind {
+            ListenKind::Play => Verdict::Play,
+            ListenKind::Skip => Verdict::Skip(match listen.end {
+                ListenEnd::Moved => listen.state.skip_reason(false),
+                ListenEnd::PlayerClosed => listen.state.skip_reason(true),
+                ListenEnd::Interrupted => SkipReason::Interrupted,
+            }),
+        })
+    }
+
+    /// Check that tracking wasn't paused and that a finished listen isn't the
+    /// same playback already seen through another MPRIS name, and remember it if not
+    fn claim(&mut self, listen: &EndedListen, settings: &Settings) -> bool {
+        let state = &listen.state;
+        if listen.paused {
+            debug!("Tracking paused, dropping listen: {}", describe(state));
+            return false;
+        }
+
+        let (Some(identity), Some(start)) = (state.track.identity(), state.start_timestamp) else {
+            return true;
+        };
+        let player = state.player_name.as_deref().unwrap_or_default();
+        let proxies = &settings.config.players.proxy_players;
+
+        // A proxy defers to the player it mirrors, if that player is tracked
+        if is_proxy_player(proxies, player) {
+            let mirrored = self.players.values().any(|other| {
+                other
+                    .player_name
+                    .as_deref()
+                    .is_some_and(|name| !is_proxy_player(proxies, name))
+                    && other.track.identity().as_ref() == Some(&identity)
+            });
+            if mirrored {
+                debug!("[{}] Dropping listen mirrored from another player", player);
+                return false;
+            }
+        }
+
+        let claimed = self.recent.claim(player, &identity, start, listen.ended_at);
+        if !claimed {
+            debug!(
+                "[{}] Dropping duplicate of a listen logged by another player",
+                player
+            );
+        }
+        claimed
+    }
+}
+
+/// Check the `local_only` setting; radio songs are always tracked
+const fn passes_local_filter(tracking: &TrackingConfig, state: &TrackState) -> bool {
+    !tracking.local_only || state.is_local || state.track.is_radio
+}
+
+/// Check if a listen meets the play thresholds and the `local_only` setting
+fn qualifies_for_log(tracking: &TrackingConfig, state: &TrackState) -> bool {
+    state.should_log(tracking.min_play_seconds, tracking.min_play_percent)
+        && passes_local_filter(tracking, state)
+}
+
+/// Check if private mode covers a listen on `player_id` starting at `at`
+fn player_is_private(
+    player_id: &str,
+    settings: &Settings,
+    sessions: &PrivateSessions,
+    at: DateTime<Local>,
+) -> bool {
+    sessions.covers(player_id) || settings.private.covers(player_id, at)
+}
+
+/// Check if a listen ending at `at` is private under the configured schedule
+/// or a runtime session.
+///
+/// Scheduled windows count if they cover the start or the end of the listen.
+pub fn listen_is_private(
+    state: &TrackState,
+    schedule: &PrivateSchedule,
+    sessions: &PrivateSessions,
+    at: DateTime<Local>,
+) -> bool {
+    let player = state.player_name.as_deref().unwrap_or_default();
+    state.private
+        || sessions.covers(player)
+        || schedule.covers(player, at)
+        || state
+            .start_timestamp
+            .is_some_and(|start| schedule.covers_time(start))
+}
+
+/// Artist and title for logs; hidden for private listens
+pub fn describe(state: &TrackState) -> String {
+    if state.private {
+        return "(private listen)".to_string();
+    }
+    format!(
+        "{} - {}",
+        state.track.artist.as_deref().unwrap_or("Unknown"),
+        state.track.title.as_deref().unwrap_or("Unknown")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::clock::ManualClock;
+
+    fn settings() -> Settings {
+        let mut config = Config::default();
+        config.tracking.min_play_seconds = 30;
+        config.tracking.local_only = false;
+        Settings::new(config).unwrap()
+    }
+
+    fn track(title: &str) -> Track {
+        Track {
+            title: Some(title.to_string()),
+            artist: Some("Artist".to_string()),
+            duration_us: Some(60_000_000),
+            ..Track::default()
+        }
+    }
+
+    fn playing(player: &str, title: &str) -> ListenEvent {
+        ListenEvent::PlayerAdded {
+            player: player.to_string(),
+            snapshot: PlayerSnapshot {
+                name: "stub".to_string(),
+                track: Some(track(title)),
+                playing: true,
+                ..PlayerSnapshot::default()
+            },
+        }
+    }
+
+    /// Apply events and judge the listens they end
+    fn run(
+        listens: &mut Listens,
+        clock: &ManualClock,
+        events: &[(u64, ListenEvent)],
+    ) -> Vec<(Option<String>, Verdict)> {
+        let settings = settings();
+        let sessions = PrivateSessions::default();
+        let mut verdicts = Vec::new();
+        for (at_secs, event) in events {
+            clock.set_elapsed(Duration::from_secs(*at_secs));
+            for mut listen in listens.apply(event, &settings, &sessions) {
+                let context = ListeningContext::default();
+                if let Some(verdict) = listens.judge(&mut listen, &settings, &sessions, &context) {
+                    verdicts.push((listen.state.track.title.clone(), verdict));
+                }
+            }
+        }
+        verdicts
+    }
+
+    fn manual() -> (Listens, Arc<ManualClock>) {
+        let clock = ManualClock::new(Local::now());
+        (Listens::new(clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_track_change_logs_play_then_skip() {
+        let (mut listens, clock) = manual();
+        let verdicts = run(
+            &mut listens,
+            &clock,
+            &[
+                (0, playing(":1.1", "One")),
+                (
+                    40,
+                    ListenEvent::TrackChanged {
+                        player: ":1.1".to_string(),
+                        track: track("Two"),
+                    },
+                ),
+                (
+                    50,
+                    ListenEvent::TrackChanged {
+                        player: ":1.1".to_string(),
+                        track: track("Three"),
+                    },
+                ),
+            ],
+        );
+
+        assert_eq!(
+            verdicts,
+            [
+                (Some("One".to_string()), Verdict::Play),
+                (Some("Two".to_string()), Verdict::Skip(SkipReason::Skipped)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_listens_ended_while_paused_are_dropped() {
+        let (mut listens, clock) = manual();
+        let verdicts = run(
+            &mut listens,
+            &clock,
+            &[
+                (0, playing(":1.1", "One")),
+                // Heard for 40s before pausing: logged
+                (40, ListenEvent::TrackingPaused { seconds: None }),
+                // Heard for 60s while paused: dropped
+                (
+                    100,
+                    ListenEvent::PlayerRemoved {
+                        player: ":1.1".to_string(),
+                    },
+                ),
+            ],
+        );
+
+        assert_eq!(verdicts, [(Some("One".to_string()), Verdict::Play)]);
+    }
+
+    #[test]
+    fn test_position_reveals_loop() {
+        let (mut listens, clock) = manual();
+        let events = [
+            (0, playing(":1.1", "Loop")),
+            (
+                58,
+                ListenEvent::Position {
+                    player: ":1.1".to_string(),
+                    position_us: 58_000_000,
+                },
+            ),
+            (
+                63,
+                ListenEvent::Position {
+                    player: ":1.1".to_string(),
+                    position_us: 3_000_000,
+                },
+            ),
+        ];
+        let verdicts = run(&mut listens, &clock, &events);
+
+        assert_eq!(verdicts, [(Some("Loop".to_string()), Verdict::Play)]);
+        let state = &listens.players()[":1.1"];
+        assert!(state.is_repeat);
+        assert_eq!(state.played_duration(), Duration::from_secs(3));
+    }
+}
@@ -1,16 +1,33 @@
 //! MPRIS metadata parsing
 
 use std::collections::HashMap;
-use zbus::zvariant::OwnedValue;
+use zbus::zvariant::{OwnedValue, Value};
 
 use crate::track::Track;
 
 use super::{extract, extract_first_or_string, extract_or_join_array};
 
+/// `mpris:trackid` sent when no track is loaded
+const NO_TRACK_ID: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Extract `mpris:trackid`, an object path (some players send a plain string)
+fn extract_track_id(value: &OwnedValue) -> Option<String> {
+    let id = match &**value {
+        Value::ObjectPath(path) => Some(path.to_string()),
+        _ => extract(value),
+    };
+    id.filter(|id| id != NO_TRACK_ID)
+}
+
 /// Parse MPRIS metadata into a Track
 pub fn parse_metadata(metadata: &HashMap<String, OwnedValue>) -> Track {
     let mut track = Track::default();
 
+    // Track ID
+    if let Some(value) = metadata.get("mpris:trackid") {
+        track.track_id = extract_track_id(value);
+    }
+
     // Title
     if let Some(value) = metadata.get("xesam:title") {
         track.title = extract(value);
@@ -100,4 +117,23 @@ mod tests {
         assert!(track.title.is_none());
         assert!(track.artist.is_none());
     }
+
+    #[test]
+    fn test_parse_track_id() {
+        use zbus::zvariant::ObjectPath;
+
+        let track_id = |path: &'static str| {
+            let path = ObjectPath::try_from(path).unwrap();
+            HashMap::from([(
+                "mpris:trackid".to_string(),
+                OwnedValue::try_from(Value::from(path)).unwrap(),
+            )])
+        };
+
+        let track = parse_metadata(&track_id("/com/spotify/track/abc"));
+        assert_eq!(track.track_id.as_deref(), Some("/com/spotify/track/abc"));
+
+        let track = parse_metadata(&track_id(NO_TRACK_ID));
+        assert!(track.track_id.is_none());
+    }
 }
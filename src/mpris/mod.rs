@@ -3,11 +3,20 @@
 //! Monitors MPRIS-compatible media players via D-Bus signals.
 //! Uses async event-driven architecture (not polling).
 
+mod dedup;
+mod listen;
 mod metadata;
 mod player;
+mod replay;
+mod service;
 
+pub use listen::Verdict;
 pub use metadata::parse_metadata;
 pub use player::MprisMonitor;
+pub use replay::{read_log, replay};
+pub use service::{is_tracker_running, query_tracker, PrivateStatus, TrackerProxy};
+#[allow(unused_imports)] // Used by the GUI
+pub use service::PlayerStatus;
 
 use zbus::zvariant::{OwnedValue, Value};
 
@@ -100,6 +109,17 @@ impl ExtractValue for f64 {
     }
 }
 
+impl ExtractValue for bool {
+    fn extract(value: &OwnedValue) -> Option<Self> {
+        bool::try_from(value.clone())
+            .ok()
+            .or_else(|| match Value::from(value.clone()) {
+                Value::Bool(v) => Some(v),
+                _ => None,
+            })
+    }
+}
+
 /// Convenience function to extract a value using the ExtractValue trait
 pub fn extract<T: ExtractValue>(value: &OwnedValue) -> Option<T> {
     T::extract(value)
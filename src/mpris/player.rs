@@ -1,39 +1,55 @@
 //! MPRIS player monitoring
 
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::{DateTime, Local};
 use futures::StreamExt;
 use tokio::sync::{mpsc, RwLock};
 use tokio::time::Instant;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use zbus::fdo::DBusProxy;
-use zbus::zvariant::OwnedValue;
 use zbus::message::Type as MessageType;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedFd, OwnedValue};
 use zbus::{Connection, MatchRule, MessageStream};
 
-use crate::config::{PlayerConfig, TrackingConfig};
+use crate::clock::{self, SharedClock};
+use crate::config::{Config, PlayerConfig};
 use crate::context::ListeningContext;
 use crate::db::Database;
 use crate::error::Result;
+use crate::journal::{Journal, ListenCheckpoint};
+use crate::logind::{Logind, PowerSignal};
+use crate::private::{PrivateSession, PrivateSessions};
+use crate::reload::{self, ReloadTrigger};
+use crate::session::SessionTracker;
 use crate::track::{Track, TrackState};
-
-use super::{extract_string, parse_metadata, MPRIS_PATH, MPRIS_PLAYER_IFACE, MPRIS_PREFIX};
+use crate::volume::{app_name_for_player, VolumeReader};
+
+use super::listen::{
+    describe, listen_is_private, EndedListen, ListenEvent, Listens, PlayerSnapshot, Settings,
+    Verdict,
+};
+use super::replay::Recorder;
+use super::service::{self, PlayerStatus, TrackerService};
+use super::{extract, extract_string, parse_metadata, MPRIS_PATH, MPRIS_PLAYER_IFACE, MPRIS_PREFIX};
+
+/// How often playing players' sound server volume is sampled.
+///
+/// Stream and device volume changes aren't signalled over MPRIS, so they are
+/// picked up on this timer whether or not positions are polled.
+const VOLUME_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
 
 /// Events emitted by the MPRIS monitor
 #[derive(Debug, Clone)]
 pub enum MprisEvent {
     /// A new track started playing
-    TrackChanged {
-        player: String,
-        track: Track,
-        /// Whether the track is from a local source.
-        /// Currently recomputed at handling time but kept for future use.
-        #[allow(dead_code)]
-        is_local: bool,
-    },
+    TrackChanged { player: String, track: Track },
     /// Playback started
     Playing { player: String },
     /// Playback paused
@@ -46,61 +62,157 @@ pub enum MprisEvent {
     PlayerDisappeared { player: String },
     /// Seek occurred
     Seeked { player: String, position_us: i64 },
+    /// Player `LoopStatus`, `Shuffle` or `Rate` changed
+    PlaybackOptionsChanged {
+        player: String,
+        loop_status: Option<String>,
+        shuffle: Option<bool>,
+        rate: Option<f64>,
+    },
+    /// Player `Volume` changed
+    VolumeChanged { player: String, volume: f64 },
+    /// System sleep or shutdown announced by logind
+    Power(PowerSignal),
+    /// Configuration file changed or `SIGHUP` received
+    ReloadConfig(ReloadTrigger),
 }
 
 /// MPRIS player monitor
 pub struct MprisMonitor {
     connection: Connection,
-    player_config: PlayerConfig,
-    tracking_config: TrackingConfig,
+    /// Current settings; reads take a snapshot so a reload swaps them atomically
+    settings: Arc<RwLock<Arc<Settings>>>,
+    /// Configuration file reloaded on change or `SIGHUP`
+    config_path: Option<PathBuf>,
     db: Database,
-    /// Map from unique bus name (e.g., `:1.500`) to track state
-    tracked_players: Arc<RwLock<HashMap<String, TrackState>>>,
+    /// Time source for listens, sessions and the idle timer
+    clock: SharedClock,
+    /// Listen in progress per unique bus name (e.g., `:1.500`)
+    listens: Arc<RwLock<Listens>>,
+    /// Log of applied events, for replaying later
+    recorder: Option<Recorder>,
     /// Map from unique bus name to well-known name (e.g., `org.mpris.MediaPlayer2.io.bassi.Amberol`)
     bus_name_map: Arc<RwLock<HashMap<String, String>>>,
     /// Atomic flag for stop signaling - more efficient than RwLock for simple bools
     running: Arc<AtomicBool>,
-    idle_since: Arc<RwLock<Option<Instant>>>,
+    idle_since: Arc<RwLock<Option<std::time::Instant>>>,
+    /// Current listening session shared by all players
+    session: Arc<RwLock<SessionTracker>>,
+    /// Sound server volume reader
+    volume: VolumeReader,
+    /// Checkpoints of in-progress listens, for recovery after a crash
+    journal: Journal,
+    /// logind client, if the system bus is available
+    logind: Option<Logind>,
+    /// Delay inhibitor lock held while awake, released once plays are closed
+    inhibitor: Arc<RwLock<Option<OwnedFd>>>,
+    /// When the monitor was created
+    started_at: Instant,
+    /// Plays written to the database since start
+    plays_logged: Arc<AtomicU32>,
+    /// Private mode switched on at runtime
+    private_sessions: Arc<RwLock<PrivateSessions>>,
 }
 
 impl MprisMonitor {
     /// Create a new MPRIS monitor
-    pub async fn new(
-        player_config: PlayerConfig,
-        tracking_config: TrackingConfig,
+    ///
+    /// # Errors
+    ///
+    /// Fails if the session bus is unavailable, the configured rules or radio
+    /// patterns don't compile, or the saved private sessions can't be read.
+    pub async fn new(config: &Config, db: Database, data_dir: &Path) -> Result<Self> {
+        let connection = Connection::session().await?;
+        let logind = match Logind::connect().await {
+            Ok(logind) => Some(logind),
+            Err(e) => {
+                debug!("logind not available, sleep and shutdown won't be tracked: {}", e);
+                None
+            }
+        };
+
+        Self::with_connections(config, db, data_dir, connection, logind)
+    }
+
+    /// Create a monitor watching players on `connection`, with sleep and
+    /// shutdown announced by `logind`
+    pub(crate) fn with_connections(
+        config: &Config,
         db: Database,
+        data_dir: &Path,
+        connection: Connection,
+        logind: Option<Logind>,
     ) -> Result<Self> {
-        let connection = Connection::session().await?;
+        let session_gap = Duration::from_secs(config.tracking.session_gap_seconds);
+        let settings = Settings::new(config.clone())?;
+        let clock = clock::system();
 
         Ok(Self {
             connection,
-            player_config,
-            tracking_config,
+            settings: Arc::new(RwLock::new(Arc::new(settings))),
+            config_path: None,
             db,
-            tracked_players: Arc::new(RwLock::new(HashMap::new())),
+            listens: Arc::new(RwLock::new(Listens::new(Arc::clone(&clock)))),
+            clock,
+            recorder: None,
             bus_name_map: Arc::new(RwLock::new(HashMap::new())),
             running: Arc::new(AtomicBool::new(true)),
             idle_since: Arc::new(RwLock::new(None)),
+            session: Arc::new(RwLock::new(SessionTracker::new(session_gap))),
+            volume: VolumeReader::new(),
+            journal: Journal::new(data_dir),
+            logind,
+            inhibitor: Arc::new(RwLock::new(None)),
+            started_at: Instant::now(),
+            plays_logged: Arc::new(AtomicU32::new(0)),
+            private_sessions: Arc::new(RwLock::new(PrivateSessions::load(data_dir)?)),
         })
     }
 
+    /// Reload the configuration from `path` when it changes or on `SIGHUP`
+    #[must_use]
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Measure listens against `clock` instead of the system clock
+    #[cfg(test)]
+    pub(crate) fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.listens = Arc::new(RwLock::new(Listens::new(Arc::clone(&clock))));
+        self.clock = clock;
+        self
+    }
+
+    /// Write every applied event to an event log at `path`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the log file can't be created.
+    pub fn with_recording(mut self, path: &Path) -> Result<Self> {
+        self.recorder = Some(Recorder::create(path, Arc::clone(&self.clock))?);
+        info!("Recording listen events to {}", path.display());
+        Ok(self)
+    }
+
+    /// Snapshot of the current settings
+    async fn settings(&self) -> Arc<Settings> {
+        Arc::clone(&*self.settings.read().await)
+    }
+
     /// Start monitoring MPRIS players
+    ///
+    /// # Errors
+    ///
+    /// Fails if the session bus can't be watched for players.
     pub async fn run(&self) -> Result<()> {
         info!("Starting MPRIS monitor...");
 
-        // Discover existing players
-        self.discover_players().await?;
-
-        // Check if we found any players
-        {
-            let players = self.tracked_players.read().await;
-            if players.is_empty() {
-                info!("No players found, starting idle timer...");
-                *self.idle_since.write().await = Some(Instant::now());
-            }
-        }
+        // Log listens left unfinished by a previous run before anything overwrites the journal
+        self.recover_journal().await;
 
-        // Set up message stream for D-Bus signals
+        // Set up message stream for D-Bus signals before looking for players,
+        // so players appearing in between aren't missed
         let rule = MatchRule::builder()
             .msg_type(MessageType::Signal)
             .build();
@@ -112,19 +224,66 @@ impl MprisMonitor {
 
         // Spawn signal handler
         let connection = self.connection.clone();
-        let player_config = self.player_config.clone();
         let tx_clone = tx.clone();
 
         tokio::spawn(async move {
             while let Some(msg) = stream.next().await {
                 if let Ok(msg) = msg {
-                    Self::handle_dbus_message(&msg, &connection, &player_config, &tx_clone).await;
+                    Self::handle_dbus_message(&msg, &connection, &tx_clone).await;
                 }
             }
         });
 
+        // Discover existing players
+        self.discover_players().await?;
+
+        // Check if we found any players
+        if self.listens.read().await.players().is_empty() {
+            info!("No players found, starting idle timer...");
+            *self.idle_since.write().await = Some(self.clock.now());
+        }
+
+        // Forward logind sleep/shutdown announcements
+        if let Some(logind) = &self.logind {
+            self.take_inhibitor().await;
+
+            match logind.power_signals().await {
+                Ok(signals) => {
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        let mut signals = Box::pin(signals);
+                        while let Some(signal) = signals.next().await {
+                            if tx.send(MprisEvent::Power(signal)).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                Err(e) => debug!("Failed to watch logind signals: {}", e),
+            }
+        }
+
+        // Reload the configuration when its file changes or on SIGHUP
+        if let Some(path) = &self.config_path {
+            match reload::requests(path) {
+                Ok(requests) => {
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        let mut requests = Box::pin(requests);
+                        while let Some(trigger) = requests.next().await {
+                            if tx.send(MprisEvent::ReloadConfig(trigger)).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                Err(e) => warn!("Configuration won't be reloaded while running: {}", e),
+            }
+        }
+
         // Main event loop
-        let idle_timeout = Duration::from_secs(self.tracking_config.idle_timeout_seconds);
+        let mut last_position_polls = HashMap::new();
+        let mut last_volume_sample = Instant::now();
 
         loop {
             // Check if we should stop
@@ -133,9 +292,12 @@ impl MprisMonitor {
             }
 
             // Check idle timeout
-            if self.tracking_config.idle_timeout_seconds > 0 {
-                if let Some(idle_start) = *self.idle_since.read().await {
-                    if idle_start.elapsed() >= idle_timeout {
+            let idle_timeout_seconds = self.settings().await.config.tracking.idle_timeout_seconds;
+            if idle_timeout_seconds > 0 {
+                let idle_since = *self.idle_since.read().await;
+                if let Some(idle_start) = idle_since {
+                    let idle = self.clock.now().duration_since(idle_start);
+                    if idle >= Duration::from_secs(idle_timeout_seconds) {
                         info!("Idle timeout reached, shutting down...");
                         break;
                     }
@@ -146,11 +308,32 @@ impl MprisMonitor {
             tokio::select! {
                 Some(event) = rx.recv() => {
                     self.handle_event(event).await;
+                    self.checkpoint().await;
                 }
                 _ = tokio::time::sleep(Duration::from_secs(1)) => {
                     // Periodic check
                 }
             }
+
+            self.update_session().await;
+
+            let pause_expired = self.listens.read().await.pause_expired();
+            if pause_expired {
+                self.resume_tracking().await;
+            }
+            self.end_private_sessions(|sessions| sessions.expire(Local::now()))
+                .await;
+            self.mark_private_listens().await;
+
+            // Sample playback positions on a low-frequency timer
+            if self.poll_positions(&mut last_position_polls).await {
+                self.checkpoint().await;
+            }
+
+            if last_volume_sample.elapsed() >= VOLUME_SAMPLE_INTERVAL {
+                last_volume_sample = Instant::now();
+                self.sample_volumes().await;
+            }
         }
 
         // Log any in-progress plays before exiting
@@ -159,6 +342,196 @@ impl MprisMonitor {
         Ok(())
     }
 
+    /// Export the tracker D-Bus service on the session bus.
+    ///
+    /// # Errors
+    ///
+    /// Fails if another tracker is already running.
+    pub async fn serve(self: &Arc<Self>) -> Result<()> {
+        service::serve(&self.connection, Arc::clone(self)).await?;
+        info!("Serving {} on the session bus", service::SERVICE_NAME);
+        Ok(())
+    }
+
+    /// Current state of every tracked player, by player ID
+    pub(crate) async fn player_statuses(&self) -> Vec<PlayerStatus> {
+        let mut statuses: Vec<PlayerStatus> = self
+            .listens
+            .read()
+            .await
+            .players()
+            .values()
+            .map(PlayerStatus::from_state)
+            .collect();
+        statuses.sort_by(|a, b| a.player.cmp(&b.player));
+        statuses
+    }
+
+    /// Answer a JSON stats query from the database
+    pub(super) async fn answer_query(&self, request: &str) -> Result<String> {
+        self.db.answer_query(request).await
+    }
+
+    /// Number of plays logged since start
+    pub(super) fn plays_logged(&self) -> u32 {
+        self.plays_logged.load(Ordering::SeqCst)
+    }
+
+    /// Time since the monitor was created
+    pub(super) fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Configuration file watched for changes
+    pub(super) fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Check if tracking is paused
+    pub(super) async fn is_paused(&self) -> bool {
+        self.listens.read().await.is_paused()
+    }
+
+    /// Stop logging listens, for `duration` or until resumed.
+    ///
+    /// What was heard so far is logged first.
+    pub(super) async fn pause_tracking(&self, duration: Option<Duration>) {
+        let seconds = duration.map(|duration| duration.as_secs());
+        let ended = self.apply(ListenEvent::TrackingPaused { seconds }).await;
+        self.finish_listens(ended).await;
+        self.checkpoint().await;
+    }
+
+    /// Resume logging listens; playback during the pause is not counted
+    pub(super) async fn resume_tracking(&self) {
+        if !self.is_paused().await {
+            return;
+        }
+        self.apply(ListenEvent::TrackingResumed).await;
+        self.checkpoint().await;
+    }
+
+    /// Drop the listen in progress on `player` (empty = every player).
+    ///
+    /// Returns how many listens were discarded.
+    pub(super) async fn discard_current_play(&self, player: &str) -> u32 {
+        let event = ListenEvent::Discarded {
+            player: player.to_string(),
+        };
+        let discarded = {
+            let mut listens = self.listens.write().await;
+            if let Some(recorder) = &self.recorder {
+                recorder.record(&event);
+            }
+            listens.discard(player)
+        };
+
+        self.checkpoint().await;
+        discarded
+    }
+
+    /// Record `event` and apply it to the listens, returning the listens it ended
+    async fn apply(&self, event: ListenEvent) -> Vec<EndedListen> {
+        let settings = self.settings().await;
+        let sessions = self.private_sessions.read().await;
+        let mut listens = self.listens.write().await;
+
+        // Recorded under the lock so the log keeps the order events were applied in
+        if let Some(recorder) = &self.recorder {
+            if listens.event_is_private(&event, &settings, &sessions) {
+                recorder.record(&event.redacted());
+            } else {
+                recorder.record(&event);
+            }
+        }
+
+        listens.apply(&event, &settings, &sessions)
+    }
+
+    /// Switch private mode on
+    pub(super) async fn start_private(&self, session: PrivateSession) -> Result<()> {
+        let player = session.player.clone();
+        let mut sessions = self.private_sessions.write().await;
+        sessions.start(session)?;
+        Self::save_private_sessions(&sessions);
+        drop(sessions);
+
+        info!(
+            "Private mode on for {}",
+            player.as_deref().unwrap_or("all players")
+        );
+
+        // Listens in progress are now private and must not be journaled
+        self.mark_private_listens().await;
+        self.checkpoint().await;
+        Ok(())
+    }
+
+    /// Switch private mode off for `player`, or everywhere if None.
+    ///
+    /// Returns how many sessions ended.
+    pub(super) async fn stop_private(&self, player: Option<&str>) -> u32 {
+        let ended = self
+            .end_private_sessions(|sessions| sessions.stop(player))
+            .await;
+        u32::try_from(ended).unwrap_or(u32::MAX)
+    }
+
+    /// Save runtime private sessions so they outlive the tracker
+    fn save_private_sessions(sessions: &PrivateSessions) {
+        if let Err(e) = sessions.save() {
+            error!("Failed to save private sessions: {}", e);
+        }
+    }
+
+    /// Private sessions currently switched on
+    pub(super) async fn private_sessions(&self) -> Vec<PrivateSession> {
+        self.private_sessions.read().await.sessions().to_vec()
+    }
+
+    /// Check if a scheduled private window is open now
+    pub(super) async fn scheduled_private(&self) -> bool {
+        self.settings().await.private.covers_time(Local::now())
+    }
+
+    /// End the private sessions picked by `end`, keeping listens that
+    /// overlapped them private. Returns how many sessions ended.
+    async fn end_private_sessions(
+        &self,
+        end: impl FnOnce(&mut PrivateSessions) -> Vec<PrivateSession>,
+    ) -> usize {
+        let mut sessions = self.private_sessions.write().await;
+        let ended = end(&mut sessions);
+        if !ended.is_empty() {
+            Self::save_private_sessions(&sessions);
+        }
+        drop(sessions);
+        if ended.is_empty() {
+            return 0;
+        }
+
+        for state in self.listens.write().await.players_mut().values_mut() {
+            let player = state.player_name.as_deref().unwrap_or_default();
+            if state.has_started() && ended.iter().any(|session| session.applies_to(player)) {
+                state.private = true;
+            }
+        }
+        for session in &ended {
+            info!(
+                "Private mode off for {}",
+                session.player.as_deref().unwrap_or("all players")
+            );
+        }
+        ended.len()
+    }
+
+    /// Flag listens private mode covers now, so status and logs hide them
+    async fn mark_private_listens(&self) {
+        let settings = self.settings().await;
+        let sessions = self.private_sessions.read().await;
+        self.listens.write().await.mark_private(&settings, &sessions);
+    }
+
     /// Stop the monitor.
     ///
     /// This method is synchronous as it only sets an atomic flag.
@@ -166,14 +539,32 @@ impl MprisMonitor {
         self.running.store(false, Ordering::SeqCst);
     }
 
+    /// Start a new listening session once playback resumes after a long gap
+    async fn update_session(&self) {
+        let playing = self
+            .listens
+            .read()
+            .await
+            .players()
+            .values()
+            .any(|state| state.is_playing);
+
+        if self.session.write().await.update(playing, self.clock.now()) {
+            debug!("Started new listening session");
+        }
+    }
+
     /// Discover existing MPRIS players
     async fn discover_players(&self) -> Result<()> {
         let dbus = DBusProxy::new(&self.connection).await?;
         let names = dbus.list_names().await?;
+        let settings = self.settings().await;
 
         for name in names {
             let name_str = name.as_str();
-            if name_str.starts_with(MPRIS_PREFIX) && self.should_track_player(name_str) {
+            if name_str.starts_with(MPRIS_PREFIX)
+                && Self::should_track_player(&settings.config.players, name_str)
+            {
                 self.add_player(name_str).await?;
             }
         }
@@ -182,12 +573,11 @@ impl MprisMonitor {
     }
 
     /// Check if a player should be tracked
-    fn should_track_player(&self, name: &str) -> bool {
+    fn should_track_player(player_config: &PlayerConfig, name: &str) -> bool {
         let player_id = name.strip_prefix(MPRIS_PREFIX).unwrap_or(name);
 
         // Check blacklist first
-        if self
-            .player_config
+        if player_config
             .blacklist
             .iter()
             .any(|p| player_id.contains(p))
@@ -196,12 +586,12 @@ impl MprisMonitor {
         }
 
         // If whitelist is empty, track all
-        if self.player_config.whitelist.is_empty() {
+        if player_config.whitelist.is_empty() {
             return true;
         }
 
         // Check whitelist
-        self.player_config
+        player_config
             .whitelist
             .iter()
             .any(|p| player_id.contains(p))
@@ -217,45 +607,71 @@ impl MprisMonitor {
         let unique_name = dbus.get_name_owner(bus_name).await?;
         let unique_name_str = unique_name.as_str().to_string();
 
-        let mut players = self.tracked_players.write().await;
-
-        if players.contains_key(&unique_name_str) {
+        if self.listens.read().await.players().contains_key(&unique_name_str) {
             return Ok(());
         }
 
         info!("Adding player: {}", well_known_name);
 
-        let mut state = TrackState::new();
-        state.player_name = Some(
-            well_known_name
-                .strip_prefix(MPRIS_PREFIX)
-                .unwrap_or(well_known_name)
-                .to_string(),
-        );
-
-        // Get initial state
-        if let Ok(metadata) = self.get_player_metadata(well_known_name).await {
-            let track = parse_metadata(&metadata);
-            state.track = track.clone();
-            state.is_local = track.is_local_source(
-                &self.player_config.local_only_players,
-                state.player_name.as_deref(),
-            );
+        let player_id = well_known_name
+            .strip_prefix(MPRIS_PREFIX)
+            .unwrap_or(well_known_name)
+            .to_string();
+        let settings = self.settings().await;
+        let tracking = settings.tracking_for(Some(&player_id));
+        if !settings.config.players.overrides.is_empty() {
+            debug!("[{}] Tracking settings: {:?}", player_id, tracking);
         }
 
-        if let Ok(status) = self.get_playback_status(well_known_name).await {
-            if status == "Playing" {
-                state.start_playing();
-                info!(
-                    "[{}] Already playing: {} - {}",
-                    well_known_name,
-                    state.track.artist.as_deref().unwrap_or("Unknown"),
-                    state.track.title.as_deref().unwrap_or("Unknown")
-                );
-            }
-        }
+        // Get initial state; optional properties aren't implemented by every player
+        let snapshot = PlayerSnapshot {
+            pid: dbus
+                .get_connection_unix_process_id(unique_name.clone().into())
+                .await
+                .ok(),
+            track: self
+                .get_player_metadata(well_known_name)
+                .await
+                .ok()
+                .map(|metadata| parse_metadata(&metadata)),
+            playing: self
+                .get_playback_status(well_known_name)
+                .await
+                .is_ok_and(|status| status == "Playing"),
+            position_us: self.get_position(well_known_name).await.ok(),
+            loop_status: self
+                .get_player_property(well_known_name, "LoopStatus")
+                .await
+                .ok()
+                .and_then(|status| extract_string(&status)),
+            shuffle: self
+                .get_player_property(well_known_name, "Shuffle")
+                .await
+                .ok()
+                .and_then(|shuffle| extract(&shuffle)),
+            rate: self
+                .get_player_property(well_known_name, "Rate")
+                .await
+                .ok()
+                .and_then(|rate| extract(&rate)),
+            volume: if tracking.track_volume {
+                self.get_player_property(well_known_name, "Volume")
+                    .await
+                    .ok()
+                    .and_then(|volume| extract(&volume))
+            } else {
+                None
+            },
+            name: player_id,
+        };
 
-        players.insert(unique_name_str.clone(), state);
+        self.apply(ListenEvent::PlayerAdded {
+            player: unique_name_str.clone(),
+            snapshot,
+        })
+        .await;
+
+        self.sample_volume(&unique_name_str).await;
 
         // Store mapping from unique name to well-known name
         self.bus_name_map
@@ -283,19 +699,28 @@ impl MprisMonitor {
             return;
         };
 
-        let mut players = self.tracked_players.write().await;
+        let player_name = self
+            .listens
+            .read()
+            .await
+            .players()
+            .get(&unique_name)
+            .map(|state| state.player_name.clone());
 
-        if let Some(state) = players.remove(&unique_name) {
+        if let Some(player_name) = player_name {
             info!("Removing player: {}", well_known_name);
 
-            // Log final play if applicable
-            if state.is_playing
-                && state.should_log(
-                    self.tracking_config.min_play_seconds,
-                    self.tracking_config.min_play_percent,
-                )
-            {
-                self.log_play(&state).await;
+            // Log final play (or skip) if applicable
+            let ended = self
+                .apply(ListenEvent::PlayerRemoved {
+                    player: unique_name.clone(),
+                })
+                .await;
+            self.finish_listens(ended).await;
+
+            if let Some(player_name) = player_name {
+                self.end_private_sessions(|sessions| sessions.player_closed(&player_name))
+                    .await;
             }
         }
 
@@ -303,12 +728,12 @@ impl MprisMonitor {
         self.bus_name_map.write().await.remove(&unique_name);
 
         // Start idle timer if no players remain
-        if players.is_empty() {
+        if self.listens.read().await.players().is_empty() {
             info!(
                 "No players remaining, will exit in {}s if none appear...",
-                self.tracking_config.idle_timeout_seconds
+                self.settings().await.config.tracking.idle_timeout_seconds
             );
-            *self.idle_since.write().await = Some(Instant::now());
+            *self.idle_since.write().await = Some(self.clock.now());
         }
     }
 
@@ -316,7 +741,6 @@ impl MprisMonitor {
     async fn handle_dbus_message(
         msg: &zbus::Message,
         _connection: &Connection,
-        player_config: &PlayerConfig,
         tx: &mpsc::Sender<MprisEvent>,
     ) {
         let header = msg.header();
@@ -379,22 +803,27 @@ impl MprisMonitor {
                             }
                         }
 
+                        // Handle loop status, shuffle and rate changes
+                        if let Some(event) = Self::playback_options_event(&player, &changed) {
+                            let _ = tx.send(event).await;
+                        }
+
+                        // Handle volume change
+                        if let Some(volume) = changed.get("Volume").and_then(extract::<f64>) {
+                            let player = player.clone();
+                            let _ = tx.send(MprisEvent::VolumeChanged { player, volume }).await;
+                        }
+
                         // Handle metadata change
                         if let Some(metadata) = changed.get("Metadata") {
                             if let Ok(meta_map) =
                                 HashMap::<String, OwnedValue>::try_from(metadata.clone())
                             {
                                 let track = parse_metadata(&meta_map);
-                                let is_local = track.is_local_source(
-                                    &player_config.local_only_players,
-                                    Some(&player),
-                                );
-
                                 let _ = tx
                                     .send(MprisEvent::TrackChanged {
                                         player: player.clone(),
                                         track,
-                                        is_local,
                                     })
                                     .await;
                             }
@@ -422,201 +851,226 @@ impl MprisMonitor {
         }
     }
 
+    /// Event for a change to `LoopStatus`, `Shuffle` or `Rate`, if any of them changed
+    fn playback_options_event(
+        player: &str,
+        changed: &HashMap<String, OwnedValue>,
+    ) -> Option<MprisEvent> {
+        let loop_status = changed.get("LoopStatus").and_then(extract_string);
+        let shuffle = changed.get("Shuffle").and_then(extract::<bool>);
+        let rate = changed.get("Rate").and_then(extract::<f64>);
+        (loop_status.is_some() || shuffle.is_some() || rate.is_some()).then(|| {
+            MprisEvent::PlaybackOptionsChanged {
+                player: player.to_string(),
+                loop_status,
+                shuffle,
+                rate,
+            }
+        })
+    }
+
     /// Handle an MPRIS event
     async fn handle_event(&self, event: MprisEvent) {
-        match event {
+        let event = match event {
             MprisEvent::PlayerAppeared { player } => {
-                if self.should_track_player(&player) {
+                if Self::should_track_player(&self.settings().await.config.players, &player) {
                     if let Err(e) = self.add_player(&player).await {
                         error!("Failed to add player {}: {}", player, e);
                     }
                 }
+                return;
             }
 
             MprisEvent::PlayerDisappeared { player } => {
                 self.remove_player(&player).await;
+                return;
+            }
+
+            MprisEvent::Power(
+                PowerSignal::PrepareForSleep(true) | PowerSignal::PrepareForShutdown(true),
+            ) => {
+                info!("System going down, closing active plays...");
+                let ended = self.apply(ListenEvent::Suspended).await;
+                self.finish_listens(ended).await;
+                // Let the system proceed
+                *self.inhibitor.write().await = None;
+                return;
+            }
+
+            MprisEvent::Power(
+                PowerSignal::PrepareForSleep(false) | PowerSignal::PrepareForShutdown(false),
+            ) => {
+                info!("System resumed");
+                self.take_inhibitor().await;
+                self.resume_listens().await;
+                return;
             }
 
-            MprisEvent::TrackChanged {
+            MprisEvent::ReloadConfig(trigger) => {
+                self.reload_config(trigger).await;
+                return;
+            }
+
+            MprisEvent::TrackChanged { player, track } => {
+                ListenEvent::TrackChanged { player, track }
+            }
+            MprisEvent::Playing { player } => ListenEvent::Playing { player },
+            MprisEvent::Paused { player } => ListenEvent::Paused { player },
+            MprisEvent::Stopped { player } => ListenEvent::Stopped { player },
+            MprisEvent::Seeked {
                 player,
-                track,
-                is_local: _,
-            } => {
-                let display_name = self
-                    .bus_name_map
-                    .read()
-                    .await
-                    .get(&player)
-                    .cloned()
-                    .unwrap_or_else(|| player.clone());
-
-                // Recompute is_local using the well-known player name (not the D-Bus unique name)
-                let is_local = track.is_local_source(
-                    &self.player_config.local_only_players,
-                    Some(&display_name),
-                );
+                position_us,
+            } => ListenEvent::Seeked {
+                player,
+                position_us,
+            },
+            MprisEvent::PlaybackOptionsChanged {
+                player,
+                loop_status,
+                shuffle,
+                rate,
+            } => ListenEvent::OptionsChanged {
+                player,
+                loop_status,
+                shuffle,
+                rate,
+            },
+            MprisEvent::VolumeChanged { player, volume } => {
+                ListenEvent::VolumeChanged { player, volume }
+            }
+        };
 
-                // Capture state to log before modifying, avoiding race conditions
-                let state_to_log = {
-                    let mut players = self.tracked_players.write().await;
-
-                    let state_to_log = if let Some(state) = players.get(&player) {
-                        // Check if previous track qualifies for logging
-                        if state.is_playing
-                            && state.should_log(
-                                self.tracking_config.min_play_seconds,
-                                self.tracking_config.min_play_percent,
-                            )
-                            && (!self.tracking_config.local_only || state.is_local)
-                        {
-                            Some(state.clone())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
-
-                    // Update state for new track while still holding the lock
-                    if let Some(state) = players.get_mut(&player) {
-                        let old_title = state.track.title.clone();
-                        state.track = track.clone();
-                        state.is_local = is_local;
-
-                        // Reset seek tracking for new track
-                        state.seek_count = 0;
-                        state.intro_skipped = false;
-                        state.seek_forward_ms = 0;
-                        state.seek_backward_ms = 0;
-                        state.last_position_us = 0;
-
-                        if state.is_playing {
-                            state.start_playing();
-                        }
+        // New tracks, playback and volume changes are a chance to catch up on
+        // the sound server volume
+        let sample = match &event {
+            ListenEvent::TrackChanged { player, .. }
+            | ListenEvent::Playing { player }
+            | ListenEvent::VolumeChanged { player, .. } => Some(player.clone()),
+            _ => None,
+        };
 
-                        if track.title != old_title {
-                            let local_info = if !is_local && self.tracking_config.local_only {
-                                " (non-local, won't track)"
-                            } else {
-                                ""
-                            };
-                            info!(
-                                "[{}] Track changed: {} - {}{}",
-                                display_name,
-                                track.artist.as_deref().unwrap_or("Unknown"),
-                                track.title.as_deref().unwrap_or("Unknown"),
-                                local_info
-                            );
-                        }
-                    }
+        let ended = self.apply(event).await;
+        self.finish_listens(ended).await;
 
-                    state_to_log
-                };
+        if let Some(player) = sample {
+            self.sample_volume(&player).await;
+        }
+    }
 
-                // Log previous track after releasing lock
-                if let Some(state) = state_to_log {
-                    self.log_play(&state).await;
-                }
+    /// Reload the configuration file and apply it to attached players.
+    ///
+    /// An invalid file is reported and the current settings are kept.
+    async fn reload_config(&self, trigger: ReloadTrigger) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+
+        let loaded = Config::load_from(path).and_then(|config| {
+            config.validate()?;
+            Settings::new(config)
+        });
+        let settings = match loaded {
+            Ok(settings) => Arc::new(settings),
+            Err(e) => {
+                error!(
+                    "Failed to reload {} ({:?}), keeping current configuration: {}",
+                    path.display(),
+                    trigger,
+                    e
+                );
+                return;
             }
+        };
 
-            MprisEvent::Playing { player } => {
-                let mut players = self.tracked_players.write().await;
-                let display_name = self
-                    .bus_name_map
-                    .read()
-                    .await
-                    .get(&player)
-                    .cloned()
-                    .unwrap_or_else(|| player.clone());
-
-                if let Some(state) = players.get_mut(&player) {
-                    if !state.is_playing {
-                        state.start_playing();
-                        info!(
-                            "[{}] Playing: {} - {}",
-                            display_name,
-                            state.track.artist.as_deref().unwrap_or("Unknown"),
-                            state.track.title.as_deref().unwrap_or("Unknown")
-                        );
-                    }
-                }
+        let changes = self.settings().await.config.changes(&settings.config);
+        if changes.is_empty() {
+            debug!("Configuration unchanged ({:?})", trigger);
+            return;
+        }
+        info!("Reloading configuration ({:?})", trigger);
+        for change in &changes {
+            if change.starts_with("general.") || change.starts_with("database.") {
+                warn!("  {} (takes effect after restart)", change);
+            } else {
+                info!("  {}", change);
             }
+        }
 
-            MprisEvent::Paused { player } | MprisEvent::Stopped { player } => {
-                let display_name = self
-                    .bus_name_map
-                    .read()
-                    .await
-                    .get(&player)
-                    .cloned()
-                    .unwrap_or_else(|| player.clone());
-
-                // Capture state and update in one lock acquisition to avoid races
-                let state_to_log = {
-                    let mut players = self.tracked_players.write().await;
-                    let state_to_log = if let Some(state) = players.get(&player) {
-                        if state.is_playing
-                            && state.should_log(
-                                self.tracking_config.min_play_seconds,
-                                self.tracking_config.min_play_percent,
-                            )
-                            && (!self.tracking_config.local_only || state.is_local)
-                        {
-                            Some(state.clone())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
-
-                    // Update state while holding lock
-                    if let Some(state) = players.get_mut(&player) {
-                        state.stop_playing();
-                        info!("[{}] Paused", display_name);
-                    }
+        *self.settings.write().await = Arc::clone(&settings);
+        let config = &settings.config;
+        self.session
+            .write()
+            .await
+            .set_gap(Duration::from_secs(config.tracking.session_gap_seconds));
+
+        // Drop players the new filters exclude, finishing their listens as usual
+        let attached: Vec<String> = self.bus_name_map.read().await.values().cloned().collect();
+        for name in attached {
+            if !Self::should_track_player(&config.players, &name) {
+                info!("No longer tracking {}", name);
+                self.remove_player(&name).await;
+            }
+        }
 
-                    state_to_log
-                };
+        // Check the players that remain against the new local players
+        for state in self.listens.write().await.players_mut().values_mut() {
+            state.is_local = state
+                .track
+                .is_local_source(&config.players.local_only_players, state.player_name.as_deref());
+        }
 
-                // Log after releasing lock
-                if let Some(state) = state_to_log {
-                    self.log_play(&state).await;
-                }
+        // Pick up players the old filters excluded
+        if let Err(e) = self.discover_players().await {
+            error!("Failed to discover players after reload: {}", e);
+        }
+    }
+
+    /// Take the logind delay lock, if not already held
+    async fn take_inhibitor(&self) {
+        let Some(logind) = &self.logind else {
+            return;
+        };
+
+        if self.inhibitor.read().await.is_some() {
+            return;
+        }
+
+        // Don't hold the lock over the bus call; a lock taken meanwhile wins
+        // and this one is released on drop
+        match logind.inhibit().await {
+            Ok(fd) => {
+                self.inhibitor.write().await.get_or_insert(fd);
             }
+            Err(e) => debug!("Failed to take logind inhibitor lock: {}", e),
+        }
+    }
 
-            MprisEvent::Seeked {
-                player,
-                position_us,
-            } => {
-                if self.tracking_config.track_seeks {
-                    let display_name = self
-                        .bus_name_map
-                        .read()
-                        .await
-                        .get(&player)
-                        .cloned()
-                        .unwrap_or_else(|| player.clone());
-
-                    let mut players = self.tracked_players.write().await;
-                    if let Some(state) = players.get_mut(&player) {
-                        state.on_seeked(position_us);
-                        debug!(
-                            "[{}] Seeked to {}s (total seeks: {})",
-                            display_name,
-                            position_us / 1_000_000,
-                            state.seek_count
-                        );
-                    }
-                }
+    /// Start new listens for players still playing after resume
+    async fn resume_listens(&self) {
+        let names: Vec<String> = self.listens.read().await.players().keys().cloned().collect();
+
+        for player in names {
+            let Ok(status) = self.get_playback_status(&player).await else {
+                continue;
+            };
+            if status != "Playing" {
+                continue;
             }
+            let position_us = self.get_position(&player).await.ok();
+            self.apply(ListenEvent::Resumed {
+                player: player.clone(),
+                position_us,
+            })
+            .await;
+
+            self.sample_volume(&player).await;
         }
     }
 
-    /// Get player metadata via D-Bus.
+    /// Get a property of the player interface via D-Bus.
     ///
     /// Times out after 5 seconds to prevent hangs from misbehaving players.
-    async fn get_player_metadata(&self, name: &str) -> Result<HashMap<String, OwnedValue>> {
+    async fn get_player_property(&self, name: &str, property: &str) -> Result<OwnedValue> {
         use zbus::names::InterfaceName;
 
         const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
@@ -634,56 +1088,219 @@ impl MprisMonitor {
         let iface = InterfaceName::try_from(MPRIS_PLAYER_IFACE)
             .map_err(|e| crate::error::Error::InvalidMetadata(e.to_string()))?;
 
-        let metadata = tokio::time::timeout(DBUS_TIMEOUT, proxy.get(iface, "Metadata"))
+        let value = tokio::time::timeout(DBUS_TIMEOUT, proxy.get(iface, property))
             .await
-            .map_err(|_| crate::error::Error::other("D-Bus metadata fetch timed out"))??;
+            .map_err(|_| crate::error::Error::other(format!("D-Bus {property} fetch timed out")))??;
+
+        Ok(value)
+    }
+
+    /// Get player metadata via D-Bus.
+    async fn get_player_metadata(&self, name: &str) -> Result<HashMap<String, OwnedValue>> {
+        let metadata = self.get_player_property(name, "Metadata").await?;
 
         HashMap::<String, OwnedValue>::try_from(metadata)
             .map_err(|_| crate::error::Error::InvalidMetadata("Failed to parse metadata".into()))
     }
 
     /// Get playback status via D-Bus.
-    ///
-    /// Times out after 5 seconds to prevent hangs from misbehaving players.
     async fn get_playback_status(&self, name: &str) -> Result<String> {
-        use zbus::names::InterfaceName;
+        let status = self.get_player_property(name, "PlaybackStatus").await?;
 
-        const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+        extract_string(&status)
+            .ok_or_else(|| crate::error::Error::InvalidMetadata("Failed to get status".into()))
+    }
 
-        let proxy = tokio::time::timeout(DBUS_TIMEOUT, async {
-            zbus::fdo::PropertiesProxy::builder(&self.connection)
-                .destination(name)?
-                .path(MPRIS_PATH)?
-                .build()
-                .await
-        })
-        .await
-        .map_err(|_| crate::error::Error::other("D-Bus proxy build timed out"))??;
+    /// Get playback position in microseconds via D-Bus.
+    async fn get_position(&self, name: &str) -> Result<i64> {
+        let position = self.get_player_property(name, "Position").await?;
 
-        let iface = InterfaceName::try_from(MPRIS_PLAYER_IFACE)
-            .map_err(|e| crate::error::Error::InvalidMetadata(e.to_string()))?;
+        extract(&position)
+            .ok_or_else(|| crate::error::Error::InvalidMetadata("Failed to get position".into()))
+    }
+
+    /// Sample the position of playing players whose `position_poll_seconds`
+    /// has elapsed since `last_polls`.
+    ///
+    /// D-Bus calls are made without holding the player lock. Returns true if
+    /// any player was polled.
+    async fn poll_positions(&self, last_polls: &mut HashMap<String, Instant>) -> bool {
+        let settings = self.settings().await;
+        let playing: Vec<(String, Option<String>)> = {
+            let listens = self.listens.read().await;
+            let players = listens.players();
+            last_polls.retain(|name, _| players.contains_key(name));
+            let playing = players
+                .iter()
+                .filter(|(_, state)| state.is_playing)
+                .map(|(name, state)| (name.clone(), state.player_name.clone()))
+                .collect();
+            drop(listens);
+            playing
+        };
+
+        let mut polled = false;
+        for (player, player_name) in playing {
+            let tracking = settings.tracking_for(player_name.as_deref());
+            let interval = Duration::from_secs(tracking.position_poll_seconds);
+            let last_poll = last_polls.entry(player.clone()).or_insert_with(Instant::now);
+            if interval.is_zero() || last_poll.elapsed() < interval {
+                continue;
+            }
+            *last_poll = Instant::now();
+            polled = true;
+
+            let position_us = match self.get_position(&player).await {
+                Ok(position_us) => position_us,
+                Err(e) => {
+                    debug!("[{}] Failed to sample position: {}", player, e);
+                    continue;
+                }
+            };
+
+            let ended = self
+                .apply(ListenEvent::Position {
+                    player,
+                    position_us,
+                })
+                .await;
+            self.finish_listens(ended).await;
+        }
 
-        let status = tokio::time::timeout(DBUS_TIMEOUT, proxy.get(iface, "PlaybackStatus"))
+        polled
+    }
+
+    /// Sample the volume of every playing player
+    async fn sample_volumes(&self) {
+        let playing: Vec<String> = self
+            .listens
+            .read()
             .await
-            .map_err(|_| crate::error::Error::other("D-Bus status fetch timed out"))??;
+            .players()
+            .iter()
+            .filter(|(_, state)| state.is_playing)
+            .map(|(name, _)| name.clone())
+            .collect();
 
-        extract_string(&status)
-            .ok_or_else(|| crate::error::Error::InvalidMetadata("Failed to get status".into()))
+        for player in playing {
+            self.sample_volume(&player).await;
+        }
+    }
+
+    /// Read a player's stream and device volume and add it to the listen's averages.
+    ///
+    /// The sound server is queried without holding the player lock.
+    async fn sample_volume(&self, player: &str) {
+        let Some((pid, player_name)) = self
+            .listens
+            .read()
+            .await
+            .players()
+            .get(player)
+            .map(|state| (state.player_pid, state.player_name.clone()))
+        else {
+            return;
+        };
+        if !self.settings().await.tracking_for(player_name.as_deref()).track_volume {
+            return;
+        }
+
+        let app_name = player_name.map(|name| app_name_for_player(&name).to_string());
+        let levels = self
+            .volume
+            .levels(pid, app_name.as_deref())
+            .await
+            .unwrap_or_default();
+
+        self.apply(ListenEvent::VolumeSampled {
+            player: player.to_string(),
+            levels,
+        })
+        .await;
+    }
+
+    /// Log listens that ended, as plays or skips
+    async fn finish_listens(&self, ended: Vec<EndedListen>) {
+        for listen in ended {
+            self.finish_listen(listen).await;
+        }
     }
 
-    /// Log a play to the database
-    async fn log_play(&self, state: &TrackState) {
-        if state.track.title.is_none() {
+    /// Log a finished listen as a play, or as a skip if it fell short of the thresholds
+    async fn finish_listen(&self, listen: EndedListen) {
+        let tracking = self
+            .settings()
+            .await
+            .tracking_for(listen.state.player_name.as_deref());
+        if listen.kind(&tracking).is_none() {
             return;
         }
 
-        let context = if self.tracking_config.track_context {
+        let context = if tracking.track_context {
             ListeningContext::capture().await
         } else {
             ListeningContext::default()
         };
+        let session_id = self.session.write().await.session_id();
+
+        self.record_listen(listen, &context, &session_id).await;
+    }
+
+    /// Write a finished listen as a play or a skip, as the listens judge it
+    async fn record_listen(
+        &self,
+        mut listen: EndedListen,
+        context: &ListeningContext,
+        session_id: &str,
+    ) {
+        let (verdict, day_start) = {
+            let settings = self.settings().await;
+            let sessions = self.private_sessions.read().await;
+            let verdict = self
+                .listens
+                .write()
+                .await
+                .judge(&mut listen, &settings, &sessions, context);
+            // Midnight if the setting is invalid, which validation reports
+            (verdict, settings.config.general.day_start().unwrap_or_default())
+        };
+        let state = &listen.state;
 
-        let seek_info = if state.seek_count > 0 {
+        match verdict {
+            None => {}
+            Some(Verdict::PrivatePlay) => {
+                if let Err(e) = self.db.log_private_play(state, day_start).await {
+                    error!("Failed to count private play: {}", e);
+                }
+            }
+            Some(Verdict::Play) => {
+                self.write_play(state, context, session_id, listen.ended_at)
+                    .await;
+            }
+            Some(Verdict::Skip(reason)) => {
+                debug!(
+                    "Logging skip ({}): {} ({}s played)",
+                    reason.as_str(),
+                    describe(state),
+                    state.played_ms() / 1000
+                );
+
+                if let Err(e) = self.db.log_skip(state, reason, listen.ended_at).await {
+                    error!("Failed to log skip: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Write a play with its context to the database as part of a session
+    async fn write_play(
+        &self,
+        state: &TrackState,
+        context: &ListeningContext,
+        session_id: &str,
+        ended_at: DateTime<Local>,
+    ) {
+        let mut seek_info = if state.seek_count > 0 {
             let mut info = format!(", {} seeks", state.seek_count);
             if state.intro_skipped {
                 info.push_str(", intro skipped");
@@ -692,6 +1309,14 @@ impl MprisMonitor {
         } else {
             String::new()
         };
+        if state.pause_count > 0 {
+            let _ = write!(
+                seek_info,
+                ", {} pauses ({}s paused)",
+                state.pause_count,
+                state.paused_ms() / 1000
+            );
+        }
 
         info!(
             "Logging play: {} - {} ({}s played{})",
@@ -701,26 +1326,410 @@ impl MprisMonitor {
             seek_info
         );
 
-        if let Err(e) = self.db.log_play(state, &context).await {
+        if let Err(e) = self.db.log_play(state, context, session_id, ended_at).await {
             error!("Failed to log play: {}", e);
+            return;
+        }
+
+        self.plays_logged.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = self.emit_play_logged(state).await {
+            debug!("Failed to emit PlayLogged: {}", e);
         }
     }
 
-    /// Finalize and log any remaining plays
-    async fn finalize(&self) {
-        let players = self.tracked_players.read().await;
-
-        for (name, state) in players.iter() {
-            if state.is_playing
-                && state.should_log(
-                    self.tracking_config.min_play_seconds,
-                    self.tracking_config.min_play_percent,
-                )
-                && (!self.tracking_config.local_only || state.is_local)
-            {
-                info!("Logging final play for {}", name);
-                self.log_play(state).await;
+    /// Announce a logged play on the tracker D-Bus service
+    async fn emit_play_logged(&self, state: &TrackState) -> zbus::Result<()> {
+        let emitter = SignalEmitter::new(&self.connection, service::SERVICE_PATH)?;
+        TrackerService::play_logged(
+            &emitter,
+            state.player_name.as_deref().unwrap_or_default(),
+            state.track.artist.as_deref().unwrap_or_default(),
+            state.track.title.as_deref().unwrap_or_default(),
+            state.track.album.as_deref().unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// Save the in-progress listen of every player to the journal
+    async fn checkpoint(&self) {
+        // Listens heard while paused must not come back after a crash
+        if self.is_paused().await {
+            if let Err(e) = self.journal.clear() {
+                error!("Failed to clear play journal: {}", e);
             }
+            return;
+        }
+
+        let session_id = self.session.write().await.session_id();
+        let settings = self.settings().await;
+        let sessions = self.private_sessions.read().await;
+        let now = Local::now();
+        let checkpoints: Vec<ListenCheckpoint> = self
+            .listens
+            .read()
+            .await
+            .players()
+            .values()
+            // Private listens never touch the disk
+            .filter(|state| !listen_is_private(state, &settings.private, &sessions, now))
+            .filter_map(|state| ListenCheckpoint::capture(state, &session_id))
+            .collect();
+        drop(sessions);
+
+        if let Err(e) = self.journal.save(&checkpoints) {
+            error!("Failed to write play journal: {}", e);
+        }
+    }
+
+    /// Log listens recovered from the journal of a run that didn't shut down cleanly
+    async fn recover_journal(&self) {
+        let checkpoints = match self.journal.load() {
+            Ok(checkpoints) => checkpoints,
+            Err(e) => {
+                error!("Failed to read play journal: {}", e);
+                return;
+            }
+        };
+
+        for checkpoint in checkpoints {
+            let session_id = checkpoint.session_id.clone();
+            // The listen ended at the last checkpoint, not now
+            let ended_at = checkpoint.saved_at;
+            let state = checkpoint.into_state();
+            info!("Recovering unfinished listen from journal: {}", describe(&state));
+
+            // Desktop context at the time of the play is lost; keep the time-based part
+            let context = state
+                .start_timestamp
+                .map(ListeningContext::at_time)
+                .unwrap_or_default();
+            self.record_listen(EndedListen::recovered(state, ended_at), &context, &session_id)
+                .await;
+        }
+
+        if let Err(e) = self.journal.clear() {
+            error!("Failed to clear play journal: {}", e);
+        }
+    }
+
+    /// Finalize and log any remaining plays and skips
+    async fn finalize(&self) {
+        let ended = self.listens.read().await.finish();
+        for listen in ended {
+            info!(
+                "Finishing listen for {}",
+                listen.state.player_name.as_deref().unwrap_or("unknown")
+            );
+            self.finish_listen(listen).await;
+        }
+
+        // Everything in progress has been handled
+        if let Err(e) = self.journal.clear() {
+            error!("Failed to clear play journal: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_bus::{PrivateBus, StubLogind, StubPlayer, TestMonitor};
+
+    /// Settings that count a listen as a play after a second, without
+    /// desktop or sound server lookups
+    fn test_config() -> Config {
+        let mut config = Config::default();
+        config.tracking.min_play_seconds = 1;
+        config.tracking.local_only = false;
+        config.tracking.track_context = false;
+        config.tracking.track_volume = false;
+        config.tracking.position_poll_seconds = 0;
+        config.tracking.idle_timeout_seconds = 0;
+        config
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_suspend_closes_listens_and_resume_restarts_them() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (logind, _) = StubLogind::serve(&bus).await;
+        let _player = StubPlayer::playing("Song").serve(&bus, "stub").await;
+        let mut monitor = TestMonitor::start_with_logind(
+            &bus,
+            &test_config(),
+            Some(Logind::with_connection(bus.connect().await)),
+        )
+        .await;
+        monitor.wait_for_player("stub").await;
+
+        // Play for 1.5s, then sleep for 3s
+        monitor.clock.advance(Duration::from_millis(1500));
+        StubLogind::emit_sleep(&logind, true).await;
+        monitor.wait_for_event("suspended").await;
+        monitor.clock.advance(Duration::from_secs(3));
+        StubLogind::emit_sleep(&logind, false).await;
+        monitor.wait_for_event("resumed").await;
+
+        // Sleep again before the listen counts as a play
+        monitor.clock.advance(Duration::from_millis(300));
+        StubLogind::emit_sleep(&logind, true).await;
+        monitor.wait_for_event("suspended").await;
+        monitor.clock.advance(Duration::from_millis(300));
+        StubLogind::emit_sleep(&logind, false).await;
+        monitor.wait_for_event("resumed").await;
+
+        monitor.clock.advance(Duration::from_millis(1500));
+        monitor.stop().await;
+
+        // The listen cut off by the first sleep, then the one finished on exit;
+        // neither includes the time asleep
+        let played: Vec<i64> = monitor
+            .db
+            .query_rows("SELECT played_ms FROM plays ORDER BY id", |row| row.get(0))
+            .await;
+        assert_eq!(played, [1500, 1500]);
+
+        let skips: Vec<String> = monitor
+            .db
+            .query_rows("SELECT reason FROM skips", |row| row.get(0))
+            .await;
+        assert_eq!(skips, ["interrupted"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_scripted_session_logs_plays_and_skips() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let player = StubPlayer::playing("One").serve(&bus, "stub").await;
+        let mut monitor = TestMonitor::start(&bus, &test_config()).await;
+        monitor.wait_for_player("stub").await;
+
+        // Heard long enough to count, with a seek
+        monitor.clock.advance(Duration::from_millis(1200));
+        player.seek(30_000_000).await;
+        monitor.wait_for_event("seeked").await;
+        monitor.clock.advance(Duration::from_millis(300));
+        player.change_track("Two").await;
+        monitor.wait_for_event("track_changed").await;
+
+        // Paused most of the time, then stopped before it counts
+        monitor.clock.advance(Duration::from_millis(300));
+        player.pause().await;
+        monitor.wait_for_event("paused").await;
+        monitor.clock.advance(Duration::from_millis(1200));
+        player.play().await;
+        monitor.wait_for_event("playing").await;
+        monitor.clock.advance(Duration::from_millis(200));
+        player.stop().await;
+        monitor.wait_for_event("stopped").await;
+
+        // Logged when the player quits
+        player.change_track("Three").await;
+        monitor.wait_for_event("track_changed").await;
+        player.play().await;
+        monitor.wait_for_event("playing").await;
+        monitor.clock.advance(Duration::from_millis(1500));
+        player.close().await;
+        monitor.wait_for_plays(2).await;
+        monitor.stop().await;
+
+        assert_eq!(
+            monitor.plays().await,
+            [
+                ("stub".to_string(), "One".to_string()),
+                ("stub".to_string(), "Three".to_string())
+            ]
+        );
+        assert_eq!(
+            monitor.skips().await,
+            [("stub".to_string(), "Two".to_string(), "skipped".to_string())]
+        );
+        let seeks: Vec<Option<i32>> = monitor
+            .db
+            .query_rows("SELECT seek_count FROM plays ORDER BY id", |row| row.get(0))
+            .await;
+        assert_eq!(seeks, [Some(1), None]);
+        let played_ms: Vec<i64> = monitor
+            .db
+            .query_rows("SELECT played_ms FROM plays ORDER BY id", |row| row.get(0))
+            .await;
+        assert_eq!(played_ms, [1500, 1500]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_whitelist_and_blacklist_pick_players() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let mut config = test_config();
+        config.players.whitelist = vec!["allowed".to_string()];
+        config.players.blacklist = vec!["blocked".to_string()];
+
+        // One player present at startup, the others appearing later
+        let _blocked = StubPlayer::playing("Blocked").serve(&bus, "allowed_blocked").await;
+        let mut monitor = TestMonitor::start(&bus, &config).await;
+        let _other = StubPlayer::playing("Other").serve(&bus, "other").await;
+        let _allowed = StubPlayer::playing("Allowed").serve(&bus, "allowed").await;
+        monitor.wait_for_player("allowed").await;
+
+        let tracked: Vec<String> = monitor
+            .monitor
+            .player_statuses()
+            .await
+            .into_iter()
+            .map(|status| status.player)
+            .collect();
+        assert_eq!(tracked, ["allowed"]);
+
+        monitor.clock.advance(Duration::from_millis(1500));
+        monitor.stop().await;
+
+        assert_eq!(
+            monitor.plays().await,
+            [("allowed".to_string(), "Allowed".to_string())]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_restarted_player_is_tracked_under_its_new_bus_name() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let mut monitor = TestMonitor::start(&bus, &test_config()).await;
+
+        let player = StubPlayer::playing("One").serve(&bus, "stub").await;
+        monitor.wait_for_player("stub").await;
+        monitor.clock.advance(Duration::from_millis(1500));
+        player.close().await;
+        monitor.wait_for_plays(1).await;
+        assert!(monitor.monitor.player_statuses().await.is_empty());
+
+        // Same well-known name, new unique name
+        let _player = StubPlayer::playing("Two").serve(&bus, "stub").await;
+        monitor.wait_for_player("stub").await;
+        assert_eq!(monitor.monitor.player_statuses().await.len(), 1);
+        monitor.clock.advance(Duration::from_millis(1500));
+        monitor.stop().await;
+
+        assert_eq!(
+            monitor.plays().await,
+            [
+                ("stub".to_string(), "One".to_string()),
+                ("stub".to_string(), "Two".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_idle_timeout_waits_for_last_player_to_close() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let mut config = test_config();
+        config.tracking.idle_timeout_seconds = 1;
+        let mut monitor = TestMonitor::start(&bus, &config).await;
+
+        let player = StubPlayer::playing("Song").serve(&bus, "stub").await;
+        monitor.wait_for_player("stub").await;
+
+        // Well past the timeout, but a player is open; the pause and play are
+        // only handled once the monitor has checked the timeout in between
+        monitor.clock.advance(Duration::from_secs(5));
+        player.pause().await;
+        monitor.wait_for_event("paused").await;
+        player.play().await;
+        monitor.wait_for_event("playing").await;
+        assert!(!monitor.exited_within(Duration::ZERO).await);
+
+        player.close().await;
+        monitor.wait_for_event("player_removed").await;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !monitor.exited_within(Duration::from_millis(100)).await {
+            assert!(Instant::now() < deadline, "monitor never timed out");
+            monitor.clock.advance(Duration::from_secs(1));
         }
+        assert_eq!(
+            monitor.plays().await,
+            [("stub".to_string(), "Song".to_string())]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tracker_answers_stats_queries() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let player = StubPlayer::playing("One").serve(&bus, "stub").await;
+        let monitor = TestMonitor::start(&bus, &test_config()).await;
+        monitor.monitor.serve().await.unwrap();
+        monitor.wait_for_player("stub").await;
+        monitor.clock.advance(Duration::from_millis(1200));
+        player.close().await;
+        monitor.wait_for_plays(1).await;
+
+        // Asked the way a reader does while the tracker holds the database
+        let client = bus.connect().await;
+        let tracker = super::super::TrackerProxy::new(&client).await.unwrap();
+        let answer = tracker
+            .query(r#"{"query":{"query":"top_tracks","start":null,"end":null,"limit":10},"content":"all"}"#)
+            .await
+            .unwrap();
+        let answer: serde_json::Value = serde_json::from_str(&answer).unwrap();
+        assert_eq!(answer["answer"], "top_tracks");
+        assert_eq!(answer["value"][0]["title"], "One");
+        assert_eq!(answer["value"][0]["play_count"], 1);
+
+        assert!(tracker.query("not a query").await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_is_cancelled_when_caller_disconnects() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let monitor = TestMonitor::start(&bus, &test_config()).await;
+        monitor.monitor.serve().await.unwrap();
+
+        // Enough plays that ranking artists takes a while
+        monitor
+            .db
+            .query_rows(
+                "INSERT INTO plays (title, artist, played_ms)
+                 SELECT 'Track ' || (i % 5000), 'Artist ' || (i % 2000) || ', Guest ' || (i % 700), 1000
+                 FROM range(200000) t(i)",
+                |row| row.get::<_, i64>(0),
+            )
+            .await;
+        let top_artists =
+            r#"{"query":{"query":"top_artists","start":null,"end":null,"limit":10},"content":"all"}"#;
+        let started = Instant::now();
+        monitor.monitor.answer_query(top_artists).await.unwrap();
+        let full = started.elapsed();
+
+        // A reader gives up on its queries and goes away
+        let client = bus.connect().await;
+        let tracker = super::super::TrackerProxy::new(&client).await.unwrap();
+        let queries = futures::future::join_all((0..4).map(|_| tracker.query(top_artists)));
+        assert!(tokio::time::timeout(full / 10, queries).await.is_err());
+        let cancelled = Instant::now();
+        drop(tracker);
+        drop(client);
+
+        monitor.db.get_play_count().await.unwrap();
+        let took = cancelled.elapsed();
+        assert!(
+            took < full * 2,
+            "abandoned queries held up the next for {took:?} (a full query takes {full:?})"
+        );
     }
 }
@@ -0,0 +1,288 @@
+//! Recording and replaying listen events
+//!
+//! `track --record <file>` writes every [`ListenEvent`] the monitor applies
+//! to a JSON Lines log, one timestamped event per line. [`replay`] feeds such
+//! a log back through the listen state machine on a manual clock and returns
+//! the plays and skips it would have logged, so a log attached to a bug
+//! report can become a regression test.
+//!
+//! What was playing is left out of the log for private listens.
+
+use std::fs::File;
+use std::io::{BufRead, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::clock::{ManualClock, SharedClock};
+use crate::config::Config;
+use crate::context::ListeningContext;
+use crate::error::Result;
+use crate::private::PrivateSessions;
+use crate::track::TrackState;
+
+use super::listen::{EndedListen, ListenEvent, Listens, Settings, Verdict};
+
+/// One line of an event log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// Milliseconds since recording started
+    pub at_ms: u64,
+    /// Wall clock time of the event
+    pub time: DateTime<Local>,
+    pub event: ListenEvent,
+}
+
+/// Writes applied events to an event log
+#[derive(Debug)]
+pub struct Recorder {
+    file: Mutex<LineWriter<File>>,
+    clock: SharedClock,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Start a new log at `path`, replacing any existing file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be created.
+    pub fn create(path: &Path, clock: SharedClock) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self {
+            file: Mutex::new(LineWriter::new(file)),
+            started: clock.now(),
+            clock,
+        })
+    }
+
+    /// Append an event, timestamped now.
+    ///
+    /// A failed write is reported and the event left out; tracking carries on.
+    pub fn record(&self, event: &ListenEvent) {
+        let record = Record {
+            at_ms: u64::try_from(self.clock.now().duration_since(self.started).as_millis())
+                .unwrap_or(u64::MAX),
+            time: self.clock.wall(),
+            event: event.clone(),
+        };
+
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let written = serde_json::to_writer(&mut *file, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|()| file.write_all(b"\n"));
+        drop(file);
+        if let Err(e) = written {
+            error!("Failed to record listen event: {}", e);
+        }
+    }
+}
+
+/// A play or skip the replayed events logged
+#[derive(Debug, Clone)]
+pub struct Replayed {
+    pub verdict: Verdict,
+    pub state: TrackState,
+    pub ended_at: DateTime<Local>,
+}
+
+/// Read an event log, skipping blank lines
+///
+/// # Errors
+///
+/// Fails if the log can't be read or a line isn't a valid record.
+pub fn read_log(log: impl BufRead) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for line in log.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Replay an event log under `config`, returning what would have been logged.
+///
+/// Listens still in progress at the end of the log are finished as if the
+/// tracker exited. Runtime private sessions aren't recorded, and rules see
+/// only the time-based part of the listening context.
+///
+/// # Errors
+///
+/// Fails if the rules or patterns in `config` don't compile.
+pub fn replay(config: &Config, records: &[Record]) -> Result<Vec<Replayed>> {
+    let settings = Settings::new(config.clone())?;
+    let sessions = PrivateSessions::default();
+
+    let Some(first) = records.first() else {
+        return Ok(Vec::new());
+    };
+    let start_wall = chrono::Duration::from_std(Duration::from_millis(first.at_ms))
+        .ok()
+        .and_then(|offset| first.time.checked_sub_signed(offset))
+        .unwrap_or(first.time);
+    let clock = ManualClock::new(start_wall);
+    let mut listens = Listens::new(clock.clone());
+
+    let mut logged = Vec::new();
+    for record in records {
+        clock.set_elapsed(Duration::from_millis(record.at_ms));
+        let ended = listens.apply(&record.event, &settings, &sessions);
+        listens.mark_private(&settings, &sessions);
+        judge(&mut listens, ended, &settings, &sessions, &mut logged);
+    }
+    let ended = listens.finish();
+    judge(&mut listens, ended, &settings, &sessions, &mut logged);
+
+    Ok(logged)
+}
+
+/// Judge ended listens as the monitor would, collecting what gets logged
+fn judge(
+    listens: &mut Listens,
+    ended: Vec<EndedListen>,
+    settings: &Settings,
+    sessions: &PrivateSessions,
+    logged: &mut Vec<Replayed>,
+) {
+    for mut listen in ended {
+        let tracking = settings.tracking_for(listen.state.player_name.as_deref());
+        let context = match listen.state.start_timestamp {
+            Some(start) if tracking.track_context => ListeningContext::at_time(start),
+            _ => ListeningContext::default(),
+        };
+        if let Some(verdict) = listens.judge(&mut listen, settings, sessions, &context) {
+            logged.push(Replayed {
+                verdict,
+                state: listen.state,
+                ended_at: listen.ended_at,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::track::{SkipReason, Track};
+
+    use super::super::listen::PlayerSnapshot;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.tracking.min_play_seconds = 30;
+        config.tracking.local_only = false;
+        config.tracking.track_context = false;
+        config
+    }
+
+    #[test]
+    fn test_replay_log() {
+        // A song played through, then one skipped after 10 seconds, then one
+        // still playing when the tracker exits
+        let log = r#"
+{"at_ms":0,"time":"2026-03-01T20:00:00+01:00","event":{"type":"player_added","player":":1.7","snapshot":{"name":"amberol","pid":null,"track":{"title":"One","artist":"A","duration_us":60000000},"playing":true,"position_us":0,"loop_status":null,"shuffle":null,"rate":null,"volume":null}}}
+{"at_ms":20000,"time":"2026-03-01T20:00:20+01:00","event":{"type":"paused","player":":1.7"}}
+{"at_ms":80000,"time":"2026-03-01T20:01:20+01:00","event":{"type":"playing","player":":1.7"}}
+{"at_ms":120000,"time":"2026-03-01T20:02:00+01:00","event":{"type":"track_changed","player":":1.7","track":{"title":"Two","artist":"A","duration_us":200000000}}}
+{"at_ms":130000,"time":"2026-03-01T20:02:10+01:00","event":{"type":"track_changed","player":":1.7","track":{"title":"Three","artist":"A","duration_us":200000000}}}
+{"at_ms":135000,"time":"2026-03-01T20:02:15+01:00","event":{"type":"seeked","player":":1.7","position_us":100000000}}
+"#;
+        let records = read_log(log.as_bytes()).unwrap();
+        let logged = replay(&config(), &records).unwrap();
+
+        let summary: Vec<(&str, Verdict, i64)> = logged
+            .iter()
+            .map(|play| {
+                (
+                    play.state.track.title.as_deref().unwrap_or_default(),
+                    play.verdict,
+                    play.state.played_ms(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("One", Verdict::Play, 60_000),
+                ("Two", Verdict::Skip(SkipReason::Skipped), 10_000),
+                ("Three", Verdict::Skip(SkipReason::Interrupted), 5_000),
+            ]
+        );
+
+        let one = &logged[0].state;
+        assert_eq!(one.pause_count, 1);
+        assert_eq!(one.paused_ms(), 60_000);
+        let at = |time| DateTime::parse_from_rfc3339(time).unwrap();
+        assert_eq!(
+            one.start_timestamp.unwrap(),
+            at("2026-03-01T20:00:00+01:00")
+        );
+        assert_eq!(logged[0].ended_at, at("2026-03-01T20:02:00+01:00"));
+        assert_eq!(logged[2].state.seek_count, 1);
+    }
+
+    #[test]
+    fn test_recording_replays_to_same_plays() {
+        let path = std::env::temp_dir().join(format!("record-test-{}.jsonl", uuid::Uuid::new_v4()));
+        let clock = ManualClock::new(Local::now());
+        let recorder = Recorder::create(&path, clock.clone()).unwrap();
+
+        let song = |title: &str| Track {
+            title: Some(title.to_string()),
+            duration_us: Some(60_000_000),
+            ..Track::default()
+        };
+        let events = [
+            (
+                5,
+                ListenEvent::PlayerAdded {
+                    player: ":1.2".to_string(),
+                    snapshot: PlayerSnapshot {
+                        name: "stub".to_string(),
+                        track: Some(song("First")),
+                        playing: true,
+                        ..PlayerSnapshot::default()
+                    },
+                },
+            ),
+            (
+                50,
+                ListenEvent::TrackChanged {
+                    player: ":1.2".to_string(),
+                    track: song("Second"),
+                },
+            ),
+            (
+                90,
+                ListenEvent::PlayerRemoved {
+                    player: ":1.2".to_string(),
+                },
+            ),
+        ];
+        for (at_secs, event) in &events {
+            clock.set_elapsed(Duration::from_secs(*at_secs));
+            recorder.record(event);
+        }
+        drop(recorder);
+
+        let records = read_log(std::io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        let logged = replay(&config(), &records).unwrap();
+        let plays: Vec<(Option<&str>, i64)> = logged
+            .iter()
+            .map(|play| (play.state.track.title.as_deref(), play.state.played_ms()))
+            .collect();
+        assert_eq!(plays, [(Some("First"), 45_000), (Some("Second"), 40_000)]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
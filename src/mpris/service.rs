@@ -0,0 +1,398 @@
+//! Tracker D-Bus service
+//!
+//! The running tracker owns `io.github.tombleher.Niandra.Tracker` on the
+//! session bus. Its interface reports what each player is doing and lets the
+//! GUI and CLI pause tracking or throw away a listen in progress.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use zbus::fdo::{DBusProxy, RequestNameFlags, RequestNameReply};
+use zbus::message::Header;
+use zbus::names::UniqueName;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedValue, Type, Value};
+use zbus::Connection;
+
+use crate::error::{Error, Result};
+use crate::private::{PrivateSession, PrivateUntil};
+use crate::track::{Track, TrackState};
+
+use super::player::MprisMonitor;
+
+/// Well-known bus name owned by the tracker
+pub const SERVICE_NAME: &str = "io.github.tombleher.Niandra.Tracker";
+
+/// Object path of the tracker interface
+pub const SERVICE_PATH: &str = "/io/github/tombleher/Niandra/Tracker";
+
+/// What a tracked player is currently doing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+pub struct PlayerStatus {
+    /// Player ID (bus name without the MPRIS prefix)
+    pub player: String,
+    /// Whether the player is playing
+    pub playing: bool,
+    /// Track artist, empty if unknown
+    pub artist: String,
+    /// Track title, empty if unknown
+    pub title: String,
+    /// Track album, empty if unknown
+    pub album: String,
+    /// Content type (music, podcast, audiobook, video)
+    pub content_type: String,
+    /// Time played in the current listen, in milliseconds
+    pub played_ms: i64,
+}
+
+impl PlayerStatus {
+    /// Summarise a player's state; what a private listen is playing is left empty
+    #[must_use]
+    pub fn from_state(state: &TrackState) -> Self {
+        let track = if state.private {
+            &Track::default()
+        } else {
+            &state.track
+        };
+
+        Self {
+            player: state.player_name.clone().unwrap_or_default(),
+            playing: state.is_playing,
+            artist: track.artist.clone().unwrap_or_default(),
+            title: track.title.clone().unwrap_or_default(),
+            album: track.album.clone().unwrap_or_default(),
+            content_type: track.content_type.as_str().to_string(),
+            played_ms: state.played_ms(),
+        }
+    }
+}
+
+/// Private mode switched on at runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+pub struct PrivateStatus {
+    /// Player the session applies to, empty for every player
+    pub player: String,
+    /// Unix timestamp the session ends at, 0 if it has no set end
+    pub until: i64,
+    /// Whether the session ends when its player closes
+    pub until_closed: bool,
+}
+
+impl PrivateStatus {
+    /// Summarise a private session
+    #[must_use]
+    pub fn from_session(session: &PrivateSession) -> Self {
+        Self {
+            player: session.player.clone().unwrap_or_default(),
+            until: match session.until {
+                PrivateUntil::Time(until) => until.timestamp(),
+                PrivateUntil::SwitchedOff | PrivateUntil::PlayerCloses => 0,
+            },
+            until_closed: session.until == PrivateUntil::PlayerCloses,
+        }
+    }
+}
+
+/// D-Bus interface served by the running tracker
+pub struct TrackerService {
+    monitor: Arc<MprisMonitor>,
+}
+
+#[zbus::interface(name = "io.github.tombleher.Niandra.Tracker")]
+impl TrackerService {
+    /// Current state of every tracked player
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn players(&self) -> Vec<PlayerStatus> {
+        self.monitor.player_statuses().await
+    }
+
+    /// Plays logged since the tracker started
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn plays_logged(&self) -> u32 {
+        self.monitor.plays_logged()
+    }
+
+    /// Seconds since the tracker started
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn uptime(&self) -> u64 {
+        self.monitor.uptime().as_secs()
+    }
+
+    /// Configuration file the tracker reloads, empty if none
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn config_path(&self) -> String {
+        self.monitor
+            .config_path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Whether tracking is paused
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn paused(&self) -> bool {
+        self.monitor.is_paused().await
+    }
+
+    /// Stop logging listens for `seconds` (0 = until `ResumeTracking`).
+    ///
+    /// Listens in progress are logged up to this point first.
+    async fn pause_tracking(&self, seconds: u64) {
+        let duration = (seconds > 0).then(|| Duration::from_secs(seconds));
+        self.monitor.pause_tracking(duration).await;
+    }
+
+    /// Resume logging listens
+    async fn resume_tracking(&self) {
+        self.monitor.resume_tracking().await;
+    }
+
+    /// Drop the listen in progress on `player` (empty = every player).
+    ///
+    /// Returns how many listens were discarded.
+    async fn discard_current_play(&self, player: &str) -> u32 {
+        self.monitor.discard_current_play(player).await
+    }
+
+    /// Private sessions switched on at runtime
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn private_sessions(&self) -> Vec<PrivateStatus> {
+        self.monitor
+            .private_sessions()
+            .await
+            .iter()
+            .map(PrivateStatus::from_session)
+            .collect()
+    }
+
+    /// Whether a private window from the configuration is open
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn scheduled_private(&self) -> bool {
+        self.monitor.scheduled_private().await
+    }
+
+    /// Stop recording listens on `player` (empty = every player) for
+    /// `seconds` (0 = until `StopPrivate`), or until the player closes.
+    ///
+    /// Only an anonymous count of private plays is kept.
+    async fn start_private(
+        &self,
+        player: &str,
+        seconds: u64,
+        until_closed: bool,
+    ) -> zbus::fdo::Result<()> {
+        let session = PrivateSession::requested(player, seconds, until_closed)
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+        self.monitor
+            .start_private(session)
+            .await
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))
+    }
+
+    /// Switch private mode off for `player` (empty = every session).
+    ///
+    /// Returns how many sessions ended.
+    async fn stop_private(&self, player: &str) -> u32 {
+        let player = (!player.is_empty()).then_some(player);
+        self.monitor.stop_private(player).await
+    }
+
+    /// Answer a stats query for a process that can't open the database while
+    /// the tracker has it. Request and answer are JSON.
+    ///
+    /// The query is cancelled if the caller disconnects before it's answered.
+    async fn query(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        request: &str,
+    ) -> zbus::fdo::Result<String> {
+        let answer = self.monitor.answer_query(request);
+        let answer = match header.sender() {
+            Some(caller) => tokio::select! {
+                answer = answer => answer,
+                () = caller_left(connection, caller) => Err(Error::other("Caller disconnected")),
+            },
+            None => answer.await,
+        };
+        answer.map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// A play was written to the database
+    #[zbus(signal)]
+    pub async fn play_logged(
+        emitter: &SignalEmitter<'_>,
+        player: &str,
+        artist: &str,
+        title: &str,
+        album: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Client for the running tracker
+#[zbus::proxy(
+    interface = "io.github.tombleher.Niandra.Tracker",
+    default_service = "io.github.tombleher.Niandra.Tracker",
+    default_path = "/io/github/tombleher/Niandra/Tracker",
+    gen_blocking = false
+)]
+pub trait Tracker {
+    /// Current state of every tracked player
+    #[zbus(property)]
+    fn players(&self) -> zbus::Result<Vec<PlayerStatus>>;
+
+    /// Plays logged since the tracker started
+    #[zbus(property)]
+    fn plays_logged(&self) -> zbus::Result<u32>;
+
+    /// Seconds since the tracker started
+    #[zbus(property)]
+    fn uptime(&self) -> zbus::Result<u64>;
+
+    /// Configuration file the tracker reloads
+    #[zbus(property)]
+    fn config_path(&self) -> zbus::Result<String>;
+
+    /// Whether tracking is paused
+    #[zbus(property)]
+    fn paused(&self) -> zbus::Result<bool>;
+
+    /// Stop logging listens for `seconds` (0 = until resumed)
+    fn pause_tracking(&self, seconds: u64) -> zbus::Result<()>;
+
+    /// Resume logging listens
+    fn resume_tracking(&self) -> zbus::Result<()>;
+
+    /// Drop the listen in progress on `player` (empty = every player)
+    fn discard_current_play(&self, player: &str) -> zbus::Result<u32>;
+
+    /// Private sessions switched on at runtime
+    #[zbus(property)]
+    fn private_sessions(&self) -> zbus::Result<Vec<PrivateStatus>>;
+
+    /// Whether a private window from the configuration is open
+    #[zbus(property)]
+    fn scheduled_private(&self) -> zbus::Result<bool>;
+
+    /// Stop recording listens on `player` (empty = every player) for
+    /// `seconds` (0 = until stopped), or until the player closes
+    fn start_private(&self, player: &str, seconds: u64, until_closed: bool) -> zbus::Result<()>;
+
+    /// Switch private mode off for `player` (empty = every session)
+    fn stop_private(&self, player: &str) -> zbus::Result<u32>;
+
+    /// Answer a JSON stats query
+    fn query(&self, request: &str) -> zbus::Result<String>;
+
+    /// A play was written to the database
+    #[zbus(signal)]
+    fn play_logged(&self, player: &str, artist: &str, title: &str, album: &str)
+        -> zbus::Result<()>;
+}
+
+/// Export the tracker interface and claim the service name.
+///
+/// Fails if another tracker already owns the name.
+pub(super) async fn serve(connection: &Connection, monitor: Arc<MprisMonitor>) -> Result<()> {
+    connection
+        .object_server()
+        .at(SERVICE_PATH, TrackerService { monitor })
+        .await?;
+
+    let reply = connection
+        .request_name_with_flags(SERVICE_NAME, RequestNameFlags::DoNotQueue.into())
+        .await?;
+    if reply == RequestNameReply::Exists {
+        return Err(Error::other(format!(
+            "Another tracker is already running ({SERVICE_NAME} is taken)"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check if a tracker owns the service name on the session bus
+pub async fn is_tracker_running() -> bool {
+    let Ok(connection) = Connection::session().await else {
+        return false;
+    };
+    tracker_owns_name(&connection).await
+}
+
+/// Send a JSON stats query to the running tracker.
+///
+/// Returns `None` if no tracker is running.
+///
+/// # Errors
+///
+/// Fails if the tracker can't answer the query.
+pub async fn query_tracker(request: &str) -> Result<Option<String>> {
+    let Ok(connection) = Connection::session().await else {
+        return Ok(None);
+    };
+    if !tracker_owns_name(&connection).await {
+        return Ok(None);
+    }
+    let tracker = TrackerProxy::new(&connection).await?;
+    Ok(Some(tracker.query(request).await?))
+}
+
+/// Resolves once `caller` disconnects from the bus
+async fn caller_left(connection: &Connection, caller: &UniqueName<'_>) {
+    let Ok(dbus) = DBusProxy::new(connection).await else {
+        return std::future::pending().await;
+    };
+    let Ok(mut changes) = dbus
+        .receive_name_owner_changed_with_args(&[(0, caller.as_str())])
+        .await
+    else {
+        return std::future::pending().await;
+    };
+
+    // It may have gone before we started listening
+    if !dbus
+        .name_has_owner(caller.to_owned().into())
+        .await
+        .unwrap_or(true)
+    {
+        return;
+    }
+    while let Some(change) = changes.next().await {
+        if change.args().is_ok_and(|args| args.new_owner().is_none()) {
+            return;
+        }
+    }
+    std::future::pending::<()>().await;
+}
+
+async fn tracker_owns_name(connection: &Connection) -> bool {
+    let Ok(dbus) = DBusProxy::new(connection).await else {
+        return false;
+    };
+    let Ok(name) = SERVICE_NAME.try_into() else {
+        return false;
+    };
+    dbus.name_has_owner(name).await.unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_status_from_state() {
+        let mut state = TrackState::new();
+        state.player_name = Some("spotify".to_string());
+        state.track.title = Some("So What".to_string());
+        state.track.artist = Some("Miles Davis".to_string());
+
+        let status = PlayerStatus::from_state(&state);
+        assert_eq!(status.player, "spotify");
+        assert!(!status.playing);
+        assert_eq!(status.artist, "Miles Davis");
+        assert_eq!(status.album, "");
+        assert_eq!(status.content_type, "music");
+        assert_eq!(status.played_ms, 0);
+    }
+}
@@ -0,0 +1,413 @@
+//! Private listening
+//!
+//! While private mode covers a player, its listens aren't recorded; only an
+//! anonymous per-day count is kept so gaps in the history are explainable.
+//! Private mode comes from the configuration (players that are always
+//! private, recurring time windows) or is switched on at runtime for a set
+//! time, until a player closes, or until switched off. Runtime sessions are
+//! saved in the data directory so they outlive the tracker, and can be
+//! switched on before the tracker starts.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeDelta, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{PrivateConfig, PrivateWindowConfig};
+use crate::error::{Error, Result};
+
+/// Runtime private sessions file name inside the data directory
+const SESSIONS_FILE: &str = "private.json";
+
+/// Recurring private time window
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Window {
+    /// Days the window starts on (empty = every day)
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    /// End time; before `start` when the window runs past midnight
+    end: NaiveTime,
+}
+
+impl Window {
+    fn parse(config: &PrivateWindowConfig) -> Result<Self> {
+        let days = config
+            .days
+            .iter()
+            .map(|day| {
+                day.parse::<Weekday>()
+                    .map_err(|_| Error::config(format!("Unknown day {day:?} in private schedule")))
+            })
+            .collect::<Result<_>>()?;
+        let start = parse_time(&config.start)?;
+        let end = parse_time(&config.end)?;
+        if start == end {
+            return Err(Error::config(format!(
+                "Private window {}-{} is empty",
+                config.start, config.end
+            )));
+        }
+
+        Ok(Self { days, start, end })
+    }
+
+    /// Check if the window covers a point in time
+    fn covers(&self, at: DateTime<Local>) -> bool {
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let (day, time) = (at.weekday(), at.time());
+
+        if self.start < self.end {
+            starts_on(day) && time >= self.start && time < self.end
+        } else {
+            // Runs past midnight: the evening part, or the morning after a start day
+            (starts_on(day) && time >= self.start) || (starts_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+        Error::config(format!(
+            "Invalid time {time:?} in private schedule, use HH:MM"
+        ))
+    })
+}
+
+/// Private players and time windows from the configuration
+#[derive(Debug, Clone, Default)]
+pub struct PrivateSchedule {
+    players: Vec<String>,
+    windows: Vec<Window>,
+}
+
+impl PrivateSchedule {
+    /// Build the schedule from the `[private]` configuration
+    pub fn new(config: &PrivateConfig) -> Result<Self> {
+        Ok(Self {
+            players: config.players.clone(),
+            windows: config
+                .schedule
+                .iter()
+                .map(Window::parse)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Check if a scheduled window covers a point in time
+    #[must_use]
+    pub fn covers_time(&self, at: DateTime<Local>) -> bool {
+        self.windows.iter().any(|window| window.covers(at))
+    }
+
+    /// Check if a listen on `player_id` at `at` is private
+    #[must_use]
+    pub fn covers(&self, player_id: &str, at: DateTime<Local>) -> bool {
+        self.players
+            .iter()
+            .any(|player| player_id.contains(player.as_str()))
+            || self.covers_time(at)
+    }
+}
+
+/// When a private session switched on at runtime ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrivateUntil {
+    /// Until switched off
+    SwitchedOff,
+    /// At a set time
+    Time(DateTime<Local>),
+    /// When the session's player closes
+    PlayerCloses,
+}
+
+/// Private mode switched on at runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateSession {
+    /// Player the session applies to, matched like `whitelist` (None = every player)
+    pub player: Option<String>,
+    /// When the session ends
+    pub until: PrivateUntil,
+}
+
+impl PrivateSession {
+    /// Build a session from a request: an empty `player` means every player,
+    /// and `seconds` of 0 means until switched off
+    pub fn requested(player: &str, seconds: u64, until_closed: bool) -> Result<Self> {
+        let until = if until_closed {
+            PrivateUntil::PlayerCloses
+        } else if seconds > 0 {
+            let seconds = i64::try_from(seconds)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .ok_or_else(|| Error::config("Private mode duration too long"))?;
+            PrivateUntil::Time(Local::now() + seconds)
+        } else {
+            PrivateUntil::SwitchedOff
+        };
+
+        Ok(Self {
+            player: (!player.is_empty()).then(|| player.to_string()),
+            until,
+        })
+    }
+
+    /// Check if the session applies to a player
+    #[must_use]
+    pub fn applies_to(&self, player_id: &str) -> bool {
+        self.player
+            .as_deref()
+            .is_none_or(|player| player_id.contains(player))
+    }
+}
+
+/// Private sessions currently switched on
+#[derive(Debug, Default)]
+pub struct PrivateSessions {
+    /// File the sessions are saved to (None = kept in memory only)
+    path: Option<PathBuf>,
+    sessions: Vec<PrivateSession>,
+}
+
+impl PrivateSessions {
+    /// Load the sessions saved in `data_dir`, dropping those whose time is up
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(SESSIONS_FILE);
+        let sessions = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut sessions = Self {
+            path: Some(path),
+            sessions,
+        };
+        if !sessions.expire(Local::now()).is_empty() {
+            sessions.save()?;
+        }
+        Ok(sessions)
+    }
+
+    /// Write the sessions to the file they were loaded from.
+    ///
+    /// Written to a temporary file and renamed, like the play journal.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.sessions.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(&self.sessions)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Switch a session on, replacing any other session for the same player.
+    ///
+    /// Sessions ending when their player closes need a player.
+    pub fn start(&mut self, session: PrivateSession) -> Result<()> {
+        if session.until == PrivateUntil::PlayerCloses && session.player.is_none() {
+            return Err(Error::config(
+                "Private mode until a player closes needs a player",
+            ));
+        }
+        self.sessions.retain(|other| other.player != session.player);
+        self.sessions.push(session);
+        Ok(())
+    }
+
+    /// Switch off the session for `player`, or every session if None
+    pub fn stop(&mut self, player: Option<&str>) -> Vec<PrivateSession> {
+        self.sessions
+            .extract_if(.., |session| {
+                player.is_none() || session.player.as_deref() == player
+            })
+            .collect()
+    }
+
+    /// Remove sessions whose time is up
+    pub fn expire(&mut self, now: DateTime<Local>) -> Vec<PrivateSession> {
+        self.sessions
+            .extract_if(
+                ..,
+                |session| matches!(session.until, PrivateUntil::Time(until) if until <= now),
+            )
+            .collect()
+    }
+
+    /// Remove sessions that end when `player_id` closes
+    pub fn player_closed(&mut self, player_id: &str) -> Vec<PrivateSession> {
+        self.sessions
+            .extract_if(.., |session| {
+                session.until == PrivateUntil::PlayerCloses && session.applies_to(player_id)
+            })
+            .collect()
+    }
+
+    /// Check if a session covers `player_id` now
+    #[must_use]
+    pub fn covers(&self, player_id: &str) -> bool {
+        self.sessions
+            .iter()
+            .any(|session| session.applies_to(player_id))
+    }
+
+    /// Sessions currently switched on
+    #[must_use]
+    pub fn sessions(&self) -> &[PrivateSession] {
+        &self.sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    /// 2024-06-01 is a Saturday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn schedule(toml: &str) -> PrivateSchedule {
+        let config: PrivateConfig = toml::from_str(toml).unwrap();
+        PrivateSchedule::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_window_past_midnight() {
+        let weekends = schedule(
+            r#"
+            [[schedule]]
+            days = ["sat", "sun"]
+            start = "22:00"
+            end = "07:00"
+            "#,
+        );
+
+        assert!(weekends.covers_time(at(1, 23, 30))); // Saturday night
+        assert!(weekends.covers_time(at(2, 6, 59))); // Sunday morning
+        assert!(weekends.covers_time(at(3, 6, 0))); // Monday morning, after Sunday night
+        assert!(!weekends.covers_time(at(1, 6, 0))); // Saturday morning, after Friday
+        assert!(!weekends.covers_time(at(2, 7, 0)));
+        assert!(!weekends.covers_time(at(3, 22, 30))); // Monday night
+    }
+
+    #[test]
+    fn test_players_and_daytime_window() {
+        let schedule = schedule(
+            r#"
+            players = ["kids-player"]
+
+            [[schedule]]
+            start = "09:00"
+            end = "12:00"
+            "#,
+        );
+
+        assert!(schedule.covers("org.kids-player.instance2", at(4, 18, 0)));
+        assert!(schedule.covers("spotify", at(4, 10, 0)));
+        assert!(!schedule.covers("spotify", at(4, 12, 0)));
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        for toml in [
+            "[[schedule]]\nstart = \"25:00\"\nend = \"07:00\"",
+            "[[schedule]]\ndays = [\"someday\"]\nstart = \"22:00\"\nend = \"07:00\"",
+            "[[schedule]]\nstart = \"22:00\"\nend = \"22:00\"",
+        ] {
+            let config: PrivateConfig = toml::from_str(toml).unwrap();
+            assert!(PrivateSchedule::new(&config).is_err(), "{toml}");
+        }
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut sessions = PrivateSessions::default();
+        assert!(sessions
+            .start(PrivateSession {
+                player: None,
+                until: PrivateUntil::PlayerCloses,
+            })
+            .is_err());
+
+        sessions
+            .start(PrivateSession {
+                player: Some("spotify".to_string()),
+                until: PrivateUntil::PlayerCloses,
+            })
+            .unwrap();
+        sessions
+            .start(PrivateSession {
+                player: None,
+                until: PrivateUntil::Time(at(1, 12, 0)),
+            })
+            .unwrap();
+        assert!(sessions.covers("vlc"));
+
+        assert_eq!(sessions.expire(at(1, 12, 0)).len(), 1);
+        assert!(!sessions.covers("vlc"));
+        assert!(sessions.covers("spotify"));
+
+        assert!(sessions.player_closed("vlc").is_empty());
+        assert_eq!(sessions.player_closed("spotify").len(), 1);
+        assert!(sessions.sessions().is_empty());
+    }
+
+    #[test]
+    fn test_sessions_save_load() {
+        let dir = std::env::temp_dir().join(format!("private-test-{}", uuid::Uuid::new_v4()));
+        let mut sessions = PrivateSessions::load(&dir).unwrap();
+        assert!(sessions.sessions().is_empty());
+
+        sessions
+            .start(PrivateSession::requested("", 0, false).unwrap())
+            .unwrap();
+        sessions
+            .start(PrivateSession::requested("spotify", 3600, false).unwrap())
+            .unwrap();
+        sessions
+            .start(PrivateSession {
+                player: Some("vlc".to_string()),
+                until: PrivateUntil::Time(Local::now() - TimeDelta::minutes(1)),
+            })
+            .unwrap();
+        sessions.save().unwrap();
+
+        // Sessions whose time is up while the tracker was down are dropped
+        let loaded = PrivateSessions::load(&dir).unwrap();
+        assert_eq!(loaded.sessions().len(), 2);
+        let players: Vec<_> = loaded
+            .sessions()
+            .iter()
+            .filter_map(|session| session.player.as_deref())
+            .collect();
+        assert_eq!(players, ["spotify"]);
+
+        // Switching everything off removes the file
+        let mut loaded = loaded;
+        loaded.stop(None);
+        loaded.save().unwrap();
+        assert!(!dir.join(SESSIONS_FILE).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+//! Internet radio stream handling
+//!
+//! Radio players keep the stream URL fixed, put the station name in
+//! `xesam:artist` and the current song as "Artist - Title" in `xesam:title`.
+//! Streams are recognised by a network URL with no track length, and the
+//! title is parsed with configurable patterns so each song becomes its own
+//! listen.
+
+use crate::config::RadioConfig;
+use crate::error::{Error, Result};
+use crate::track::Track;
+
+/// URL schemes used by network streams
+const STREAM_SCHEMES: &[&str] = &[
+    "http://", "https://", "icy://", "mms://", "rtsp://", "rtmp://",
+];
+
+/// Part of the stream title a pattern placeholder captures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Artist,
+    Title,
+}
+
+/// Piece of a title pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// Stream title pattern such as `"{artist} - {title}"`
+#[derive(Debug, Clone)]
+pub struct TitlePattern {
+    segments: Vec<Segment>,
+}
+
+impl TitlePattern {
+    /// Parse a pattern containing one `{artist}` and one `{title}` placeholder
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = pattern;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| {
+                Error::config(format!("Unclosed placeholder in radio pattern {pattern:?}"))
+            })? + open;
+            let field = match &rest[open + 1..close] {
+                "artist" => Field::Artist,
+                "title" => Field::Title,
+                other => {
+                    return Err(Error::config(format!(
+                        "Unknown placeholder {{{other}}} in radio pattern {pattern:?}"
+                    )))
+                }
+            };
+            if matches!(segments.last(), Some(Segment::Field(_))) {
+                return Err(Error::config(format!(
+                    "Placeholders must be separated by text in radio pattern {pattern:?}"
+                )));
+            }
+            segments.push(Segment::Field(field));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        let count = |field| {
+            segments
+                .iter()
+                .filter(|segment| **segment == Segment::Field(field))
+                .count()
+        };
+        if count(Field::Artist) != 1 || count(Field::Title) != 1 {
+            return Err(Error::config(format!(
+                "Radio pattern {pattern:?} needs exactly one {{artist}} and one {{title}}"
+            )));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Extract `(artist, title)` from a stream title, if it fits the pattern
+    #[must_use]
+    pub fn extract(&self, text: &str) -> Option<(String, String)> {
+        let mut artist = None;
+        let mut title = None;
+        if !match_segments(&self.segments, text.trim(), &mut artist, &mut title) {
+            return None;
+        }
+        Some((artist?, title?))
+    }
+}
+
+/// Match `segments` against all of `text`, trying the leftmost split first
+fn match_segments(
+    segments: &[Segment],
+    text: &str,
+    artist: &mut Option<String>,
+    title: &mut Option<String>,
+) -> bool {
+    let Some((first, rest)) = segments.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        Segment::Literal(literal) => text
+            .strip_prefix(literal.as_str())
+            .is_some_and(|remaining| match_segments(rest, remaining, artist, title)),
+        Segment::Field(field) => match rest.first() {
+            // Fields are always followed by a literal or the end of the text
+            None => capture(*field, text, artist, title),
+            Some(Segment::Literal(literal)) => {
+                text.match_indices(literal.as_str()).any(|(at, _)| {
+                    capture(*field, &text[..at], artist, title)
+                        && match_segments(rest, &text[at..], artist, title)
+                })
+            }
+            Some(Segment::Field(_)) => false,
+        },
+    }
+}
+
+/// Store a non-empty captured value in its field's slot
+fn capture(
+    field: Field,
+    value: &str,
+    artist: &mut Option<String>,
+    title: &mut Option<String>,
+) -> bool {
+    let value = value.trim();
+    if value.is_empty() {
+        return false;
+    }
+    let slot = match field {
+        Field::Artist => artist,
+        Field::Title => title,
+    };
+    *slot = Some(value.to_string());
+    true
+}
+
+/// Check if a track is a live network stream rather than a file
+#[must_use]
+pub fn is_stream(track: &Track) -> bool {
+    track
+        .file_path
+        .as_deref()
+        .is_some_and(|url| STREAM_SCHEMES.iter().any(|scheme| url.starts_with(scheme)))
+        && !matches!(track.duration_us, Some(duration) if duration > 0)
+}
+
+/// Splits radio stream metadata into the current song and its station
+#[derive(Debug, Clone)]
+pub struct RadioParser {
+    enabled: bool,
+    patterns: Vec<TitlePattern>,
+}
+
+impl RadioParser {
+    /// Build a parser from the radio configuration
+    pub fn new(config: &RadioConfig) -> Result<Self> {
+        let patterns = config
+            .title_patterns
+            .iter()
+            .map(|pattern| TitlePattern::parse(pattern))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            enabled: config.enabled,
+            patterns,
+        })
+    }
+
+    /// Rewrite a radio stream track as the song it is currently playing.
+    ///
+    /// The station name moves to `station` and the parsed artist and title
+    /// replace the stream's. Tracks that aren't streams, or whose title
+    /// matches no pattern (jingles, news), are left unchanged.
+    pub fn apply(&self, track: &mut Track) {
+        if !self.enabled || !is_stream(track) {
+            return;
+        }
+        let Some(stream_title) = track.title.as_deref() else {
+            return;
+        };
+        let Some((artist, title)) = self
+            .patterns
+            .iter()
+            .find_map(|pattern| pattern.extract(stream_title))
+        else {
+            return;
+        };
+
+        let station = track.artist.take().or_else(|| track.album.take());
+        if track.album.is_some() && track.album == station {
+            track.album = None;
+        }
+
+        track.station = station;
+        track.artist = Some(artist);
+        track.title = Some(title);
+        track.is_radio = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(title: &str) -> Track {
+        Track {
+            title: Some(title.to_string()),
+            artist: Some("Radio Paradise".to_string()),
+            file_path: Some("https://stream.radioparadise.com/flac".to_string()),
+            ..Track::default()
+        }
+    }
+
+    #[test]
+    fn test_pattern_extract() {
+        let pattern = TitlePattern::parse("{artist} - {title}").unwrap();
+        assert_eq!(
+            pattern.extract("Miles Davis - So What"),
+            Some(("Miles Davis".to_string(), "So What".to_string()))
+        );
+        // Leftmost separator wins, the rest belongs to the title
+        assert_eq!(
+            pattern.extract("Beck - Loser - Live"),
+            Some(("Beck".to_string(), "Loser - Live".to_string()))
+        );
+        assert_eq!(pattern.extract("Station ID"), None);
+        assert_eq!(pattern.extract(" - Untitled"), None);
+
+        let reversed = TitlePattern::parse("{title} by {artist}").unwrap();
+        assert_eq!(
+            reversed.extract("Hurt by Johnny Cash"),
+            Some(("Johnny Cash".to_string(), "Hurt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_pattern_parse_errors() {
+        assert!(TitlePattern::parse("{artist}").is_err());
+        assert!(TitlePattern::parse("{artist}{title}").is_err());
+        assert!(TitlePattern::parse("{artist} - {song}").is_err());
+        assert!(TitlePattern::parse("{artist} - {title").is_err());
+    }
+
+    #[test]
+    fn test_is_stream() {
+        assert!(is_stream(&stream("A - B")));
+
+        let mut file = stream("A - B");
+        file.file_path = Some("file:///music/a.flac".to_string());
+        assert!(!is_stream(&file));
+
+        // Online tracks with a known length are not radio
+        let mut online = stream("A - B");
+        online.duration_us = Some(180_000_000);
+        assert!(!is_stream(&online));
+    }
+
+    #[test]
+    fn test_apply_splits_song_and_station() {
+        let parser = RadioParser::new(&RadioConfig::default()).unwrap();
+
+        let mut track = stream("Miles Davis - So What");
+        parser.apply(&mut track);
+        assert!(track.is_radio);
+        assert_eq!(track.source(), Some("radio"));
+        assert_eq!(track.station.as_deref(), Some("Radio Paradise"));
+        assert_eq!(track.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(track.title.as_deref(), Some("So What"));
+
+        // Consecutive songs on the same stream are different tracks
+        let mut next = stream("Bill Evans - Peace Piece");
+        parser.apply(&mut next);
+        assert!(!track.is_same_track(&next));
+    }
+
+    #[test]
+    fn test_apply_leaves_unparsed_and_disabled() {
+        let parser = RadioParser::new(&RadioConfig::default()).unwrap();
+        let mut jingle = stream("You're listening to Radio Paradise");
+        parser.apply(&mut jingle);
+        assert!(!jingle.is_radio);
+        assert_eq!(jingle.artist.as_deref(), Some("Radio Paradise"));
+
+        let disabled = RadioParser::new(&RadioConfig {
+            enabled: false,
+            ..RadioConfig::default()
+        })
+        .unwrap();
+        let mut track = stream("Miles Davis - So What");
+        disabled.apply(&mut track);
+        assert!(!track.is_radio);
+    }
+}
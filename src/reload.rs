@@ -0,0 +1,116 @@
+//! Configuration reload triggers
+//!
+//! The tracker reloads its configuration when the file changes on disk or when
+//! it receives `SIGHUP`. The file's directory is watched rather than the file
+//! itself, since editors usually save by writing a new file and renaming it
+//! over the old one.
+
+use std::ffi::OsString;
+use std::path::Path;
+
+use futures::{Stream, StreamExt};
+use inotify::{Inotify, WatchMask};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+/// Size of the buffer inotify events are read into
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// What asked for the configuration to be reloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadTrigger {
+    /// The configuration file was written or replaced
+    FileChanged,
+    /// The process received `SIGHUP`
+    Hangup,
+}
+
+/// Stream of reload requests for the configuration file at `path`.
+///
+/// If the file's directory can't be watched, only `SIGHUP` triggers reloads.
+pub fn requests(path: &Path) -> Result<impl Stream<Item = ReloadTrigger>> {
+    let file_changes = match file_changes(path) {
+        Ok(changes) => Some(changes),
+        Err(e) => {
+            warn!("Not watching {} for changes: {}", path.display(), e);
+            None
+        }
+    };
+
+    Ok(futures::stream::select(
+        hangups()?,
+        futures::stream::iter(file_changes).flatten(),
+    ))
+}
+
+/// Stream of writes to the file at `path`
+fn file_changes(path: &Path) -> Result<impl Stream<Item = ReloadTrigger>> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name: OsString = path
+        .file_name()
+        .ok_or_else(|| Error::config(format!("Not a file path: {}", path.display())))?
+        .to_os_string();
+
+    let inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    let events = inotify.into_event_stream(vec![0; EVENT_BUFFER_SIZE])?;
+
+    Ok(events.filter_map(move |event| {
+        let changed = event.is_ok_and(|event| event.name.as_ref() == Some(&file_name));
+        async move { changed.then_some(ReloadTrigger::FileChanged) }
+    }))
+}
+
+/// Stream of `SIGHUP` deliveries
+fn hangups() -> Result<impl Stream<Item = ReloadTrigger>> {
+    let hangup = signal(SignalKind::hangup())?;
+
+    Ok(futures::stream::unfold(hangup, |mut hangup| async move {
+        hangup.recv().await?;
+        Some((ReloadTrigger::Hangup, hangup))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_file_changes() {
+        let dir = std::env::temp_dir().join(format!("reload-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+
+        let mut changes = Box::pin(file_changes(&path).unwrap());
+
+        // Other files in the directory are ignored
+        std::fs::write(dir.join("other.toml"), "").unwrap();
+        // Saving through a temporary file and renaming counts as a change
+        let temp = dir.join(".config.toml.swp");
+        std::fs::write(&temp, "[tracking]").unwrap();
+        std::fs::rename(&temp, &path).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            tokio::time::timeout(timeout, changes.next()).await.unwrap(),
+            Some(ReloadTrigger::FileChanged)
+        );
+
+        std::fs::write(&path, "[players]").unwrap();
+        assert_eq!(
+            tokio::time::timeout(timeout, changes.next()).await.unwrap(),
+            Some(ReloadTrigger::FileChanged)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
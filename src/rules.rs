@@ -0,0 +1,529 @@
+//! User-defined rules applied to plays before they are logged
+//!
+//! Each `[[rules]]` entry in the config has `match` conditions on the play's
+//! fields and actions: drop the play, overwrite track fields, or add tags.
+//! Fields are those of [`Track`] and [`ListeningContext`], plus
+//! `player_name`, `is_local`, `duration_seconds` and `played_seconds`.
+//!
+//! Conditions are regexes for text fields, comparisons such as `"< 30"` for
+//! numbers, or plain values that must be equal.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::config::RuleConfig;
+use crate::context::ListeningContext;
+use crate::error::{Error, Result};
+use crate::track::{PlayInterval, Track, TrackState};
+
+/// Comparison operator of a numeric condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// Split a leading operator off a condition such as `"<= 30"`
+    fn parse_prefix(text: &str) -> Option<(Self, &str)> {
+        let text = text.trim_start();
+        [
+            ("<=", Self::LessOrEqual),
+            (">=", Self::GreaterOrEqual),
+            ("!=", Self::NotEqual),
+            ("==", Self::Equal),
+            ("<", Self::Less),
+            (">", Self::Greater),
+            ("=", Self::Equal),
+        ]
+        .into_iter()
+        .find_map(|(op, comparison)| text.strip_prefix(op).map(|rest| (comparison, rest)))
+    }
+
+    fn holds(self, value: f64, bound: f64) -> bool {
+        match self {
+            Self::Less => value < bound,
+            Self::LessOrEqual => value <= bound,
+            Self::Greater => value > bound,
+            Self::GreaterOrEqual => value >= bound,
+            Self::Equal => (value - bound).abs() < f64::EPSILON,
+            Self::NotEqual => (value - bound).abs() >= f64::EPSILON,
+        }
+    }
+}
+
+/// Test applied to one field
+#[derive(Debug, Clone)]
+enum Condition {
+    /// Text (or the text form of a value) matches
+    Regex(Regex),
+    /// Number compares to a bound
+    Compare(Comparison, f64),
+    /// Boolean equals
+    Bool(bool),
+}
+
+impl Condition {
+    fn parse(value: &toml::Value) -> std::result::Result<Self, String> {
+        match value {
+            toml::Value::String(text) => {
+                if let Some((comparison, bound)) = Comparison::parse_prefix(text) {
+                    if let Ok(bound) = bound.trim().parse() {
+                        return Ok(Self::Compare(comparison, bound));
+                    }
+                }
+                Regex::new(text)
+                    .map(Self::Regex)
+                    .map_err(|e| format!("invalid regex: {e}"))
+            }
+            #[allow(clippy::cast_precision_loss)]
+            toml::Value::Integer(number) => Ok(Self::Compare(Comparison::Equal, *number as f64)),
+            toml::Value::Float(number) => Ok(Self::Compare(Comparison::Equal, *number)),
+            toml::Value::Boolean(flag) => Ok(Self::Bool(*flag)),
+            other => Err(format!("unsupported condition {other}")),
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (_, Value::Null) => false,
+            (Self::Regex(regex), Value::String(text)) => regex.is_match(text),
+            (Self::Regex(regex), other) => regex.is_match(&other.to_string()),
+            (Self::Compare(comparison, bound), Value::Number(number)) => number
+                .as_f64()
+                .is_some_and(|number| comparison.holds(number, *bound)),
+            (Self::Bool(flag), Value::Bool(value)) => flag == value,
+            _ => false,
+        }
+    }
+}
+
+/// A compiled rule
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    conditions: Vec<(String, Condition)>,
+    drop: bool,
+    set: Vec<(String, Value)>,
+    tags: Vec<String>,
+}
+
+impl Rule {
+    fn compile(config: &RuleConfig, index: usize) -> Result<Self> {
+        let name = if config.name.is_empty() {
+            format!("rule #{}", index + 1)
+        } else {
+            config.name.clone()
+        };
+        let invalid = |message: String| Error::config(format!("Rule {name:?}: {message}"));
+
+        let known = play_fields(&TrackState::new(), &ListeningContext::default());
+        let conditions = config
+            .conditions
+            .iter()
+            .map(|(field, value)| {
+                if !known.contains_key(field) {
+                    return Err(invalid(format!("unknown field {field:?}")));
+                }
+                Condition::parse(value)
+                    .map(|condition| (field.clone(), condition))
+                    .map_err(|message| invalid(format!("{field}: {message}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let set = config
+            .set
+            .iter()
+            .map(|(field, value)| {
+                let value = serde_json::to_value(value)?;
+                let mut track = track_fields(&Track::default());
+                if !track.contains_key(field) {
+                    return Err(invalid(format!("cannot set {field:?}")));
+                }
+                track.insert(field.clone(), value.clone());
+                serde_json::from_value::<Track>(Value::Object(track))
+                    .map_err(|e| invalid(format!("cannot set {field}: {e}")))?;
+                Ok((field.clone(), value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(tag) = config
+            .tags
+            .iter()
+            .find(|tag| tag.is_empty() || tag.contains(','))
+        {
+            return Err(invalid(format!("invalid tag {tag:?}")));
+        }
+
+        Ok(Self {
+            name,
+            conditions,
+            drop: config.drop,
+            set,
+            tags: config.tags.clone(),
+        })
+    }
+
+    /// Name of the first condition that fails, or None if the rule fires
+    fn first_mismatch(&self, fields: &Map<String, Value>) -> Option<&str> {
+        self.conditions
+            .iter()
+            .find(|(field, condition)| {
+                !condition.matches(fields.get(field).unwrap_or(&Value::Null))
+            })
+            .map(|(field, _)| field.as_str())
+    }
+}
+
+/// How one rule evaluated against a play
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleResult {
+    pub name: String,
+    /// None if the rule fired, otherwise the first condition that failed
+    pub mismatch: Option<String>,
+}
+
+/// Result of running the rules on a play
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleOutcome {
+    /// Every rule evaluated, in order
+    pub results: Vec<RuleResult>,
+    /// Name of the rule that dropped the play, if any
+    pub dropped_by: Option<String>,
+}
+
+impl RuleOutcome {
+    /// Names of the rules that fired
+    pub fn fired(&self) -> impl Iterator<Item = &str> {
+        self.results
+            .iter()
+            .filter(|result| result.mismatch.is_none())
+            .map(|result| result.name.as_str())
+    }
+}
+
+/// Ordered set of compiled rules
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Compile the configured rules, checking field names and regexes
+    pub fn new(configs: &[RuleConfig]) -> Result<Self> {
+        let rules = configs
+            .iter()
+            .enumerate()
+            .map(|(index, config)| Rule::compile(config, index))
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Check if there are no rules
+    #[must_use]
+    #[allow(dead_code)] // Used by the `rules test` command
+    pub const fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Run the rules in order on a play about to be logged.
+    ///
+    /// Field changes and tags are applied to `state`; later rules see the
+    /// changes of earlier ones. Evaluation stops at the first rule that drops
+    /// the play.
+    pub fn apply(&self, state: &mut TrackState, context: &ListeningContext) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+
+        for rule in &self.rules {
+            let fields = play_fields(state, context);
+            let mismatch = rule.first_mismatch(&fields).map(str::to_string);
+            let fired = mismatch.is_none();
+            outcome.results.push(RuleResult {
+                name: rule.name.clone(),
+                mismatch,
+            });
+            if !fired {
+                continue;
+            }
+
+            if rule.drop {
+                outcome.dropped_by = Some(rule.name.clone());
+                break;
+            }
+            if !rule.set.is_empty() {
+                set_track_fields(&mut state.track, &rule.set, &fields);
+            }
+            for tag in &rule.tags {
+                if !state.tags.contains(tag) {
+                    state.tags.push(tag.clone());
+                }
+            }
+        }
+
+        outcome
+    }
+}
+
+impl fmt::Display for RuleOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            match &result.mismatch {
+                None => writeln!(f, "  \u{2713} {}", result.name)?,
+                Some(field) => writeln!(f, "    {} (no match on {})", result.name, field)?,
+            }
+        }
+        match &self.dropped_by {
+            Some(name) => write!(f, "Result: dropped by {name:?}"),
+            None => write!(f, "Result: logged"),
+        }
+    }
+}
+
+/// Track fields by column name
+fn track_fields(track: &Track) -> Map<String, Value> {
+    match serde_json::to_value(track) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// All fields rules can match on for a play
+#[must_use]
+pub fn play_fields(state: &TrackState, context: &ListeningContext) -> Map<String, Value> {
+    let mut fields = track_fields(&state.track);
+    if let Ok(Value::Object(context_fields)) = serde_json::to_value(context) {
+        fields.extend(context_fields);
+    }
+
+    fields.insert("player_name".into(), state.player_name.clone().into());
+    fields.insert("is_local".into(), state.is_local.into());
+    fields.insert(
+        "duration_seconds".into(),
+        state.track.duration_us.map(|us| us / 1_000_000).into(),
+    );
+    fields.insert("played_seconds".into(), (state.played_ms() / 1000).into());
+    fields
+}
+
+/// Build a play from `field=value` pairs, for trying out rules.
+///
+/// Values are read as JSON where that fits the field (numbers, booleans) and
+/// as text otherwise.
+#[allow(dead_code)] // Used by the `rules test` command
+pub fn sample_play(pairs: &[(String, String)]) -> Result<(TrackState, ListeningContext)> {
+    let mut state = TrackState::new();
+    let mut track = track_fields(&state.track);
+    let mut context = match serde_json::to_value(ListeningContext::default())? {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+
+    for (field, text) in pairs {
+        let seconds = || {
+            text.parse::<u64>()
+                .map_err(|_| Error::config(format!("{field} must be a number of seconds")))
+        };
+        match field.as_str() {
+            "player_name" => state.player_name = Some(text.clone()),
+            "is_local" => state.is_local = text == "true",
+            "duration_seconds" => {
+                let duration_us =
+                    i64::try_from(seconds()?.saturating_mul(1_000_000)).unwrap_or(i64::MAX);
+                track.insert("duration_us".into(), duration_us.into());
+            }
+            "played_seconds" => {
+                let end = Instant::now();
+                let played = Duration::from_secs(seconds()?);
+                state.intervals.push(PlayInterval {
+                    start: end.checked_sub(played).unwrap_or(end),
+                    end,
+                });
+            }
+            _ if track.contains_key(field) => insert_typed::<Track>(&mut track, field, text),
+            _ if context.contains_key(field) => {
+                insert_typed::<ListeningContext>(&mut context, field, text);
+            }
+            _ => return Err(Error::config(format!("Unknown field {field:?}"))),
+        }
+    }
+
+    state.track = serde_json::from_value(Value::Object(track))?;
+    let context = serde_json::from_value(Value::Object(context))?;
+    Ok((state, context))
+}
+
+/// Insert a value parsed as JSON if `T` accepts it, otherwise as text
+#[allow(dead_code)]
+fn insert_typed<T: DeserializeOwned>(fields: &mut Map<String, Value>, field: &str, text: &str) {
+    let parsed = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()));
+    fields.insert(field.to_string(), parsed);
+    if serde_json::from_value::<T>(Value::Object(fields.clone())).is_err() {
+        fields.insert(field.to_string(), Value::String(text.to_string()));
+    }
+}
+
+/// Overwrite track fields, expanding `{field}` references in text values
+fn set_track_fields(track: &mut Track, set: &[(String, Value)], fields: &Map<String, Value>) {
+    let mut updated = track_fields(track);
+    for (field, value) in set {
+        let value = match value {
+            Value::String(template) => Value::String(expand(template, fields)),
+            other => other.clone(),
+        };
+        updated.insert(field.clone(), value);
+    }
+
+    match serde_json::from_value(Value::Object(updated)) {
+        Ok(new_track) => *track = new_track,
+        Err(e) => tracing::warn!("Rule produced invalid track fields: {}", e),
+    }
+}
+
+/// Replace `{field}` with the field's value (empty if unset)
+fn expand(template: &str, fields: &Map<String, Value>) -> String {
+    let mut expanded = template.to_string();
+    for (name, value) in fields {
+        let placeholder = format!("{{{name}}}");
+        if expanded.contains(&placeholder) {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            expanded = expanded.replace(&placeholder, &text);
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml_rule: &str) -> RuleConfig {
+        toml::from_str(toml_rule).unwrap()
+    }
+
+    fn play(title: &str, artist: &str) -> TrackState {
+        let mut state = TrackState::new();
+        state.track.title = Some(title.to_string());
+        state.track.artist = Some(artist.to_string());
+        state.track.duration_us = Some(20_000_000);
+        state.player_name = Some("spotify".to_string());
+        state
+    }
+
+    #[test]
+    fn test_drop_by_title_regex_and_duration() {
+        let rules = RuleSet::new(&[
+            rule(
+                r#"
+                name = "noise"
+                drop = true
+                match = { title = "(?i)white noise|test tone" }
+                "#,
+            ),
+            rule(
+                r#"
+                name = "short"
+                drop = true
+                match = { duration_seconds = "< 30" }
+                "#,
+            ),
+        ])
+        .unwrap();
+
+        let context = ListeningContext::default();
+        let outcome = rules.apply(&mut play("Pure White Noise", "Nature"), &context);
+        assert_eq!(outcome.dropped_by.as_deref(), Some("noise"));
+        assert_eq!(outcome.results.len(), 1);
+
+        let mut song = play("Song", "Band");
+        let outcome = rules.apply(&mut song, &context);
+        assert_eq!(outcome.dropped_by.as_deref(), Some("short"));
+
+        song.track.duration_us = Some(200_000_000);
+        let outcome = rules.apply(&mut song, &context);
+        assert_eq!(outcome.dropped_by, None);
+        assert_eq!(outcome.fired().count(), 0);
+        assert_eq!(outcome.results[0].mismatch.as_deref(), Some("title"));
+    }
+
+    #[test]
+    fn test_set_and_tag() {
+        let rules = RuleSet::new(&[
+            rule(
+                r#"
+                name = "remap"
+                match = { player_name = "^spotify$" }
+                set = { artist = "{album_artist}", genre = "Rock" }
+                "#,
+            ),
+            rule(
+                r#"
+                name = "work"
+                tags = ["work"]
+                match = { active_window = "(?i)slack", artist = "^Band$" }
+                "#,
+            ),
+        ])
+        .unwrap();
+
+        let mut state = play("Song", "Band feat. Someone");
+        state.track.album_artist = Some("Band".to_string());
+        let context = ListeningContext {
+            active_window: Some("Slack | general".to_string()),
+            ..ListeningContext::default()
+        };
+
+        let outcome = rules.apply(&mut state, &context);
+        assert_eq!(outcome.fired().collect::<Vec<_>>(), ["remap", "work"]);
+        assert_eq!(state.track.artist.as_deref(), Some("Band"));
+        assert_eq!(state.track.genre.as_deref(), Some("Rock"));
+        assert_eq!(state.tags, ["work"]);
+    }
+
+    #[test]
+    fn test_sample_play() {
+        let pairs = [
+            ("title", "White Noise"),
+            ("track_number", "3"),
+            ("duration_seconds", "600"),
+            ("player_name", "spotify"),
+            ("hour_of_day", "23"),
+        ]
+        .map(|(field, value)| (field.to_string(), value.to_string()));
+        let (state, context) = sample_play(&pairs).unwrap();
+        assert_eq!(state.track.title.as_deref(), Some("White Noise"));
+        assert_eq!(state.track.track_number, Some(3));
+        assert_eq!(state.track.duration_us, Some(600_000_000));
+        assert_eq!(state.player_name.as_deref(), Some("spotify"));
+        assert_eq!(context.hour_of_day, 23);
+
+        let unknown = [("colour".to_string(), "red".to_string())];
+        assert!(sample_play(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let unknown_field = rule(r#"match = { colour = "red" }"#);
+        assert!(RuleSet::new(&[unknown_field]).is_err());
+
+        let bad_regex = rule(r#"match = { title = "(" }"#);
+        assert!(RuleSet::new(&[bad_regex]).is_err());
+
+        let bad_set = rule(r#"set = { track_number = "one" }"#);
+        assert!(RuleSet::new(&[bad_set]).is_err());
+
+        let context_set = rule(r#"set = { active_window = "x" }"#);
+        assert!(RuleSet::new(&[context_set]).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Listening session tracking
+//!
+//! Groups plays into sessions: a session ends once nothing has been playing
+//! on any player for longer than the configured gap.
+
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+/// Tracks the current listening session across all players
+#[derive(Debug, Clone)]
+pub struct SessionTracker {
+    /// Silence after which the next playback starts a new session
+    gap: Duration,
+    /// Current session ID, if a session has started
+    current: Option<String>,
+    /// When playback last stopped on every player (None while something plays)
+    quiet_since: Option<Instant>,
+}
+
+impl SessionTracker {
+    /// Create a tracker that ends sessions after `gap` with nothing playing
+    #[must_use]
+    pub const fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            current: None,
+            quiet_since: None,
+        }
+    }
+
+    /// Change the gap, e.g. after the configuration is reloaded
+    pub const fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Note whether anything is playing at `now`.
+    ///
+    /// Returns true if this started a new session.
+    pub fn update(&mut self, playing: bool, now: Instant) -> bool {
+        if !playing {
+            if self.quiet_since.is_none() {
+                self.quiet_since = Some(now);
+            }
+            return false;
+        }
+
+        let expired = self
+            .quiet_since
+            .take()
+            .is_some_and(|since| now.saturating_duration_since(since) >= self.gap);
+
+        if self.current.is_none() || expired {
+            self.current = Some(Uuid::new_v4().to_string());
+            return true;
+        }
+
+        false
+    }
+
+    /// ID of the current session, starting one if none is active
+    pub fn session_id(&mut self) -> String {
+        self.current
+            .get_or_insert_with(|| Uuid::new_v4().to_string())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAP: Duration = Duration::from_mins(25);
+
+    #[test]
+    fn test_first_playback_starts_session() {
+        let mut tracker = SessionTracker::new(GAP);
+        assert!(tracker.update(true, Instant::now()));
+        assert!(!tracker.update(true, Instant::now()));
+    }
+
+    #[test]
+    fn test_short_pause_keeps_session() {
+        let mut tracker = SessionTracker::new(GAP);
+        let start = Instant::now();
+        tracker.update(true, start);
+        let id = tracker.session_id();
+
+        tracker.update(false, start + Duration::from_secs(10));
+        assert!(!tracker.update(true, start + Duration::from_secs(610)));
+        assert_eq!(tracker.session_id(), id);
+    }
+
+    #[test]
+    fn test_long_gap_starts_new_session() {
+        let mut tracker = SessionTracker::new(GAP);
+        let start = Instant::now();
+        tracker.update(true, start);
+        let id = tracker.session_id();
+
+        tracker.update(false, start + Duration::from_secs(10));
+        // Repeated idle updates don't move the start of the quiet period
+        tracker.update(false, start + Duration::from_secs(1000));
+        assert!(tracker.update(true, start + Duration::from_secs(1510)));
+        assert_ne!(tracker.session_id(), id);
+    }
+
+    #[test]
+    fn test_session_id_without_playback() {
+        let mut tracker = SessionTracker::new(GAP);
+        let id = tracker.session_id();
+        assert_eq!(tracker.session_id(), id);
+    }
+}
@@ -0,0 +1,426 @@
+//! Private D-Bus daemon and service stubs for tests
+//!
+//! Tests that need a bus start their own `dbus-daemon` so they never touch
+//! the real session or system bus, and skip themselves if it isn't installed.
+//! Scripted stub players and a [`TestMonitor`] let monitor tests play
+//! through a listening session and check what ends up in the database, with
+//! time stepped by a [`ManualClock`] rather than slept through.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedFd, OwnedValue, Value};
+use zbus::Connection;
+
+use crate::clock::ManualClock;
+use crate::config::Config;
+use crate::db::Database;
+use crate::logind::{Logind, INHIBIT_WHO, INHIBIT_WHY, LOGIND_PATH, LOGIND_SERVICE};
+use crate::mpris::{MprisMonitor, MPRIS_PATH, MPRIS_PREFIX};
+
+/// Private bus daemon, killed on drop
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl PrivateBus {
+    /// Start a private `dbus-daemon`, or None if it isn't installed
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    /// Open a new connection to the bus
+    pub async fn connect(&self) -> Connection {
+        zbus::connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Minimal stand-in for the logind manager
+pub struct StubLogind {
+    pub inhibit_calls: Arc<AtomicU32>,
+}
+
+impl StubLogind {
+    /// Serve the stub as `org.freedesktop.login1` on `bus`
+    pub async fn serve(bus: &PrivateBus) -> (Connection, Arc<AtomicU32>) {
+        let inhibit_calls = Arc::new(AtomicU32::new(0));
+        let connection = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(LOGIND_SERVICE)
+            .unwrap()
+            .serve_at(
+                LOGIND_PATH,
+                Self {
+                    inhibit_calls: Arc::clone(&inhibit_calls),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        (connection, inhibit_calls)
+    }
+
+    /// Announce sleep (`start` = true) or resume on the stub's connection
+    pub async fn emit_sleep(connection: &Connection, start: bool) {
+        let emitter = SignalEmitter::new(connection, LOGIND_PATH).unwrap();
+        Self::prepare_for_sleep(&emitter, start).await.unwrap();
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl StubLogind {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> OwnedFd {
+        assert_eq!(what, "sleep:shutdown");
+        assert_eq!(who, INHIBIT_WHO);
+        assert_eq!(why, INHIBIT_WHY);
+        assert_eq!(mode, "delay");
+        self.inhibit_calls.fetch_add(1, Ordering::SeqCst);
+        std::os::fd::OwnedFd::from(std::fs::File::open("/dev/null").unwrap()).into()
+    }
+
+    #[zbus(signal)]
+    pub async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn prepare_for_shutdown(emitter: &SignalEmitter<'_>, start: bool)
+        -> zbus::Result<()>;
+}
+
+/// Scriptable MPRIS player serving a single local track at a time
+pub struct StubPlayer {
+    title: String,
+    status: String,
+    position_us: i64,
+}
+
+impl StubPlayer {
+    /// A player already playing `title`
+    pub fn playing(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            status: "Playing".to_string(),
+            position_us: 0,
+        }
+    }
+
+    /// Serve the player as `org.mpris.MediaPlayer2.<name>` on `bus`
+    pub async fn serve(self, bus: &PrivateBus, name: &str) -> PlayerScript {
+        let connection = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(format!("{MPRIS_PREFIX}{name}"))
+            .unwrap()
+            .serve_at(MPRIS_PATH, self)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        PlayerScript { connection }
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl StubPlayer {
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.status.clone()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "xesam:title".to_string(),
+            Value::from(self.title.as_str()).try_into().unwrap(),
+        );
+        metadata.insert(
+            "xesam:url".to_string(),
+            Value::from(format!("file:///music/{}.flac", self.title))
+                .try_into()
+                .unwrap(),
+        );
+        metadata
+    }
+
+    #[zbus(property)]
+    const fn position(&self) -> i64 {
+        self.position_us
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+}
+
+/// Drives a served [`StubPlayer`], announcing each change like a real player
+pub struct PlayerScript {
+    connection: Connection,
+}
+
+impl PlayerScript {
+    async fn player(&self) -> zbus::object_server::InterfaceRef<StubPlayer> {
+        self.connection
+            .object_server()
+            .interface::<_, StubPlayer>(MPRIS_PATH)
+            .await
+            .unwrap()
+    }
+
+    /// Switch to a new track from the start
+    pub async fn change_track(&self, title: &str) {
+        let player = self.player().await;
+        let mut stub = player.get_mut().await;
+        stub.title = title.to_string();
+        stub.position_us = 0;
+        stub.metadata_changed(player.signal_emitter())
+            .await
+            .unwrap();
+        drop(stub);
+    }
+
+    async fn set_status(&self, status: &str) {
+        let player = self.player().await;
+        let mut stub = player.get_mut().await;
+        stub.status = status.to_string();
+        stub.playback_status_changed(player.signal_emitter())
+            .await
+            .unwrap();
+        drop(stub);
+    }
+
+    /// Start or resume playback
+    pub async fn play(&self) {
+        self.set_status("Playing").await;
+    }
+
+    /// Pause playback
+    pub async fn pause(&self) {
+        self.set_status("Paused").await;
+    }
+
+    /// Stop playback
+    pub async fn stop(&self) {
+        self.set_status("Stopped").await;
+    }
+
+    /// Jump to `position_us` in the current track
+    pub async fn seek(&self, position_us: i64) {
+        let player = self.player().await;
+        player.get_mut().await.position_us = position_us;
+        StubPlayer::seeked(player.signal_emitter(), position_us)
+            .await
+            .unwrap();
+    }
+
+    /// Quit, releasing the player's bus name
+    pub async fn close(self) {
+        self.connection.close().await.unwrap();
+    }
+}
+
+/// Monitor running on a private bus, logging to a database in a scratch
+/// data directory that is removed on drop.
+///
+/// The monitor's clock only moves when a test advances it, and every event it
+/// applies is recorded so tests can wait for one before moving on.
+pub struct TestMonitor {
+    pub monitor: Arc<MprisMonitor>,
+    pub db: Database,
+    pub clock: Arc<ManualClock>,
+    data_dir: PathBuf,
+    /// Recorded events already waited for
+    events_seen: AtomicUsize,
+    run: Option<JoinHandle<crate::error::Result<()>>>,
+}
+
+impl TestMonitor {
+    /// Start a monitor with `config` watching players on `bus`
+    pub async fn start(bus: &PrivateBus, config: &Config) -> Self {
+        Self::start_with_logind(bus, config, None).await
+    }
+
+    /// Start a monitor with `config` watching players on `bus`, with sleep
+    /// and shutdown announced by `logind`
+    pub async fn start_with_logind(
+        bus: &PrivateBus,
+        config: &Config,
+        logind: Option<Logind>,
+    ) -> Self {
+        let data_dir = std::env::temp_dir().join(format!("monitor-test-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(&data_dir.join("listens.duckdb"))
+            .await
+            .unwrap();
+        let clock = ManualClock::new(chrono::Local::now());
+        let monitor = Arc::new(
+            MprisMonitor::with_connections(
+                config,
+                db.clone(),
+                &data_dir,
+                bus.connect().await,
+                logind,
+            )
+            .unwrap()
+            .with_clock(clock.clone())
+            .with_recording(&data_dir.join("events.jsonl"))
+            .unwrap(),
+        );
+
+        let run = tokio::spawn({
+            let monitor = Arc::clone(&monitor);
+            async move { monitor.run().await }
+        });
+
+        Self {
+            monitor,
+            db,
+            clock,
+            data_dir,
+            events_seen: AtomicUsize::new(0),
+            run: Some(run),
+        }
+    }
+
+    /// Wait until the monitor has applied the next event of type `kind`
+    /// (e.g. `"paused"`) after the last one waited for
+    pub async fn wait_for_event(&self, kind: &str) {
+        let path = self.data_dir.join("events.jsonl");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let log = std::fs::read_to_string(&path).unwrap_or_default();
+            let seen = self.events_seen.load(Ordering::SeqCst);
+            let found = log
+                .split_inclusive('\n')
+                .enumerate()
+                .skip(seen)
+                .filter(|(_, line)| line.ends_with('\n'))
+                .find(|(_, line)| {
+                    serde_json::from_str::<serde_json::Value>(line)
+                        .is_ok_and(|record| record["event"]["type"] == kind)
+                });
+            if let Some((index, _)) = found {
+                self.events_seen.store(index + 1, Ordering::SeqCst);
+                break;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "no {kind} event was applied after {seen} of:\n{log}"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // Events are recorded just before they are applied; this waits for
+        // the listens to be released
+        self.monitor.player_statuses().await;
+    }
+
+    /// Wait until the monitor tracks a player with ID `name`
+    pub async fn wait_for_player(&self, name: &str) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !self
+            .monitor
+            .player_statuses()
+            .await
+            .iter()
+            .any(|status| status.player == name)
+        {
+            assert!(Instant::now() < deadline, "player {name} was never tracked");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Wait until at least `count` plays have been logged
+    pub async fn wait_for_plays(&self, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.plays().await.len() < count {
+            assert!(
+                Instant::now() < deadline,
+                "fewer than {count} plays were logged"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Wait up to `timeout` for the monitor to exit by itself.
+    ///
+    /// Returns false if it is still running.
+    pub async fn exited_within(&mut self, timeout: Duration) -> bool {
+        let Some(run) = &mut self.run else {
+            return true;
+        };
+        match tokio::time::timeout(timeout, run).await {
+            Ok(result) => {
+                result.unwrap().unwrap();
+                self.run = None;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Stop the monitor and wait for it to log what was in progress
+    pub async fn stop(&mut self) {
+        self.monitor.stop();
+        assert!(self.exited_within(Duration::from_secs(5)).await);
+    }
+
+    /// Logged plays as (player, title), oldest first
+    pub async fn plays(&self) -> Vec<(String, String)> {
+        self.db
+            .query_rows("SELECT player_name, title FROM plays ORDER BY id", |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .await
+    }
+
+    /// Logged skips as (player, title, reason), oldest first
+    pub async fn skips(&self) -> Vec<(String, String, String)> {
+        self.db
+            .query_rows(
+                "SELECT player_name, title, reason FROM skips ORDER BY id",
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .await
+    }
+}
+
+impl Drop for TestMonitor {
+    fn drop(&mut self) {
+        self.monitor.stop();
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
@@ -4,16 +4,37 @@ use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
+use crate::clock::{self, SharedClock};
+use crate::content::ContentType;
+use crate::coverage::PositionCoverage;
+use crate::volume::{VolumeAverage, VolumeLevels};
+
 /// Threshold in microseconds for detecting intro position (5 seconds).
 const INTRO_START_THRESHOLD_US: i64 = 5_000_000;
 
 /// Threshold in microseconds for detecting intro skip (15 seconds).
 const INTRO_SKIP_THRESHOLD_US: i64 = 15_000_000;
 
+/// Distance from the end of a track in microseconds within which a track
+/// change counts as the player auto-advancing (5 seconds).
+const TRACK_END_THRESHOLD_US: i64 = 5_000_000;
+
+/// Position in microseconds below which a backwards jump counts as the track
+/// restarting (3 seconds).
+const LOOP_RESTART_THRESHOLD_US: i64 = 3_000_000;
+
+/// MPRIS `LoopStatus` value for repeating the current track
+const LOOP_STATUS_TRACK: &str = "Track";
+
+/// `plays.source` value for songs split out of a radio stream
+const SOURCE_RADIO: &str = "radio";
+
 /// Complete track metadata from MPRIS
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Track {
     // Core fields
+    /// Player's `mpris:trackid` for the track
+    pub track_id: Option<String>,
     pub title: Option<String>,
     pub artist: Option<String>,
     pub album: Option<String>,
@@ -31,26 +52,103 @@ pub struct Track {
     pub bpm: Option<i32>,
     pub composer: Option<String>,
     pub musicbrainz_track_id: Option<String>,
+
+    // Radio
+    /// Station name, for songs split out of an internet radio stream
+    pub station: Option<String>,
+    /// Whether the track is a song parsed from a radio stream title
+    #[serde(default)]
+    pub is_radio: bool,
+
+    /// Kind of media (music, podcast, audiobook or video)
+    #[serde(default)]
+    pub content_type: ContentType,
+}
+
+/// How a started listen that did not qualify as a play ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The user moved on before the track finished
+    Skipped,
+    /// The player reached the end of the track and advanced on its own
+    AutoAdvanced,
+    /// The player went away mid-track
+    PlayerClosed,
+    /// Tracking was paused, the system went to sleep or the tracker exited
+    Interrupted,
+}
+
+impl SkipReason {
+    /// Value stored in the `skips.reason` column
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Skipped => "skipped",
+            Self::AutoAdvanced => "auto_advanced",
+            Self::PlayerClosed => "player_closed",
+            Self::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// A contiguous stretch of playback between a play and a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayInterval {
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl PlayInterval {
+    /// Length of this interval
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
 }
 
 /// Tracks the current playing state with full metadata
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct TrackState {
     /// Current track metadata
     pub track: Track,
 
-    /// When playback started (for calculating play time)
+    /// When the current playing segment started (None while paused)
     pub start_time: Option<Instant>,
 
-    /// When playback started (wall clock time for DB)
+    /// When the listen started (wall clock time for DB)
     pub start_timestamp: Option<DateTime<Local>>,
 
+    /// Completed play segments of the current listen
+    pub intervals: Vec<PlayInterval>,
+
+    /// When playback was last paused (None while playing)
+    pub paused_at: Option<Instant>,
+
+    /// Number of times the listen was paused and resumed
+    pub pause_count: u32,
+
+    /// Total time spent paused between segments
+    pub paused_duration: Duration,
+
     /// Whether currently playing
     pub is_playing: bool,
 
     /// Whether source is a local file
     pub is_local: bool,
 
+    /// Whether this listen began because the player looped the track
+    pub is_repeat: bool,
+
+    /// Player `LoopStatus` (None, Track or Playlist)
+    pub loop_status: Option<String>,
+
+    /// Player `Shuffle` setting
+    pub shuffle: Option<bool>,
+
+    /// Player playback `Rate` (1.0 = normal speed)
+    pub rate: Option<f64>,
+
     /// Player name (bus name suffix)
     pub player_name: Option<String>,
 
@@ -60,10 +158,30 @@ pub struct TrackState {
     pub seek_forward_ms: i64,
     pub seek_backward_ms: i64,
     pub last_position_us: i64,
+    /// Played time at which `last_position_us` was observed
+    pub position_anchor: Duration,
+    /// Track positions heard so far, from `Position` samples and seeks
+    pub coverage: PositionCoverage,
 
     // Volume tracking
     pub app_volume: Option<f64>,
     pub system_volume: Option<f64>,
+    /// Time-weighted stream volume over the listen
+    pub app_volume_avg: VolumeAverage,
+    /// Time-weighted device volume over the listen
+    pub system_volume_avg: VolumeAverage,
+    /// Player `Volume` property, used when the sound server can't be queried
+    pub mpris_volume: Option<f64>,
+    /// Process ID owning the player's bus name
+    pub player_pid: Option<u32>,
+    /// Tags added by rules when the play is logged
+    pub tags: Vec<String>,
+    /// Whether private mode covered part of the listen
+    pub private: bool,
+    /// IANA time zone the listen ended in, stored with its end time
+    pub timezone: Option<String>,
+    /// Time source play time is measured against
+    pub clock: SharedClock,
 }
 
 impl Default for TrackState {
@@ -76,23 +194,53 @@ impl TrackState {
     /// Create a new empty track state
     #[must_use]
     pub fn new() -> Self {
+        Self::with_clock(clock::system())
+    }
+
+    /// Create a new empty track state timed by `clock`
+    #[must_use]
+    pub fn with_clock(clock: SharedClock) -> Self {
         Self {
             track: Track::default(),
             start_time: None,
             start_timestamp: None,
+            intervals: Vec::new(),
+            paused_at: None,
+            pause_count: 0,
+            paused_duration: Duration::ZERO,
             is_playing: false,
             is_local: false,
+            is_repeat: false,
+            loop_status: None,
+            shuffle: None,
+            rate: None,
             player_name: None,
             seek_count: 0,
             intro_skipped: false,
             seek_forward_ms: 0,
             seek_backward_ms: 0,
             last_position_us: 0,
+            position_anchor: Duration::ZERO,
+            coverage: PositionCoverage::new(),
             app_volume: None,
             system_volume: None,
+            app_volume_avg: VolumeAverage::new(),
+            system_volume_avg: VolumeAverage::new(),
+            mpris_volume: None,
+            player_pid: None,
+            tags: Vec::new(),
+            private: false,
+            timezone: None,
+            clock,
         }
     }
 
+    /// Check if a listen of a titled track has started
+    #[must_use]
+    pub const fn has_started(&self) -> bool {
+        self.track.title.is_some() && self.start_timestamp.is_some()
+    }
+
     /// Check if current play meets minimum thresholds for logging
     ///
     /// Rules (similar to Last.fm):
@@ -100,7 +248,7 @@ impl TrackState {
     /// - AND either: 50% of track, OR 4+ minutes played, OR duration unknown
     #[must_use]
     pub fn should_log(&self, min_seconds: u64, min_percent: f64) -> bool {
-        if self.track.title.is_none() || self.start_time.is_none() {
+        if self.track.title.is_none() || self.start_timestamp.is_none() {
             return false;
         }
 
@@ -128,25 +276,127 @@ impl TrackState {
         played_seconds_f64 >= duration_seconds * min_percent || played_seconds >= 240
     }
 
-    /// Get duration played
+    /// Get duration played, summed over all segments of the current listen
     #[must_use]
     pub fn played_duration(&self) -> Duration {
-        self.start_time
-            .map(|start| start.elapsed())
-            .unwrap_or_default()
+        let completed: Duration = self.intervals.iter().map(PlayInterval::duration).sum();
+        completed
+            + self
+                .start_time
+                .map(|start| self.clock.now().saturating_duration_since(start))
+                .unwrap_or_default()
     }
 
     /// Get milliseconds played
     #[must_use]
     pub fn played_ms(&self) -> i64 {
-        self.played_duration().as_millis() as i64
+        i64::try_from(self.played_duration().as_millis()).unwrap_or(i64::MAX)
+    }
+
+    /// Get milliseconds spent paused between segments
+    #[must_use]
+    pub fn paused_ms(&self) -> i64 {
+        i64::try_from(self.paused_duration.as_millis()).unwrap_or(i64::MAX)
+    }
+
+    /// Estimate the current playback position from the last known position
+    /// plus the time played since it was observed
+    #[must_use]
+    pub fn estimated_position_us(&self) -> i64 {
+        let since_anchor_us =
+            i64::try_from(self.track_time_since_anchor().as_micros()).unwrap_or(i64::MAX);
+        self.last_position_us.saturating_add(since_anchor_us)
+    }
+
+    /// Track time advanced since `last_position_us` was observed, scaled by the playback rate
+    fn track_time_since_anchor(&self) -> Duration {
+        self.played_duration()
+            .saturating_sub(self.position_anchor)
+            .mul_f64(self.playback_rate())
+    }
+
+    /// Playback rate, defaulting to normal speed when unknown or invalid
+    #[must_use]
+    pub fn playback_rate(&self) -> f64 {
+        self.rate
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .unwrap_or(1.0)
+    }
+
+    /// Update the playback rate, anchoring the position estimate at the old rate first
+    pub fn set_rate(&mut self, rate: f64) {
+        let position_us = self.capped_estimated_position_us();
+        self.reposition(position_us);
+        self.rate = Some(rate);
+    }
+
+    /// Record a `Position` sample reported by the player
+    pub fn on_position(&mut self, position_us: i64) {
+        let played = self.played_duration();
+        self.coverage.add_progress(
+            self.last_position_us,
+            position_us,
+            self.track_time_since_anchor(),
+        );
+
+        self.last_position_us = position_us;
+        self.position_anchor = played;
+    }
+
+    /// Position coverage including the stretch played since the last sample
+    #[must_use]
+    pub fn heard_coverage(&self) -> PositionCoverage {
+        let mut coverage = self.coverage.clone();
+        coverage.add_range(self.last_position_us, self.capped_estimated_position_us());
+        coverage
+    }
+
+    /// Get milliseconds of audio heard, based on track positions
+    #[must_use]
+    pub fn heard_ms(&self) -> i64 {
+        self.heard_coverage().heard_ms()
+    }
+
+    /// Get the number of distinct seconds of the track heard
+    #[must_use]
+    pub fn distinct_seconds_heard(&self) -> i64 {
+        self.heard_coverage().distinct_seconds()
+    }
+
+    /// Estimated position, capped at the track duration when known
+    #[must_use]
+    pub fn capped_estimated_position_us(&self) -> i64 {
+        let estimate = self.estimated_position_us();
+        match self.track.duration_us {
+            Some(duration_us) if duration_us > 0 => estimate.min(duration_us),
+            _ => estimate,
+        }
+    }
+
+    /// Classify how a listen that fell short of the play thresholds ended
+    #[must_use]
+    pub fn skip_reason(&self, player_closed: bool) -> SkipReason {
+        if player_closed {
+            return SkipReason::PlayerClosed;
+        }
+
+        match self.track.duration_us {
+            Some(duration_us)
+                if duration_us > 0
+                    && self.estimated_position_us() >= duration_us - TRACK_END_THRESHOLD_US =>
+            {
+                SkipReason::AutoAdvanced
+            }
+            _ => SkipReason::Skipped,
+        }
     }
 
     /// Handle a seek event
     pub fn on_seeked(&mut self, new_position_us: i64) {
         self.seek_count += 1;
 
-        let delta_us = new_position_us - self.last_position_us;
+        let position_us = self.capped_estimated_position_us();
+        let delta_us = new_position_us - position_us;
         let delta_ms = delta_us / 1000;
 
         if delta_us > 0 {
@@ -156,25 +406,179 @@ impl TrackState {
         }
 
         // Check if intro was skipped (seeked past first 15 seconds from near start)
-        if self.last_position_us < INTRO_START_THRESHOLD_US
-            && new_position_us > INTRO_SKIP_THRESHOLD_US
-        {
+        if position_us < INTRO_START_THRESHOLD_US && new_position_us > INTRO_SKIP_THRESHOLD_US {
             self.intro_skipped = true;
         }
 
+        self.reposition(new_position_us);
+    }
+
+    /// Move to a new position after a jump, closing the range heard up to it
+    pub fn reposition(&mut self, new_position_us: i64) {
+        let position_us = self.capped_estimated_position_us();
+        self.coverage.add_range(self.last_position_us, position_us);
+
         self.last_position_us = new_position_us;
+        self.position_anchor = self.played_duration();
     }
 
-    /// Start playback
+    /// Start or resume playback.
+    ///
+    /// Resuming a paused listen opens a new segment; time already played is kept.
     pub fn start_playing(&mut self) {
+        if self.is_playing && self.start_time.is_some() {
+            return;
+        }
+
+        let now = self.clock.now();
+        if let Some(paused_at) = self.paused_at.take() {
+            self.pause_count += 1;
+            self.paused_duration += now.saturating_duration_since(paused_at);
+        }
+
         self.is_playing = true;
-        self.start_time = Some(Instant::now());
-        self.start_timestamp = Some(Local::now());
+        self.start_time = Some(now);
+        if self.start_timestamp.is_none() {
+            self.start_timestamp = Some(self.clock.wall());
+        }
     }
 
-    /// Stop playback
+    /// Pause playback, closing the current segment
     pub fn stop_playing(&mut self) {
+        self.close_segment();
         self.is_playing = false;
+        if self.start_timestamp.is_some() {
+            self.paused_at = Some(self.clock.now());
+        }
+    }
+
+    /// Close the currently open play segment, if any, freezing the played time
+    pub fn close_segment(&mut self) {
+        self.close_segment_at(self.clock.now());
+    }
+
+    /// Close the currently open play segment at the given instant
+    fn close_segment_at(&mut self, end: Instant) {
+        if let Some(start) = self.start_time.take() {
+            self.intervals.push(PlayInterval {
+                start,
+                end: end.max(start),
+            });
+        }
+        self.settle_volume();
+    }
+
+    /// Check if the player is set to repeat the current track
+    #[must_use]
+    pub fn loops_track(&self) -> bool {
+        self.loop_status.as_deref() == Some(LOOP_STATUS_TRACK)
+    }
+
+    /// Check if a jump to `new_position_us` means the track wrapped around to
+    /// the start, either after reaching its end or while repeating the track
+    #[must_use]
+    pub fn is_loop_restart(&self, new_position_us: i64) -> bool {
+        if new_position_us >= self.last_position_us {
+            return false;
+        }
+
+        // The new position must be explainable by playing from zero since the last sample
+        let since_anchor_us =
+            i64::try_from(self.track_time_since_anchor().as_micros()).unwrap_or(i64::MAX);
+        if new_position_us > LOOP_RESTART_THRESHOLD_US.max(since_anchor_us) {
+            return false;
+        }
+
+        let position_us = self.estimated_position_us();
+        let reached_end = matches!(
+            self.track.duration_us,
+            Some(duration_us) if duration_us > 0
+                && position_us >= duration_us - TRACK_END_THRESHOLD_US
+        );
+
+        reached_end || (self.loops_track() && position_us > LOOP_RESTART_THRESHOLD_US)
+    }
+
+    /// Finish the current loop of a repeating track and start the next one.
+    ///
+    /// Returns the finished listen; `self` becomes a fresh listen flagged as a
+    /// repeat, already `new_position_us` into the track.
+    pub fn restart_loop(&mut self, new_position_us: i64) -> Self {
+        let now = self.clock.now();
+        let open = self
+            .start_time
+            .map(|start| now.saturating_duration_since(start))
+            .unwrap_or_default();
+        let into_loop =
+            Duration::from_micros(u64::try_from(new_position_us).unwrap_or(0)).min(open);
+        let boundary = now.checked_sub(into_loop).unwrap_or(now);
+
+        let mut finished = self.clone();
+        finished.close_segment_at(boundary);
+        let end_us = finished.capped_estimated_position_us();
+        finished.reposition(end_us);
+
+        self.reset_listen();
+        if self.is_playing {
+            self.start_time = Some(boundary);
+            self.start_timestamp = chrono::Duration::from_std(into_loop)
+                .ok()
+                .and_then(|offset| self.clock.wall().checked_sub_signed(offset));
+        }
+        self.is_repeat = true;
+        self.coverage.add_range(0, new_position_us);
+        self.last_position_us = new_position_us;
+        self.position_anchor = self.played_duration();
+
+        finished
+    }
+
+    /// Begin a fresh listen for the current track.
+    ///
+    /// Clears accumulated play time, pauses and seek tracking. If the player is
+    /// currently playing, a new segment is opened immediately.
+    pub fn reset_listen(&mut self) {
+        self.start_time = None;
+        self.start_timestamp = None;
+        self.intervals.clear();
+        self.paused_at = None;
+        self.pause_count = 0;
+        self.paused_duration = Duration::ZERO;
+
+        self.seek_count = 0;
+        self.intro_skipped = false;
+        self.seek_forward_ms = 0;
+        self.seek_backward_ms = 0;
+        self.last_position_us = 0;
+        self.position_anchor = Duration::ZERO;
+        self.coverage = PositionCoverage::new();
+        self.is_repeat = false;
+        self.private = false;
+        self.app_volume_avg.restart();
+        self.system_volume_avg.restart();
+        self.settle_volume();
+
+        if self.is_playing {
+            self.is_playing = false;
+            self.start_playing();
+        }
+    }
+
+    /// Record volume levels observed now, falling back to the MPRIS volume
+    /// for the stream when the sound server doesn't report one
+    pub fn record_volume(&mut self, levels: VolumeLevels) {
+        let at = self.played_duration();
+        self.app_volume_avg
+            .update(levels.app.or(self.mpris_volume), at);
+        self.system_volume_avg.update(levels.system, at);
+        self.settle_volume();
+    }
+
+    /// Set `app_volume`/`system_volume` to their averages over the listen so far
+    fn settle_volume(&mut self) {
+        let at = self.played_duration();
+        self.app_volume = self.app_volume_avg.average(at);
+        self.system_volume = self.system_volume_avg.average(at);
     }
 
     /// Calculate effective volume (app × system)
@@ -189,6 +593,59 @@ impl TrackState {
 }
 
 impl Track {
+    /// Value stored in the `plays.source` column
+    #[must_use]
+    pub const fn source(&self) -> Option<&'static str> {
+        if self.is_radio {
+            Some(SOURCE_RADIO)
+        } else {
+            None
+        }
+    }
+
+    /// Check if `other` is metadata for the same track, e.g. a later update
+    /// that only filled in art or length.
+    ///
+    /// Any of track ID, URL, title, artist or album known on both sides must
+    /// agree, and track ID, URL or title must actually match.
+    #[must_use]
+    pub fn is_same_track(&self, other: &Self) -> bool {
+        fn conflicts(a: Option<&String>, b: Option<&String>) -> bool {
+            matches!((a, b), (Some(a), Some(b)) if a != b)
+        }
+        fn matches(a: Option<&String>, b: Option<&String>) -> bool {
+            matches!((a, b), (Some(a), Some(b)) if a == b)
+        }
+
+        let fields = [
+            (self.track_id.as_ref(), other.track_id.as_ref()),
+            (self.file_path.as_ref(), other.file_path.as_ref()),
+            (self.title.as_ref(), other.title.as_ref()),
+            (self.artist.as_ref(), other.artist.as_ref()),
+            (self.album.as_ref(), other.album.as_ref()),
+        ];
+
+        !fields.iter().any(|&(a, b)| conflicts(a, b))
+            && fields[..3].iter().any(|&(a, b)| matches(a, b))
+    }
+
+    /// Key identifying the same song across players: normalized artist and
+    /// title, or the file path when there is no title
+    #[must_use]
+    pub fn identity(&self) -> Option<String> {
+        let normalize = |s: &str| s.trim().to_lowercase();
+        self.title.as_deref().map_or_else(
+            || self.file_path.clone(),
+            |title| {
+                Some(format!(
+                    "{}\u{1f}{}",
+                    normalize(self.artist.as_deref().unwrap_or_default()),
+                    normalize(title)
+                ))
+            },
+        )
+    }
+
     /// Check if this track appears to be from a local file
     #[must_use]
     pub fn is_local_source(&self, local_players: &[String], player_name: Option<&str>) -> bool {
@@ -227,13 +684,19 @@ impl Track {
 mod tests {
     use super::*;
 
-    fn make_playing_state(title: &str, duration_us: Option<i64>) -> TrackState {
-        let mut state = TrackState::new();
+    use std::sync::Arc;
+
+    use crate::clock::{Clock, ManualClock};
+
+    /// A playing listen on a clock that only moves when the test advances it
+    fn make_playing_state(title: &str, duration_us: Option<i64>) -> (TrackState, Arc<ManualClock>) {
+        let clock = ManualClock::new(Local::now());
+        let mut state = TrackState::with_clock(clock.clone());
         state.track.title = Some(title.to_string());
         state.track.duration_us = duration_us;
         state.is_playing = true;
         state.start_playing();
-        state
+        (state, clock)
     }
 
     #[test]
@@ -255,39 +718,38 @@ mod tests {
 
     #[test]
     fn test_should_log_under_min_seconds() {
-        let state = make_playing_state("Test", Some(300_000_000)); // 5 min track
+        let (state, _clock) = make_playing_state("Test", Some(300_000_000)); // 5 min track
         // Just started, hasn't played 30 seconds
         assert!(!state.should_log(30, 0.5));
     }
 
     #[test]
     fn test_should_log_unknown_duration_only_needs_min_seconds() {
-        let mut state = make_playing_state("Test", None);
-        // Simulate time passing by manipulating start_time
-        state.start_time = Some(Instant::now() - Duration::from_secs(35));
+        let (state, clock) = make_playing_state("Test", None);
+        clock.advance(Duration::from_secs(35));
         // With unknown duration, only min_seconds matters
         assert!(state.should_log(30, 0.5));
     }
 
     #[test]
     fn test_should_log_zero_duration_only_needs_min_seconds() {
-        let mut state = make_playing_state("Test", Some(0));
-        state.start_time = Some(Instant::now() - Duration::from_secs(35));
+        let (state, clock) = make_playing_state("Test", Some(0));
+        clock.advance(Duration::from_secs(35));
         assert!(state.should_log(30, 0.5));
     }
 
     #[test]
     fn test_should_log_negative_duration_only_needs_min_seconds() {
-        let mut state = make_playing_state("Test", Some(-1000));
-        state.start_time = Some(Instant::now() - Duration::from_secs(35));
+        let (state, clock) = make_playing_state("Test", Some(-1000));
+        clock.advance(Duration::from_secs(35));
         assert!(state.should_log(30, 0.5));
     }
 
     #[test]
     fn test_should_log_50_percent_rule() {
         // 60 second track, need 50% = 30 seconds
-        let mut state = make_playing_state("Test", Some(60_000_000));
-        state.start_time = Some(Instant::now() - Duration::from_secs(35));
+        let (state, clock) = make_playing_state("Test", Some(60_000_000));
+        clock.advance(Duration::from_secs(35));
         // Played 35s of 60s track (58%) - should log
         assert!(state.should_log(30, 0.5));
     }
@@ -295,29 +757,250 @@ mod tests {
     #[test]
     fn test_should_log_under_50_percent_under_4_minutes() {
         // 10 minute track (600 seconds), 50% = 300 seconds = 5 minutes
-        let mut state = make_playing_state("Test", Some(600_000_000));
+        let (state, clock) = make_playing_state("Test", Some(600_000_000));
         // Played 200 seconds (33%) - under 50% AND under 4 minutes
-        state.start_time = Some(Instant::now() - Duration::from_secs(200));
+        clock.advance(Duration::from_secs(200));
         assert!(!state.should_log(30, 0.5));
 
         // Played 239 seconds (39.8%) - still under 50% and just under 4 minutes
-        state.start_time = Some(Instant::now() - Duration::from_secs(239));
+        clock.advance(Duration::from_secs(39));
         assert!(!state.should_log(30, 0.5));
 
         // Played 240 seconds (40%) - under 50% but exactly 4 minutes
-        state.start_time = Some(Instant::now() - Duration::from_secs(240));
+        clock.advance(Duration::from_secs(1));
         assert!(state.should_log(30, 0.5)); // 240 >= 240, triggers 4-min rule
     }
 
     #[test]
     fn test_should_log_4_minute_rule() {
         // Very long track (1 hour), 50% = 30 minutes
-        let mut state = make_playing_state("Test", Some(3600_000_000));
+        let (state, clock) = make_playing_state("Test", Some(3600_000_000));
         // Played 4 minutes = 240 seconds
-        state.start_time = Some(Instant::now() - Duration::from_secs(240));
+        clock.advance(Duration::from_secs(240));
         assert!(state.should_log(30, 0.5));
     }
 
+    #[test]
+    fn test_pause_resume_accumulates_played_time() {
+        let (mut state, clock) = make_playing_state("Test", Some(600_000_000));
+        clock.advance(Duration::from_secs(20));
+        state.stop_playing();
+        assert!(!state.is_playing);
+        assert!(state.start_time.is_none());
+        assert_eq!(state.intervals.len(), 1);
+
+        clock.advance(Duration::from_secs(5));
+        state.start_playing();
+        clock.advance(Duration::from_secs(15));
+
+        // 20s + 15s across two segments, with 5s paused in between
+        assert_eq!(state.played_duration(), Duration::from_secs(35));
+        assert!(state.should_log(30, 0.0));
+        assert_eq!(state.pause_count, 1);
+        assert_eq!(state.paused_ms(), 5_000);
+    }
+
+    #[test]
+    fn test_paused_state_still_loggable() {
+        let (mut state, clock) = make_playing_state("Test", None);
+        clock.advance(Duration::from_secs(35));
+        state.stop_playing();
+        clock.advance(Duration::from_secs(60));
+        assert!(state.should_log(30, 0.5));
+        assert_eq!(state.played_duration(), Duration::from_secs(35));
+        // A trailing pause that was never resumed is not counted
+        assert_eq!(state.pause_count, 0);
+    }
+
+    #[test]
+    fn test_resume_keeps_start_timestamp() {
+        let (mut state, clock) = make_playing_state("Test", None);
+        let started = state.start_timestamp;
+        state.stop_playing();
+        clock.advance(Duration::from_secs(5));
+        state.start_playing();
+        assert_eq!(state.start_timestamp, started);
+    }
+
+    #[test]
+    fn test_paused_duration_accumulates() {
+        let (mut state, clock) = make_playing_state("Test", None);
+        state.stop_playing();
+        clock.advance(Duration::from_secs(10));
+        state.start_playing();
+        assert_eq!(state.pause_count, 1);
+        assert_eq!(state.paused_ms(), 10_000);
+        assert!(state.paused_at.is_none());
+    }
+
+    #[test]
+    fn test_reset_listen_clears_accumulated_time() {
+        let (mut state, clock) = make_playing_state("Test", None);
+        clock.advance(Duration::from_secs(45));
+        state.stop_playing();
+        state.start_playing();
+        state.seek_count = 3;
+
+        state.reset_listen();
+        assert!(state.is_playing);
+        assert!(state.intervals.is_empty());
+        assert_eq!(state.pause_count, 0);
+        assert_eq!(state.seek_count, 0);
+        assert_eq!(state.played_duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_skip_reason_mid_track_is_skipped() {
+        let (state, clock) = make_playing_state("Test", Some(200_000_000));
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(state.skip_reason(false), SkipReason::Skipped);
+    }
+
+    #[test]
+    fn test_skip_reason_near_end_is_auto_advance() {
+        // 20 second track played through to the end
+        let (state, clock) = make_playing_state("Test", Some(20_000_000));
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(state.skip_reason(false), SkipReason::AutoAdvanced);
+    }
+
+    #[test]
+    fn test_skip_reason_uses_seek_position() {
+        let (mut state, _clock) = make_playing_state("Test", Some(200_000_000));
+        state.on_seeked(198_000_000);
+        assert_eq!(state.skip_reason(false), SkipReason::AutoAdvanced);
+        assert_eq!(state.skip_reason(true), SkipReason::PlayerClosed);
+    }
+
+    #[test]
+    fn test_on_position_builds_coverage() {
+        let (mut state, clock) = make_playing_state("Test", Some(200_000_000));
+        clock.advance(Duration::from_secs(10));
+        state.on_position(10_000_000);
+        assert_eq!(state.coverage.heard_ms(), 10_000);
+        assert_eq!(state.last_position_us, 10_000_000);
+    }
+
+    #[test]
+    fn test_heard_ms_excludes_skipped_section() {
+        let (mut state, clock) = make_playing_state("Test", Some(200_000_000));
+        clock.advance(Duration::from_secs(10));
+        state.on_position(10_000_000);
+        // Seek far ahead, then hear 5 more seconds
+        state.on_seeked(100_000_000);
+        clock.advance(Duration::from_secs(5));
+        state.on_position(105_000_000);
+
+        assert_eq!(state.heard_ms(), 15_000);
+        assert_eq!(state.distinct_seconds_heard(), 15);
+    }
+
+    #[test]
+    fn test_heard_ms_ignores_stall() {
+        let (mut state, clock) = make_playing_state("Test", Some(200_000_000));
+        // 30 seconds of wall-clock play, but the player only advanced 5 seconds
+        clock.advance(Duration::from_secs(30));
+        state.on_position(5_000_000);
+        assert_eq!(state.played_ms(), 30_000);
+        assert!(state.heard_ms() < 6_000);
+    }
+
+    #[test]
+    fn test_is_loop_restart_after_reaching_end() {
+        // 60 second track, last sample 2 seconds before the end
+        let (mut state, clock) = make_playing_state("Test", Some(60_000_000));
+        clock.advance(Duration::from_secs(62));
+        state.on_position(58_000_000);
+        // Played on past the end and wrapped to 1s
+        clock.advance(Duration::from_secs(3));
+        assert!(state.is_loop_restart(1_000_000));
+    }
+
+    #[test]
+    fn test_is_loop_restart_rejects_manual_seek_to_start() {
+        let (mut state, clock) = make_playing_state("Test", Some(200_000_000));
+        clock.advance(Duration::from_secs(61));
+        state.on_position(60_000_000);
+        assert!(!state.is_loop_restart(0));
+
+        // With repeat-one enabled, restarting the track counts as a loop
+        state.loop_status = Some("Track".to_string());
+        assert!(state.is_loop_restart(0));
+    }
+
+    #[test]
+    fn test_is_loop_restart_ignores_forward_jumps() {
+        let (mut state, _clock) = make_playing_state("Test", Some(60_000_000));
+        state.loop_status = Some("Track".to_string());
+        state.last_position_us = 10_000_000;
+        assert!(!state.is_loop_restart(30_000_000));
+    }
+
+    #[test]
+    fn test_restart_loop_splits_listens() {
+        let (mut state, clock) = make_playing_state("Test", Some(60_000_000));
+        clock.advance(Duration::from_secs(62));
+        state.on_position(58_000_000);
+
+        // The loop wrapped 2 seconds ago
+        let finished = state.restart_loop(2_000_000);
+        assert!(!finished.is_repeat);
+        assert!(finished.start_time.is_none());
+        assert_eq!(finished.played_duration(), Duration::from_secs(60));
+
+        assert!(state.is_repeat);
+        assert!(state.is_playing);
+        assert_eq!(state.last_position_us, 2_000_000);
+        assert_eq!(state.played_duration(), Duration::from_secs(2));
+        assert_eq!(
+            state.start_timestamp,
+            Some(clock.wall() - chrono::Duration::seconds(2))
+        );
+        assert_eq!(state.track.title.as_deref(), Some("Test"));
+    }
+
+    #[test]
+    fn test_rate_scales_position_estimate() {
+        let (mut state, clock) = make_playing_state("Test", Some(600_000_000));
+        state.rate = Some(2.0);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(state.estimated_position_us(), 20_000_000);
+
+        // 10 seconds of wall-clock time covered 20 seconds of track at double speed
+        state.on_position(20_000_000);
+        assert_eq!(state.coverage.heard_ms(), 20_000);
+    }
+
+    #[test]
+    fn test_set_rate_anchors_position() {
+        let (mut state, clock) = make_playing_state("Test", Some(600_000_000));
+        clock.advance(Duration::from_secs(10));
+        state.set_rate(2.0);
+        assert_eq!(state.last_position_us, 10_000_000);
+        assert_eq!(state.rate, Some(2.0));
+        assert_eq!(state.coverage.heard_ms(), 10_000);
+    }
+
+    #[test]
+    fn test_playback_rate_ignores_invalid() {
+        let mut state = TrackState::new();
+        assert!((state.playback_rate() - 1.0).abs() < f64::EPSILON);
+        state.rate = Some(0.0);
+        assert!((state.playback_rate() - 1.0).abs() < f64::EPSILON);
+        state.rate = Some(1.5);
+        assert!((state.playback_rate() - 1.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_has_started() {
+        let mut state = TrackState::new();
+        assert!(!state.has_started());
+        state.start_playing();
+        assert!(!state.has_started());
+        state.track.title = Some("Test".to_string());
+        assert!(state.has_started());
+    }
+
     #[test]
     fn test_on_seeked_tracks_forward_seek() {
         let mut state = TrackState::new();
@@ -370,6 +1053,70 @@ mod tests {
         assert!(track.is_local_source(&[], None));
     }
 
+    #[test]
+    fn test_is_same_track() {
+        let track = Track {
+            track_id: Some("/org/mpris/track/1".into()),
+            title: Some("Song".into()),
+            artist: Some("Artist".into()),
+            ..Default::default()
+        };
+
+        // Late art URL and length on the same track
+        let update = Track {
+            art_url: Some("file:///cover.jpg".into()),
+            duration_us: Some(180_000_000),
+            album: Some("Album".into()),
+            ..track.clone()
+        };
+        assert!(track.is_same_track(&update));
+
+        // Track ID known before the title arrives
+        let partial = Track {
+            track_id: track.track_id.clone(),
+            ..Default::default()
+        };
+        assert!(partial.is_same_track(&track));
+
+        // Different track ID, or a constant track ID with a new title
+        let next = Track {
+            track_id: Some("/org/mpris/track/2".into()),
+            ..track.clone()
+        };
+        assert!(!track.is_same_track(&next));
+        let retitled = Track {
+            title: Some("Other Song".into()),
+            ..track.clone()
+        };
+        assert!(!track.is_same_track(&retitled));
+
+        // Nothing to match on
+        assert!(!Track::default().is_same_track(&Track::default()));
+    }
+
+    #[test]
+    fn test_track_identity() {
+        let track = Track {
+            title: Some("Song ".into()),
+            artist: Some("Artist".into()),
+            ..Default::default()
+        };
+        let same = Track {
+            title: Some("song".into()),
+            artist: Some("ARTIST".into()),
+            album: Some("Album".into()),
+            ..Default::default()
+        };
+        assert_eq!(track.identity(), same.identity());
+
+        let untitled = Track {
+            file_path: Some("file:///music/a.flac".into()),
+            ..Default::default()
+        };
+        assert_eq!(untitled.identity().as_deref(), Some("file:///music/a.flac"));
+        assert_eq!(Track::default().identity(), None);
+    }
+
     #[test]
     fn test_is_local_source_absolute_path() {
         let mut track = Track::default();
@@ -393,6 +1140,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_record_volume_falls_back_to_mpris() {
+        let mut state = TrackState::new();
+        state.mpris_volume = Some(0.6);
+        state.record_volume(VolumeLevels {
+            app: None,
+            system: Some(0.5),
+        });
+        assert_eq!(state.app_volume, Some(0.6));
+        assert_eq!(state.system_volume, Some(0.5));
+        assert_eq!(state.effective_volume(), Some(0.3));
+
+        // A new listen starts from the last known levels
+        state.reset_listen();
+        assert_eq!(state.app_volume, Some(0.6));
+        assert_eq!(state.system_volume, Some(0.5));
+    }
+
     #[test]
     fn test_effective_volume() {
         let mut state = TrackState::new();
@@ -0,0 +1,431 @@
+//! Volume tracking (PulseAudio/PipeWire)
+//!
+//! Reads the player's sink-input volume and the volume of the sink it plays
+//! on. Requires the `pulse` feature; without it no levels are ever reported
+//! and the monitor falls back to the MPRIS `Volume` property.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Volume levels observed at one moment (1.0 = 100%)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct VolumeLevels {
+    /// Volume of the player's own stream
+    pub app: Option<f64>,
+    /// Volume of the output device
+    pub system: Option<f64>,
+}
+
+/// Time-weighted average of a volume level over a listen.
+///
+/// Times are played durations, so pauses don't count towards the average.
+#[derive(Debug, Clone, Default)]
+pub struct VolumeAverage {
+    /// Latest known level and the played time it was observed at
+    current: Option<(f64, Duration)>,
+    /// Sum of level × seconds over completed spans
+    weighted_sum: f64,
+    /// Seconds covered by completed spans
+    weighted_secs: f64,
+}
+
+impl VolumeAverage {
+    /// Create an empty average
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            current: None,
+            weighted_sum: 0.0,
+            weighted_secs: 0.0,
+        }
+    }
+
+    /// Record the level observed at played time `at` (None = unknown from here on)
+    pub fn update(&mut self, level: Option<f64>, at: Duration) {
+        self.fold_until(at);
+        self.current = level.map(|level| (level, at));
+    }
+
+    /// Average level up to played time `at`
+    #[must_use]
+    pub fn average(&self, at: Duration) -> Option<f64> {
+        let (mut sum, mut secs) = (self.weighted_sum, self.weighted_secs);
+        if let Some((level, since)) = self.current {
+            let span = at.saturating_sub(since).as_secs_f64();
+            sum += level * span;
+            secs += span;
+        }
+
+        if secs > 0.0 {
+            Some(sum / secs)
+        } else {
+            // Only an instantaneous sample so far
+            self.current.map(|(level, _)| level)
+        }
+    }
+
+    /// Start averaging a new listen, carrying over the latest known level
+    pub fn restart(&mut self) {
+        *self = Self {
+            current: self.current.map(|(level, _)| (level, Duration::ZERO)),
+            ..Self::new()
+        };
+    }
+
+    /// Add the span of the current level up to `at` to the weighted sums
+    fn fold_until(&mut self, at: Duration) {
+        if let Some((level, since)) = self.current {
+            let span = at.saturating_sub(since).as_secs_f64();
+            self.weighted_sum += level * span;
+            self.weighted_secs += span;
+        }
+    }
+}
+
+/// Guess the sound-server application name from an MPRIS player name,
+/// e.g. `io.bassi.Amberol` -> `Amberol`, `firefox.instance_1_23` -> `firefox`
+#[must_use]
+pub fn app_name_for_player(player_name: &str) -> &str {
+    let name = player_name
+        .split_once(".instance")
+        .map_or(player_name, |(name, _)| name);
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Convert a sound server volume to a fraction of normal volume
+#[cfg(feature = "pulse")]
+fn volume_fraction(volume: libpulse_binding::volume::Volume, muted: bool) -> f64 {
+    if muted {
+        0.0
+    } else {
+        f64::from(volume.0) / f64::from(libpulse_binding::volume::Volume::NORMAL.0)
+    }
+}
+
+#[cfg(feature = "pulse")]
+mod pulse {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc as std_mpsc;
+
+    use libpulse_binding::callbacks::ListResult;
+    use libpulse_binding::context::{Context, FlagSet, State};
+    use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
+    use libpulse_binding::operation::{Operation, State as OperationState};
+    use libpulse_binding::proplist::Proplist;
+    use tokio::sync::oneshot;
+    use tracing::debug;
+
+    use super::{volume_fraction, VolumeLevels};
+
+    /// Client name shown by the sound server
+    const CLIENT_NAME: &str = "music-analytics";
+
+    /// A request for the volume levels of one player
+    pub(super) struct Request {
+        pub pid: Option<u32>,
+        pub app_name: Option<String>,
+        pub reply: oneshot::Sender<Option<VolumeLevels>>,
+    }
+
+    /// A sink input (playback stream) as seen by the sound server
+    struct SinkInput {
+        sink: u32,
+        volume: f64,
+        corked: bool,
+        pid: Option<u32>,
+        app_name: Option<String>,
+        binary: Option<String>,
+    }
+
+    /// Connection to the sound server, owned by the worker thread
+    struct Connection {
+        mainloop: Mainloop,
+        context: Context,
+    }
+
+    impl Connection {
+        fn connect() -> Option<Self> {
+            let mut mainloop = Mainloop::new()?;
+            let mut proplist = Proplist::new()?;
+            proplist
+                .set_str(
+                    libpulse_binding::proplist::properties::APPLICATION_NAME,
+                    CLIENT_NAME,
+                )
+                .ok()?;
+            let mut context = Context::new_with_proplist(&mainloop, CLIENT_NAME, &proplist)?;
+            context.connect(None, FlagSet::NOAUTOSPAWN, None).ok()?;
+
+            loop {
+                if !matches!(mainloop.iterate(true), IterateResult::Success(_)) {
+                    return None;
+                }
+                match context.get_state() {
+                    State::Ready => break,
+                    State::Failed | State::Terminated => return None,
+                    _ => {}
+                }
+            }
+
+            Some(Self { mainloop, context })
+        }
+
+        fn is_ready(&self) -> bool {
+            self.context.get_state() == State::Ready
+        }
+
+        /// Run the mainloop until an operation completes
+        fn wait<F: ?Sized>(&mut self, operation: &Operation<F>) -> bool {
+            while operation.get_state() == OperationState::Running {
+                if !matches!(self.mainloop.iterate(true), IterateResult::Success(_)) {
+                    return false;
+                }
+            }
+            operation.get_state() == OperationState::Done
+        }
+
+        fn sink_inputs(&mut self) -> Option<Vec<SinkInput>> {
+            let inputs = Rc::new(RefCell::new(Vec::new()));
+            let collected = Rc::clone(&inputs);
+            let operation = self
+                .context
+                .introspect()
+                .get_sink_input_info_list(move |result| {
+                    if let ListResult::Item(info) = result {
+                        let props = &info.proplist;
+                        collected.borrow_mut().push(SinkInput {
+                            sink: info.sink,
+                            volume: volume_fraction(info.volume.avg(), info.mute),
+                            corked: info.corked,
+                            pid: props
+                                .get_str("application.process.id")
+                                .and_then(|pid| pid.parse().ok()),
+                            app_name: props.get_str("application.name"),
+                            binary: props.get_str("application.process.binary"),
+                        });
+                    }
+                });
+            if !self.wait(&operation) {
+                return None;
+            }
+            Some(inputs.take())
+        }
+
+        fn sink_volume(&mut self, index: u32) -> Option<f64> {
+            let volume = Rc::new(RefCell::new(None));
+            let found = Rc::clone(&volume);
+            let operation =
+                self.context
+                    .introspect()
+                    .get_sink_info_by_index(index, move |result| {
+                        if let ListResult::Item(info) = result {
+                            *found.borrow_mut() =
+                                Some(volume_fraction(info.volume.avg(), info.mute));
+                        }
+                    });
+            self.wait(&operation);
+            volume.take()
+        }
+
+        fn default_sink_volume(&mut self) -> Option<f64> {
+            let name = Rc::new(RefCell::new(None));
+            let found = Rc::clone(&name);
+            let operation = self.context.introspect().get_server_info(move |info| {
+                *found.borrow_mut() = info.default_sink_name.as_ref().map(ToString::to_string);
+            });
+            self.wait(&operation);
+            let name = name.take()?;
+
+            let volume = Rc::new(RefCell::new(None));
+            let found = Rc::clone(&volume);
+            let operation = self
+                .context
+                .introspect()
+                .get_sink_info_by_name(&name, move |result| {
+                    if let ListResult::Item(info) = result {
+                        *found.borrow_mut() = Some(volume_fraction(info.volume.avg(), info.mute));
+                    }
+                });
+            self.wait(&operation);
+            volume.take()
+        }
+
+        /// Look up the volume levels for a player's stream
+        fn levels(&mut self, pid: Option<u32>, app_name: Option<&str>) -> Option<VolumeLevels> {
+            let inputs = self.sink_inputs()?;
+            let matches_app = |input: &SinkInput| {
+                app_name.is_some_and(|name| {
+                    [input.app_name.as_deref(), input.binary.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .any(|candidate| candidate.eq_ignore_ascii_case(name))
+                })
+            };
+
+            // Prefer streams owned by the player's process, then by name;
+            // an uncorked (actually playing) stream wins over a paused one
+            let mut candidates: Vec<&SinkInput> = inputs
+                .iter()
+                .filter(|input| pid.is_some() && input.pid == pid)
+                .collect();
+            if candidates.is_empty() {
+                candidates = inputs.iter().filter(|input| matches_app(input)).collect();
+            }
+            let input = candidates
+                .iter()
+                .find(|input| !input.corked)
+                .or_else(|| candidates.first())
+                .copied();
+
+            let system = input
+                .and_then(|input| self.sink_volume(input.sink))
+                .or_else(|| self.default_sink_volume());
+
+            Some(VolumeLevels {
+                app: input.map(|input| input.volume),
+                system,
+            })
+        }
+    }
+
+    /// Serve volume requests until every sender is dropped
+    pub(super) fn run(requests: &std_mpsc::Receiver<Request>) {
+        let mut connection: Option<Connection> = None;
+
+        for request in requests {
+            if !connection.as_ref().is_some_and(Connection::is_ready) {
+                connection = Connection::connect();
+                if connection.is_none() {
+                    debug!("PulseAudio not available");
+                }
+            }
+
+            let levels = connection
+                .as_mut()
+                .and_then(|conn| conn.levels(request.pid, request.app_name.as_deref()));
+            let _ = request.reply.send(levels);
+        }
+    }
+}
+
+/// Reads stream and device volumes from PulseAudio/PipeWire.
+///
+/// The Pulse client API is not thread-safe, so queries are served by a dedicated
+/// worker thread.
+#[derive(Debug, Clone)]
+pub struct VolumeReader {
+    #[cfg(feature = "pulse")]
+    requests: Option<std::sync::mpsc::Sender<pulse::Request>>,
+}
+
+impl VolumeReader {
+    /// Start the volume reader
+    #[must_use]
+    #[cfg_attr(not(feature = "pulse"), allow(clippy::missing_const_for_fn))]
+    pub fn new() -> Self {
+        #[cfg(feature = "pulse")]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let spawned = std::thread::Builder::new()
+                .name("pulse-volume".into())
+                .spawn(move || pulse::run(&rx));
+            if let Err(e) = &spawned {
+                tracing::warn!("Failed to start volume reader: {}", e);
+            }
+            Self {
+                requests: spawned.ok().map(|_| tx),
+            }
+        }
+
+        #[cfg(not(feature = "pulse"))]
+        Self {}
+    }
+
+    /// Read the volume levels for the player with the given process ID or
+    /// application name. Returns None if the sound server can't be queried.
+    #[cfg_attr(not(feature = "pulse"), allow(clippy::unused_async))]
+    pub async fn levels(&self, pid: Option<u32>, app_name: Option<&str>) -> Option<VolumeLevels> {
+        #[cfg(feature = "pulse")]
+        {
+            let (reply, response) = tokio::sync::oneshot::channel();
+            self.requests
+                .as_ref()?
+                .send(pulse::Request {
+                    pid,
+                    app_name: app_name.map(String::from),
+                    reply,
+                })
+                .ok()?;
+            tokio::time::timeout(Duration::from_secs(2), response)
+                .await
+                .ok()?
+                .ok()
+                .flatten()
+        }
+
+        #[cfg(not(feature = "pulse"))]
+        {
+            let _ = (pid, app_name);
+            None
+        }
+    }
+}
+
+impl Default for VolumeReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_weights_by_time() {
+        let mut avg = VolumeAverage::new();
+        assert_eq!(avg.average(Duration::from_secs(10)), None);
+
+        avg.update(Some(1.0), Duration::ZERO);
+        avg.update(Some(0.5), Duration::from_secs(30));
+        // 30s at 100%, 10s at 50%
+        let value = avg.average(Duration::from_secs(40)).unwrap();
+        assert!((value - 0.875).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_average_single_sample() {
+        let mut avg = VolumeAverage::new();
+        avg.update(Some(0.4), Duration::from_secs(5));
+        assert_eq!(avg.average(Duration::from_secs(5)), Some(0.4));
+    }
+
+    #[test]
+    fn test_average_skips_unknown_spans() {
+        let mut avg = VolumeAverage::new();
+        avg.update(Some(0.8), Duration::ZERO);
+        avg.update(None, Duration::from_secs(10));
+        avg.update(Some(0.2), Duration::from_secs(50));
+        // 10s at 80%, unknown for 40s, then 10s at 20%
+        let value = avg.average(Duration::from_secs(61)).unwrap();
+        assert!((value - 0.8f64.mul_add(10.0, 0.2 * 11.0) / 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_app_name_for_player() {
+        assert_eq!(app_name_for_player("spotify"), "spotify");
+        assert_eq!(app_name_for_player("io.bassi.Amberol"), "Amberol");
+        assert_eq!(app_name_for_player("firefox.instance_1_23"), "firefox");
+    }
+
+    #[test]
+    fn test_restart_carries_latest_level() {
+        let mut avg = VolumeAverage::new();
+        avg.update(Some(1.0), Duration::ZERO);
+        avg.update(Some(0.3), Duration::from_secs(100));
+        avg.restart();
+        assert_eq!(avg.average(Duration::from_secs(20)), Some(0.3));
+    }
+}